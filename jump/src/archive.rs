@@ -8,20 +8,91 @@ use log::debug;
 use logging_timer::time;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
+use zip::DateTime;
+
+use crate::config::ArchiveType;
+
+/// Reads the `SOURCE_DATE_EPOCH` env var, if set, as the seconds since the Unix epoch to stamp
+/// archive entries with.
+///
+/// See: https://reproducible-builds.org/specs/source-date-epoch/
+pub fn source_date_epoch() -> Result<Option<u64>, String> {
+    let Some(value) = std::env::var_os("SOURCE_DATE_EPOCH") else {
+        return Ok(None);
+    };
+    let value = value
+        .into_string()
+        .map_err(|value| format!("The SOURCE_DATE_EPOCH is not a valid utf-8 string: {value:?}"))?;
+    value.parse().map(Some).map_err(|e| {
+        format!("The SOURCE_DATE_EPOCH must be a non-negative integer; given: {value}: {e}")
+    })
+}
+
+// N.B.: Zip timestamps are local date times with no time zone that can only represent the years
+// 1980 through 2107; so we use UTC and clamp earlier epochs to the zip epoch.
+fn zip_date_time(epoch: u64) -> Result<DateTime, String> {
+    let (days, secs) = (epoch / 86_400, epoch % 86_400);
+    // See: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    if year < 1980 {
+        return Ok(DateTime::default());
+    }
+    u16::try_from(year)
+        .ok()
+        .and_then(|year| {
+            DateTime::from_date_and_time(
+                year,
+                month as u8,
+                day as u8,
+                (secs / 3_600) as u8,
+                (secs % 3_600 / 60) as u8,
+                (secs % 60) as u8,
+            )
+            .ok()
+        })
+        .ok_or_else(|| {
+            format!("The source date epoch {epoch} is past the last date a zip can record.")
+        })
+}
+
+// N.B.: Only the file mode is carried over from the file metadata into a zip entry and, since we
+// build zip without its "time" feature, entries are stamped with the zip epoch of 1980-01-01 unless
+// a source date epoch is given.
+fn fixed_options(source_date_epoch: Option<u64>) -> Result<FileOptions, String> {
+    let options = FileOptions::default();
+    match source_date_epoch {
+        Some(epoch) => Ok(options.last_modified_time(zip_date_time(epoch)?)),
+        None => Ok(options),
+    }
+}
 
 #[cfg(not(target_family = "unix"))]
-pub fn create_options(_metadata: &Metadata) -> Result<FileOptions, String> {
-    Ok(FileOptions::default())
+pub fn create_options(
+    _metadata: &Metadata,
+    source_date_epoch: Option<u64>,
+) -> Result<FileOptions, String> {
+    fixed_options(source_date_epoch)
 }
 
 #[cfg(target_family = "unix")]
-pub fn create_options(metadata: &Metadata) -> Result<FileOptions, String> {
+pub fn create_options(
+    metadata: &Metadata,
+    source_date_epoch: Option<u64>,
+) -> Result<FileOptions, String> {
     use std::os::unix::fs::PermissionsExt;
     let perms = metadata.permissions();
-    Ok(FileOptions::default().unix_permissions(perms.mode()))
+    Ok(fixed_options(source_date_epoch)?.unix_permissions(perms.mode()))
 }
 
-fn create_zip(dir: &Path) -> Result<PathBuf, String> {
+fn create_zip(dir: &Path, source_date_epoch: Option<u64>) -> Result<PathBuf, String> {
     let zip_path = dir.with_extension("zip");
     let mut zip = zip::ZipWriter::new(
         std::fs::OpenOptions::new()
@@ -36,7 +107,11 @@ fn create_zip(dir: &Path) -> Result<PathBuf, String> {
                 )
             })?,
     );
-    for entry in WalkDir::new(dir).contents_first(false).follow_links(true) {
+    for entry in WalkDir::new(dir)
+        .contents_first(false)
+        .follow_links(true)
+        .sort_by_file_name()
+    {
         let entry = entry.map_err(|e| {
             format!(
                 "Walk failed while trying to create a zip of {dir}: {e}",
@@ -60,12 +135,15 @@ fn create_zip(dir: &Path) -> Result<PathBuf, String> {
             .collect::<Result<Vec<_>, _>>()?
             // N.B.: Zip archive entry names always use / as the directory separator.
             .join("/");
-        let options = create_options(&entry.metadata().map_err(|e| {
-            format!(
-                "Failed to read metadata for {path}: {e}",
-                path = entry.path().display()
-            )
-        })?)?;
+        let options = create_options(
+            &entry.metadata().map_err(|e| {
+                format!(
+                    "Failed to read metadata for {path}: {e}",
+                    path = entry.path().display()
+                )
+            })?,
+            source_date_epoch,
+        )?;
         if entry.path().is_dir() {
             debug!("Adding dir entry {entry}", entry = rel_path.display());
             zip.add_directory(entry_name, options)
@@ -90,20 +168,161 @@ fn create_zip(dir: &Path) -> Result<PathBuf, String> {
     Ok(zip_path)
 }
 
+// N.B.: Unlike a zip, a tar records exact file modes and stores symlinks as symlinks; so the
+// directory is reproduced faithfully when the tar is unpacked. A tar also records ownership and
+// modification times; so those are fixed when a source date epoch is given.
+fn create_tar(dir: &Path, source_date_epoch: Option<u64>) -> Result<PathBuf, String> {
+    let tar_path = dir.with_extension("tar");
+    let mut tar = tar::Builder::new(
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tar_path)
+            .map_err(|e| {
+                format!(
+                    "Failed to open {tar} for packing {dir} into: {e}",
+                    tar = tar_path.display(),
+                    dir = dir.display()
+                )
+            })?,
+    );
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(|e| {
+            format!(
+                "Walk failed while trying to create a tar of {dir}: {e}",
+                dir = dir.display()
+            )
+        })?;
+        if entry.path() == dir {
+            continue;
+        }
+        let rel_path = entry
+            .path()
+            .strip_prefix(dir)
+            .map_err(|e| format!("Failed to relativize archive path: {e}"))?;
+        let metadata = entry.metadata().map_err(|e| {
+            format!(
+                "Failed to read metadata for {path}: {e}",
+                path = entry.path().display()
+            )
+        })?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&metadata, tar::HeaderMode::Complete);
+        if let Some(epoch) = source_date_epoch {
+            header.set_mtime(epoch);
+            header.set_uid(0);
+            header.set_gid(0);
+        }
+        let result = if entry.path_is_symlink() {
+            std::fs::read_link(entry.path())
+                .and_then(|target| tar.append_link(&mut header, rel_path, target))
+        } else if entry.file_type().is_dir() {
+            tar.append_data(&mut header, rel_path, std::io::empty())
+        } else {
+            std::fs::File::open(entry.path())
+                .and_then(|file| tar.append_data(&mut header, rel_path, file))
+        };
+        result.map_err(|e| {
+            format!(
+                "Failed to add {path} to the tar of {dir}: {e}",
+                path = rel_path.display(),
+                dir = dir.display()
+            )
+        })?;
+    }
+    tar.into_inner().map_err(|e| {
+        format!(
+            "Failed to finalize tar {tar}: {e}",
+            tar = tar_path.display()
+        )
+    })?;
+    Ok(tar_path)
+}
+
+/// Creates an archive of the directory `name` in `dir` alongside it.
+///
+/// The `archive_type` must be either a zip or a tar. Entries are stamped with the
+/// `source_date_epoch` when given.
 #[time("debug", "archive::{}")]
-pub(crate) fn create(dir: &Path, name: &str) -> Result<PathBuf, String> {
+pub(crate) fn create(
+    dir: &Path,
+    name: &str,
+    archive_type: ArchiveType,
+    source_date_epoch: Option<u64>,
+) -> Result<PathBuf, String> {
     let path = dir.join(name);
+    let ext = archive_type.as_ext();
     let directory = path.canonicalize().map_err(|e| {
         format!(
-            "Cannot create a zip archive from {path}: Directory does not exist: {e}",
+            "Cannot create a {ext} archive from {path}: Directory does not exist: {e}",
             path = path.display()
         )
     })?;
     if !directory.is_dir() {
         return Err(format!(
-            "Cannot create a zip archive from {name}: {directory} is a file.",
+            "Cannot create a {ext} archive from {name}: {directory} is a file.",
             directory = directory.display()
         ));
     }
-    create_zip(&directory)
+    match archive_type {
+        ArchiveType::Zip => create_zip(&directory, source_date_epoch),
+        ArchiveType::Tar => create_tar(&directory, source_date_epoch),
+        ArchiveType::CompressedTar(_) => Err(format!(
+            "Directories can only be stored as zip or tar archives; given: {ext}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use filetime::FileTime;
+
+    use super::{create_tar, create_zip, zip_date_time};
+
+    #[test]
+    fn zip_date_times() {
+        let date_time = |epoch| {
+            let date_time = zip_date_time(epoch).unwrap();
+            (
+                date_time.year(),
+                date_time.month(),
+                date_time.day(),
+                date_time.hour(),
+                date_time.minute(),
+                date_time.second(),
+            )
+        };
+        assert_eq!((1980, 1, 1, 0, 0, 0), date_time(0));
+        assert_eq!((2001, 9, 9, 1, 46, 40), date_time(1_000_000_000));
+        assert_eq!((2024, 2, 29, 0, 0, 0), date_time(1_709_164_800));
+        assert!(zip_date_time(4_354_819_200).is_err());
+    }
+
+    type Create = fn(&Path, Option<u64>) -> Result<PathBuf, String>;
+
+    fn archive(create: Create, names: &[&str], mtime: i64) -> Vec<u8> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path().join("app");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        for name in names {
+            let path = dir.join(name);
+            std::fs::write(&path, name).unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(mtime, 0)).unwrap();
+        }
+        std::fs::read(create(&dir, Some(1_000_000_000)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reproducible() {
+        // N.B.: The files are written in a different order and with different modification times
+        // for each archive.
+        for create in [create_zip as Create, create_tar] {
+            assert_eq!(
+                archive(create, &["a.txt", "b.txt", "lib/c.txt"], 1_600_000_000),
+                archive(create, &["lib/c.txt", "b.txt", "a.txt"], 1_700_000_000)
+            );
+        }
+    }
 }
@@ -1,9 +1,11 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::path::Path;
+use std::time::Duration;
 
 use serde::Serializer;
 
@@ -47,19 +49,115 @@ impl Target {
     }
 }
 
+// The number of attempts made at filesystem operations that can fail transiently.
+const ATTEMPTS: u32 = 5;
+
+/// Returns `true` if the error is one Windows raises while an antivirus scanner or indexer briefly
+/// holds open a file we just created.
+#[cfg(windows)]
+fn is_transient(e: &std::io::Error) -> bool {
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    matches!(
+        e.raw_os_error(),
+        Some(ERROR_ACCESS_DENIED) | Some(ERROR_SHARING_VIOLATION)
+    )
+}
+
+#[cfg(not(windows))]
+fn is_transient(_e: &std::io::Error) -> bool {
+    false
+}
+
+/// Performs `op` up to `attempts` times, backing off between attempts that fail with an error
+/// `is_transient` accepts. The error from the final attempt is returned if no attempt succeeds.
+fn retry_with<T>(
+    attempts: u32,
+    is_transient: impl Fn(&std::io::Error) -> bool,
+    mut op: impl FnMut() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if attempt < attempts && is_transient(&e) => {
+                debug!("Retrying after attempt {attempt} of {attempts} failed: {e}");
+                std::thread::sleep(Duration::from_millis(10 << attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn retry<T>(op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    retry_with(ATTEMPTS, is_transient, op)
+}
+
 fn clean(path: &Path) -> Result<(), String> {
     if !path.exists() {
         return Ok(());
     }
 
     if path.is_dir() {
-        std::fs::remove_dir(path)
+        retry(|| std::fs::remove_dir_all(path))
     } else {
-        std::fs::remove_file(path)
+        retry(|| std::fs::remove_file(path))
     }
     .map_err(|e| format!("Failed to remove path {path}: {e}", path = path.display()))
 }
 
+/// Returns the extended-length form of an absolute path; so paths deeper than MAX_PATH (260
+/// characters) can be created.
+///
+/// N.B.: Extended-length paths are not normalized by Windows; so we normalize them here.
+#[cfg(windows)]
+pub(crate) fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, PathBuf, Prefix};
+
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) if path.is_absolute() => prefix,
+        _ => return Cow::Borrowed(path),
+    };
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(disk) => PathBuf::from(format!(r"\\?\{disk}:\", disk = disk as char)),
+        Prefix::UNC(server, share) => PathBuf::from(format!(
+            r"\\?\UNC\{server}\{share}\",
+            server = server.to_string_lossy(),
+            share = share.to_string_lossy()
+        )),
+        // The path is already verbatim or else is a device path, neither of which we alter.
+        _ => return Cow::Borrowed(path),
+    };
+    for component in components {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                extended.pop();
+            }
+            Component::Normal(name) => extended.push(name),
+            Component::Prefix(_) => return Cow::Borrowed(path),
+        }
+    }
+    Cow::Owned(extended)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Removes the work path when dropped unless `mem::forget`-ten.
+struct WorkPathGuard<'a>(&'a Path);
+
+impl<'a> Drop for WorkPathGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(e) = clean(self.0) {
+            warn!("{e}")
+        }
+    }
+}
+
 /// Executes work to create the `target` path exactly once across threads and processes.
 ///
 /// If the `target_type` is `Target::Directory` and the `target` directory has not yet been created,
@@ -78,6 +176,9 @@ where
     // We use an atomic rename under a double-checked exclusive write lock to implement an atomic
     // path creation.
 
+    let target = extended_length_path(target);
+    let target = target.as_ref();
+
     // First check.
     if target_type.check_exists(target)? {
         debug!(
@@ -131,9 +232,10 @@ where
 
     // N.B.: Prior work may have been terminated in ways outside the Rust runtime control (panic
     // handling not installed, signals of various sorts); so, with the lock in hand, we clean up any
-    // stray work path before proceeding. Since we need to do this up front anyway, we do not attach
-    // cleanup to the work error path.
+    // stray work path before proceeding. We also clean up the work path when work fails or panics
+    // below, but that cannot cover all cases.
     clean(&work_path)?;
+    let work_path_guard = WorkPathGuard(&work_path);
 
     if Target::Directory == target_type {
         std::fs::create_dir(&work_path).map_err(|e| {
@@ -151,12 +253,117 @@ where
             target_dir = target.display()
         )
     })?;
-    std::fs::rename(work_path, target).map_err(|e| {
+    retry(|| std::fs::rename(&work_path, target)).map_err(|e| {
         format!(
             "Failed to establish atomic directory {target_dir}. Rename of work directory \
             failed: {e}",
             target_dir = target.display()
         )
     })?;
+    std::mem::forget(work_path_guard);
     Ok(Some(result))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{atomic_path, retry_with, Target};
+
+    #[test]
+    fn work_error_cleans_up() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let target = tempdir.path().join("target");
+        let work = target.with_extension("work");
+
+        let result = atomic_path(&target, Target::Directory, |work_dir| {
+            std::fs::create_dir(work_dir.join("subdir")).unwrap();
+            std::fs::write(work_dir.join("subdir").join("file"), "partial").unwrap();
+            Err::<(), _>("failed")
+        });
+        assert!(result.is_err());
+        assert!(!work.exists());
+        assert!(!target.exists());
+
+        let result = atomic_path(&target, Target::File, |work_file| {
+            std::fs::write(work_file, "partial").unwrap();
+            Err::<(), _>("failed")
+        });
+        assert!(result.is_err());
+        assert!(!work.exists());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn work_panic_cleans_up() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let target = tempdir.path().join("target");
+        let work = target.with_extension("work");
+
+        let result = std::panic::catch_unwind(|| {
+            atomic_path(&target, Target::Directory, |work_dir| {
+                std::fs::write(work_dir.join("file"), "partial").unwrap();
+                if work_dir.exists() {
+                    panic!("Simulated failure.");
+                }
+                Ok::<_, String>(())
+            })
+        });
+        assert!(result.is_err());
+        assert!(!work.exists());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn work_success() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let target = tempdir.path().join("target");
+
+        assert_eq!(
+            Some(42),
+            atomic_path(&target, Target::File, |work_file| {
+                std::fs::write(work_file, "complete").map(|_| 42)
+            })
+            .unwrap()
+        );
+        assert_eq!("complete", std::fs::read_to_string(&target).unwrap());
+        assert!(!target.with_extension("work").exists());
+        assert_eq!(
+            None,
+            atomic_path(&target, Target::File, |_| Ok::<_, String>(1)).unwrap()
+        );
+    }
+
+    #[test]
+    fn retry() {
+        let sharing_violation = || std::io::Error::from_raw_os_error(32);
+        let is_sharing_violation = |e: &std::io::Error| e.raw_os_error() == Some(32);
+
+        let attempts = Cell::new(0);
+        let result = retry_with(5, is_sharing_violation, || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(sharing_violation())
+            } else {
+                Ok(attempts.get())
+            }
+        });
+        assert_eq!(3, result.unwrap());
+
+        attempts.set(0);
+        let result = retry_with(3, is_sharing_violation, || {
+            attempts.set(attempts.get() + 1);
+            Err::<(), _>(sharing_violation())
+        });
+        assert_eq!(Some(32), result.unwrap_err().raw_os_error());
+        assert_eq!(3, attempts.get());
+
+        attempts.set(0);
+        let result = retry_with(3, is_sharing_violation, || {
+            attempts.set(attempts.get() + 1);
+            Err::<(), _>(std::io::Error::from(std::io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts.get());
+    }
+}
@@ -1,24 +1,130 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
 use std::fmt::Formatter;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::de::{Error, Unexpected, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 
+use crate::placeholders::{self, Item, Placeholder};
+
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub enum Compression {
     Bzip2,
     Gzip,
+    // The LZW compression of the classic Unix `compress` utility.
+    Lzw,
     Xz,
     Zlib,
     Zstd,
 }
 
+impl Compression {
+    fn as_str(&self) -> &str {
+        match self {
+            Compression::Bzip2 => "bzip2",
+            Compression::Gzip => "gzip",
+            Compression::Lzw => "lzw",
+            Compression::Xz => "xz",
+            Compression::Zlib => "zlib",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+impl Serialize for Compression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Compression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        match value.as_str() {
+            "bzip2" => Ok(Compression::Bzip2),
+            "gzip" => Ok(Compression::Gzip),
+            "lzw" => Ok(Compression::Lzw),
+            "xz" => Ok(Compression::Xz),
+            "zlib" => Ok(Compression::Zlib),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(D::Error::invalid_value(
+                Unexpected::Str(value.as_str()),
+                &"one of bzip2, gzip, lzw, xz, zlib or zstd",
+            )),
+        }
+    }
+}
+
+/// The format of an env file written by a boot binding or loaded as a dotenv file.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq)]
+pub enum EnvFileFormat {
+    /// `<key>=<value>` pairs on individual lines.
+    #[default]
+    Dotenv,
+    /// A JSON object mapping keys to scalar values.
+    Json,
+    /// A TOML table mapping keys to scalar values.
+    Toml,
+}
+
+impl EnvFileFormat {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            EnvFileFormat::Dotenv => "dotenv",
+            EnvFileFormat::Json => "json",
+            EnvFileFormat::Toml => "toml",
+        }
+    }
+
+    /// Returns the structured format implied by the extension of `path`, if any.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("json") => Some(EnvFileFormat::Json),
+            Some("toml") => Some(EnvFileFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for EnvFileFormat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EnvFileFormat {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        match value.as_str() {
+            "dotenv" => Ok(EnvFileFormat::Dotenv),
+            "json" => Ok(EnvFileFormat::Json),
+            "toml" => Ok(EnvFileFormat::Toml),
+            _ => Err(D::Error::invalid_value(
+                Unexpected::Str(value.as_str()),
+                &"one of dotenv, json or toml",
+            )),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub enum ArchiveType {
     CompressedTar(Compression),
@@ -35,8 +141,12 @@ impl ArchiveType {
             "tar" => Some(ArchiveType::Tar),
             "tar.bz2" | "tbz2" => Some(ArchiveType::CompressedTar(Compression::Bzip2)),
             "tar.gz" | "tgz" => Some(ArchiveType::CompressedTar(Compression::Gzip)),
-            "tar.xz" | "tar.lzma" | "tlz" => Some(ArchiveType::CompressedTar(Compression::Xz)),
-            "tar.Z" => Some(ArchiveType::CompressedTar(Compression::Zlib)),
+            "tar.xz" | "tar.lzma" | "tlz" | "txz" => {
+                Some(ArchiveType::CompressedTar(Compression::Xz))
+            }
+            "tar.Z" => Some(ArchiveType::CompressedTar(Compression::Lzw)),
+            // N.B.: This is not a GNU tar extension, but it gives zlib compressed tarballs a name.
+            "tar.zlib" => Some(ArchiveType::CompressedTar(Compression::Zlib)),
             "tar.zst" | "tzst" => Some(ArchiveType::CompressedTar(Compression::Zstd)),
             _ => None,
         }
@@ -49,7 +159,8 @@ impl ArchiveType {
             ArchiveType::CompressedTar(Compression::Bzip2) => "tar.bz2",
             ArchiveType::CompressedTar(Compression::Gzip) => "tar.gz",
             ArchiveType::CompressedTar(Compression::Xz) => "tar.xz",
-            ArchiveType::CompressedTar(Compression::Zlib) => "tar.Z",
+            ArchiveType::CompressedTar(Compression::Lzw) => "tar.Z",
+            ArchiveType::CompressedTar(Compression::Zlib) => "tar.zlib",
             ArchiveType::CompressedTar(Compression::Zstd) => "tar.zst",
         }
     }
@@ -84,7 +195,7 @@ impl<'de> Visitor<'de> for FileTypeVisitor {
         write!(
             formatter,
             "one of: blob, directory, zip, tar, tar.bz2, tbz2, tar.gz, tgz, tar.xz, tar.lzma, \
-            tlz, tar.Z, tar.zst or tzst"
+            tlz, tar.Z, tar.zlib, tar.zst or tzst"
         )
     }
 
@@ -115,6 +226,31 @@ fn is_false(value: &bool) -> bool {
     !*value
 }
 
+// File modes are only legible in octal; so they are stored as octal strings, e.g.: "0600".
+fn serialize_mode<S>(mode: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match mode {
+        Some(mode) => serializer.serialize_str(format!("{mode:04o}").as_str()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_mode<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mode = String::deserialize(deserializer)?;
+    u32::from_str_radix(mode.as_str(), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .map(Some)
+        .ok_or_else(|| {
+            D::Error::invalid_value(Unexpected::Str(mode.as_str()), &"an octal file mode string")
+        })
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct File {
@@ -133,9 +269,28 @@ pub struct File {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub executable: Option<bool>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        serialize_with = "serialize_mode",
+        deserialize_with = "deserialize_mode"
+    )]
+    pub mode: Option<u32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "is_false")]
     pub eager_extract: bool,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extract_members: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub use_zstd_dictionary: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompressor: Option<String>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
 }
@@ -144,6 +299,8 @@ pub struct File {
 pub enum EnvVar {
     Default(String),
     Replace(String),
+    Append(String),
+    Prepend(String),
 }
 
 impl Serialize for EnvVar {
@@ -154,6 +311,8 @@ impl Serialize for EnvVar {
         match self {
             EnvVar::Default(name) => serializer.serialize_str(name),
             EnvVar::Replace(name) => serializer.serialize_str(format!("={name}").as_str()),
+            EnvVar::Append(name) => serializer.serialize_str(format!("+{name}").as_str()),
+            EnvVar::Prepend(name) => serializer.serialize_str(format!("{name}+").as_str()),
         }
     }
 }
@@ -179,17 +338,41 @@ impl<'de> Visitor<'de> for EnvVarVisitor {
         // prefix presents an obvious parsing challenge to OSes; so people likely avoid it and this
         // fact is encoded here:
         // https://pubs.opengroup.org/onlinepubs/009696899/basedefs/xbd_chap08.html
+        // Similarly, we use a + prefix to indicate the value should be appended to any current
+        // value and a + suffix to indicate it should be prepended. Since a regex can end in a +, we
+        // only treat a + suffix as a prepend when it follows a plain env var name; so removal
+        // regexes like `BASH_.+` keep their meaning.
         match value.as_bytes() {
             [b'=', name @ ..] => {
                 let env_var_name = std::str::from_utf8(name)
                     .map_err(|_| E::invalid_value(Unexpected::Str(value), &self))?;
                 Ok(EnvVar::Replace(env_var_name.into()))
             }
+            [b'+', name @ ..] if !name.is_empty() => {
+                let env_var_name = std::str::from_utf8(name)
+                    .map_err(|_| E::invalid_value(Unexpected::Str(value), &self))?;
+                Ok(EnvVar::Append(env_var_name.into()))
+            }
+            [name @ .., b'+'] if is_plain_name(name) => {
+                let env_var_name = std::str::from_utf8(name)
+                    .map_err(|_| E::invalid_value(Unexpected::Str(value), &self))?;
+                Ok(EnvVar::Prepend(env_var_name.into()))
+            }
             _ => Ok(EnvVar::Default(value.into())),
         }
     }
 }
 
+fn is_plain_name(name: &[u8]) -> bool {
+    match name {
+        [first, rest @ ..] => {
+            (first.is_ascii_alphabetic() || *first == b'_')
+                && rest.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        }
+        [] => false,
+    }
+}
+
 impl<'de> Deserialize<'de> for EnvVar {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -199,7 +382,7 @@ impl<'de> Deserialize<'de> for EnvVar {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Cmd {
     pub exe: String,
@@ -207,11 +390,35 @@ pub struct Cmd {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args_append: Vec<String>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub env: BTreeMap<EnvVar, Option<String>>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub then: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inherit_fds: Vec<i32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_dotenv: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file_format: Option<EnvFileFormat>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -222,7 +429,7 @@ pub struct Jump {
     pub version: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Boot {
     pub commands: BTreeMap<String, Cmd>,
@@ -242,10 +449,48 @@ pub struct Lift {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub base: Option<PathBuf>,
     pub files: Vec<File>,
+    // N.B.: The boot can be omitted when the default command is given by `interpreter` and `app`.
+    #[serde(default)]
     pub boot: Boot,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub load_dotenv: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dotenv_files: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extract_by_hash: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zstd_dictionary: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_jump_version: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_binding: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commands_file: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_base: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_bindings: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -330,37 +575,219 @@ impl Config {
         Ok(config)
     }
 
-    pub fn serialize<W: Write>(&self, mut stream: W, fmt: Fmt) -> Result<(), String> {
-        let mut write_bytes = |bytes| {
-            stream
-                .write_all(bytes)
-                .map_err(|e| format!("Failed to write scie lift manifest: {e}"))
-        };
+    pub fn serialize<W: Write>(&self, stream: W, fmt: Fmt) -> Result<(), String> {
+        serialize(self, "scie lift manifest", stream, fmt)
+    }
+
+    /// Checks the lift manifest for the semantic errors `parse` can't catch; e.g.: a command that
+    /// refers to a file the manifest does not contain.
+    ///
+    /// All the problems found are returned at once.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let lift = &self.scie.lift;
+        let mut errors = vec![];
 
-        if fmt.leading_newline {
-            write_bytes(Config::NEWLINE)?;
+        if let Some(base) = lift.base.as_ref() {
+            match base.to_str().map(collect_placeholders) {
+                Some(Ok(found)) => {
+                    if found
+                        .iter()
+                        .any(|placeholder| matches!(placeholder, Placeholder::ScieLift))
+                    {
+                        errors.push(format!(
+                            "The lift base {base} cannot contain {{scie.lift}} since the lift \
+                            manifest is stored under the base.",
+                            base = base.display()
+                        ));
+                    }
+                }
+                Some(Err(e)) => errors.push(format!(
+                    "The lift base {base} is invalid: {e}",
+                    base = base.display()
+                )),
+                None => errors.push(format!(
+                    "The lift base {base} is not a valid utf-8 string.",
+                    base = base.display()
+                )),
+            }
         }
 
-        let body = if fmt.pretty {
-            serde_json::to_vec_pretty(self)
+        let mut files = BTreeSet::new();
+        for file in &lift.files {
+            files.insert(file.name.as_str());
+            if let Some(key) = file.key.as_deref() {
+                files.insert(key);
+            }
+            if let (Some(FileType::Blob), Some(archive_type)) =
+                (file.file_type, archive_type(&file.name))
+            {
+                errors.push(format!(
+                    "The file {name} has the name of a {ext} archive but is declared as a blob. \
+                    Declare its type as {ext} or else rename it.",
+                    name = file.name,
+                    ext = archive_type.as_ext()
+                ));
+            }
+        }
+        if lift.zstd_dictionary.is_none() {
+            for file in lift.files.iter().filter(|file| file.use_zstd_dictionary) {
+                errors.push(format!(
+                    "The file {name} uses the zstd dictionary but the lift has no zstd_dictionary.",
+                    name = file.name
+                ));
+            }
+        }
+        if let Some(env_file) = lift.env_file.as_deref() {
+            if !files.contains(env_file) {
+                errors.push(format!(
+                    "The env_file {env_file} does not name a file stored in this scie."
+                ));
+            }
+        }
+
+        for (kind, cmds) in [
+            ("command", &lift.boot.commands),
+            ("binding", &lift.boot.bindings),
+        ] {
+            for (name, cmd) in cmds {
+                let cmd_name = if name.is_empty() {
+                    format!("The default {kind}")
+                } else {
+                    format!("The {kind} {name}")
+                };
+                let values = std::iter::once(&cmd.exe)
+                    .chain(cmd.args.iter())
+                    .chain(cmd.args_append.iter())
+                    .chain(cmd.env.values().flatten())
+                    .chain(cmd.stdout.iter())
+                    .chain(cmd.stderr.iter());
+                for value in values {
+                    let found = match collect_placeholders(value) {
+                        Ok(found) => found,
+                        Err(e) => {
+                            errors.push(format!("{cmd_name} has an invalid value {value}: {e}"));
+                            continue;
+                        }
+                    };
+                    for placeholder in found {
+                        match placeholder {
+                            Placeholder::FileName(file) | Placeholder::FileHash(file)
+                                if !files.contains(file) =>
+                            {
+                                errors.push(format!(
+                                    "{cmd_name} refers to {file} in {value}: {problem}",
+                                    problem = placeholders::unknown_scie_placeholder(file)
+                                        .unwrap_or_else(|| format!(
+                                            "no file named {file} is stored in this scie."
+                                        ))
+                                ));
+                            }
+                            Placeholder::ScieBindingCmd(placeholders::ScieBindingCmd {
+                                binding,
+                                ..
+                            })
+                            | Placeholder::ScieBindingEnv(placeholders::ScieBindingEnv {
+                                binding,
+                                ..
+                            }) if !lift.boot.bindings.contains_key(binding) => {
+                                errors.push(format!(
+                                    "{cmd_name} refers to the binding {binding} in {value}, but \
+                                    there is no such binding."
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
         } else {
-            serde_json::to_vec(self)
+            Err(errors)
         }
-        .map_err(|e| format!("Failed to serialize scie lift manifest: {e}"))?;
-        write_bytes(body.as_slice())?;
+    }
+}
 
-        if fmt.trailing_newline {
-            write_bytes(Config::NEWLINE)?;
+// Mirrors the archive type detection used when packing files with no declared type.
+fn archive_type(name: &str) -> Option<ArchiveType> {
+    let ext = match name.rsplitn(3, '.').collect::<Vec<_>>()[..] {
+        [_, "tar", stem] => name.trim_start_matches(stem).trim_start_matches('.'),
+        [ext, _, ..] => ext,
+        _ => return None,
+    };
+    ArchiveType::from_ext(ext)
+}
+
+// Collects all the placeholders in `value`, including those nested in other placeholders; e.g.:
+// in env var defaults. File names that are themselves computed from placeholders can't be checked
+// statically; so only the placeholders they contain are collected.
+fn collect_placeholders(value: &str) -> Result<Vec<Placeholder>, String> {
+    let mut found = vec![];
+    for item in placeholders::parse(value)?.items {
+        let Item::Placeholder(placeholder) = item else {
+            continue;
+        };
+        match placeholder {
+            Placeholder::FileName(name) | Placeholder::FileHash(name) if name.contains('{') => {
+                found.extend(collect_placeholders(name)?)
+            }
+            Placeholder::Env(env) => found.extend(collect_placeholders(env)?),
+            Placeholder::ScieBindingCmd(placeholders::ScieBindingCmd { ref args, .. })
+            | Placeholder::ScieBindingEnv(placeholders::ScieBindingEnv { ref args, .. }) => {
+                for arg in args {
+                    found.extend(collect_placeholders(arg)?);
+                }
+                found.push(placeholder);
+            }
+            placeholder => found.push(placeholder),
         }
-        Ok(())
     }
+    Ok(found)
+}
+
+impl File {
+    pub fn serialize<W: Write>(&self, stream: W, fmt: Fmt) -> Result<(), String> {
+        serialize(self, "scie lift manifest file", stream, fmt)
+    }
+}
+
+fn serialize<T: Serialize, W: Write>(
+    value: &T,
+    description: &str,
+    mut stream: W,
+    fmt: Fmt,
+) -> Result<(), String> {
+    let mut write_bytes = |bytes| {
+        stream
+            .write_all(bytes)
+            .map_err(|e| format!("Failed to write {description}: {e}"))
+    };
+
+    if fmt.leading_newline {
+        write_bytes(Config::NEWLINE)?;
+    }
+
+    let body = if fmt.pretty {
+        serde_json::to_vec_pretty(value)
+    } else {
+        serde_json::to_vec(value)
+    }
+    .map_err(|e| format!("Failed to serialize {description}: {e}"))?;
+    write_bytes(body.as_slice())?;
+
+    if fmt.trailing_newline {
+        write_bytes(Config::NEWLINE)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
 
-    use super::{ArchiveType, Boot, Cmd, Compression, Config, EnvVar, File, Jump, Lift};
+    use super::{ArchiveType, Boot, Cmd, Compression, Config, EnvVar, File, Fmt, Jump, Lift};
     use crate::config::FileType;
 
     #[test]
@@ -382,7 +809,12 @@ mod tests {
                             hash: Some("abc".to_string()),
                             file_type: Some(FileType::Blob),
                             executable: Some(true),
+                            mode: None,
+                            compression: None,
                             eager_extract: true,
+                            extract_members: vec![],
+                            use_zstd_dictionary: false,
+                            decompressor: None,
                             source: None,
                         },
                         File {
@@ -394,7 +826,12 @@ mod tests {
                                 Compression::Zstd
                             ))),
                             executable: None,
+                            mode: None,
+                            compression: None,
                             eager_extract: false,
+                            extract_members: vec![],
+                            use_zstd_dictionary: false,
+                            decompressor: None,
                             source: None,
                         },
                         File {
@@ -404,7 +841,12 @@ mod tests {
                             hash: Some("def".to_string()),
                             file_type: Some(FileType::Archive(ArchiveType::Zip)),
                             executable: None,
+                            mode: None,
+                            compression: None,
                             eager_extract: false,
+                            extract_members: vec![],
+                            use_zstd_dictionary: false,
+                            decompressor: None,
                             source: None,
                         }
                     ],
@@ -428,7 +870,7 @@ mod tests {
                                 ]
                                 .into_iter()
                                 .collect(),
-                                description: None
+                                ..Default::default()
                             }
                         )]
                         .into_iter()
@@ -437,7 +879,19 @@ mod tests {
                     },
                     name: "test".to_string(),
                     description: None,
-                    load_dotenv: Some(false)
+                    load_dotenv: Some(false),
+                    dotenv_files: vec![],
+                    extract_by_hash: None,
+                    zstd_dictionary: None,
+                    min_jump_version: None,
+                    base_binding: None,
+                    commands_file: None,
+                    env_file: None,
+                    relative_base: None,
+                    dev: None,
+                    shared_bindings: None,
+                    interpreter: None,
+                    app: None,
                 },
                 None,
             ))
@@ -490,4 +944,343 @@ mod tests {
             .unwrap()
         )
     }
+
+    #[test]
+    fn test_cmd_stdio() {
+        let cmd = serde_json::from_str::<Cmd>(
+            r#"
+            {
+                "exe": "{scie.bindings.daemon}/bin/daemon",
+                "stdout": "{scie.base}/daemon.log",
+                "stderr": "{scie.base}/daemon.err"
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some("{scie.base}/daemon.log"), cmd.stdout.as_deref());
+        assert_eq!(Some("{scie.base}/daemon.err"), cmd.stderr.as_deref());
+
+        let value = serde_json::to_value(&cmd).unwrap();
+        assert_eq!("{scie.base}/daemon.log", value["stdout"]);
+        assert_eq!("{scie.base}/daemon.err", value["stderr"]);
+
+        let value = serde_json::to_value(Cmd {
+            stdout: None,
+            stderr: None,
+            ..cmd
+        })
+        .unwrap();
+        assert!(value.get("stdout").is_none());
+        assert!(value.get("stderr").is_none());
+    }
+
+    #[test]
+    fn test_cmd_args_append() {
+        let cmd = serde_json::from_str::<Cmd>(
+            r#"
+            {
+                "exe": "{python}/bin/python",
+                "args": ["-m", "tool"],
+                "args_append": ["--config", "{scie.base}/tool.cfg"]
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(vec!["-m", "tool"], cmd.args);
+        assert_eq!(vec!["--config", "{scie.base}/tool.cfg"], cmd.args_append);
+
+        let value = serde_json::to_value(&cmd).unwrap();
+        assert_eq!(
+            serde_json::json!(["--config", "{scie.base}/tool.cfg"]),
+            value["args_append"]
+        );
+
+        let value = serde_json::to_value(Cmd {
+            args_append: vec![],
+            ..cmd
+        })
+        .unwrap();
+        assert!(value.get("args_append").is_none());
+    }
+
+    #[test]
+    fn test_env_var_append_prepend() {
+        let cmd: Cmd = serde_json::from_str(
+            r#"
+            {
+                "exe": "java",
+                "env": {
+                    "+PATH": "/opt/java/bin",
+                    "LD_LIBRARY_PATH+": "/opt/java/lib",
+                    "=JAVA_HOME": "/opt/java",
+                    "FOO": "bar"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                (
+                    EnvVar::Append("PATH".to_string()),
+                    Some("/opt/java/bin".to_string())
+                ),
+                (
+                    EnvVar::Prepend("LD_LIBRARY_PATH".to_string()),
+                    Some("/opt/java/lib".to_string())
+                ),
+                (
+                    EnvVar::Replace("JAVA_HOME".to_string()),
+                    Some("/opt/java".to_string())
+                ),
+                (EnvVar::Default("FOO".to_string()), Some("bar".to_string())),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+            cmd.env
+        );
+
+        let value = serde_json::to_value(&cmd).unwrap();
+        assert_eq!("/opt/java/bin", value["env"]["+PATH"]);
+        assert_eq!("/opt/java/lib", value["env"]["LD_LIBRARY_PATH+"]);
+
+        let cmd: Cmd = serde_json::from_str(
+            r#"
+            {
+                "exe": "bash",
+                "env": {
+                    "BASH_.+": null,
+                    "1+": "one"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                (EnvVar::Default("1+".to_string()), Some("one".to_string())),
+                (EnvVar::Default("BASH_.+".to_string()), None),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+            cmd.env
+        );
+    }
+
+    #[test]
+    fn test_file_serialize() {
+        let file = File {
+            name: "python".to_string(),
+            key: Some("cpython".to_string()),
+            size: Some(123),
+            hash: Some("345".to_string()),
+            file_type: Some(FileType::Archive(ArchiveType::CompressedTar(
+                Compression::Zstd,
+            ))),
+            executable: None,
+            mode: None,
+            compression: None,
+            eager_extract: false,
+            extract_members: vec![],
+            use_zstd_dictionary: false,
+            decompressor: None,
+            source: None,
+        };
+        let mut buffer = vec![];
+        file.serialize(&mut buffer, Fmt::new()).unwrap();
+        let value = serde_json::from_slice::<serde_json::Value>(buffer.as_slice()).unwrap();
+        assert_eq!("python", value["name"]);
+        assert_eq!("cpython", value["key"]);
+        assert_eq!("345", value["hash"]);
+        assert_eq!("tar.zst", value["type"]);
+    }
+
+    #[test]
+    fn test_file_mode() {
+        let file = serde_json::from_str::<File>(r#"{"name": "key.pem", "mode": "0600"}"#).unwrap();
+        assert_eq!(Some(0o600), file.mode);
+        assert_eq!("0600", serde_json::to_value(&file).unwrap()["mode"]);
+
+        let file = serde_json::from_str::<File>(r#"{"name": "key.pem"}"#).unwrap();
+        assert_eq!(None, file.mode);
+        assert!(serde_json::to_value(&file).unwrap().get("mode").is_none());
+
+        assert!(serde_json::from_str::<File>(r#"{"name": "key.pem", "mode": "0800"}"#).is_err());
+        assert!(serde_json::from_str::<File>(r#"{"name": "key.pem", "mode": "10000"}"#).is_err());
+    }
+
+    fn validate(lift: serde_json::Value) -> Result<(), Vec<String>> {
+        let data = serde_json::to_vec(&serde_json::json!({"scie": {"lift": lift}})).unwrap();
+        Config::parse(&data).unwrap().validate()
+    }
+
+    #[test]
+    fn validate_ok() {
+        assert_eq!(
+            Ok(()),
+            validate(serde_json::json!({
+                "name": "test",
+                "base": "{scie.env.BASE={scie.platform}}",
+                "files": [
+                    {"name": "python.tar.gz", "key": "python"},
+                    {"name": "app.pex"},
+                    {"name": "data.zip", "type": "zip"},
+                    {"name": "README", "type": "blob"}
+                ],
+                "boot": {
+                    "commands": {
+                        "": {
+                            "exe": "{python}/bin/python",
+                            "args": ["{scie.files.app.pex}", "{scie.files:hash.data.zip}"],
+                            "env": {
+                                "CONFIG": "{scie.env.CONFIG={scie.bindings.configure:CONFIG}}",
+                                "README": "{scie.env.README_NAME=README}",
+                                "UNSET": null
+                            },
+                            "stdout": "{scie.bindings.configure({python})}/out.log"
+                        }
+                    },
+                    "bindings": {
+                        "configure": {"exe": "{python}/bin/python", "args": ["{{literal}}"]}
+                    }
+                }
+            }))
+        );
+    }
+
+    #[test]
+    fn validate_missing_file() {
+        let errors = validate(serde_json::json!({
+            "name": "test",
+            "files": [{"name": "python"}],
+            "boot": {
+                "commands": {"": {"exe": "{python}/bin/python", "args": ["{app}"]}},
+                "bindings": {"setup": {"exe": "{scie.files:hash.tool}"}}
+            }
+        }))
+        .unwrap_err();
+        assert_eq!(2, errors.len(), "{errors:?}");
+        assert!(
+            errors[0].starts_with("The default command refers to app in {app}: "),
+            "{errors:?}"
+        );
+        assert!(
+            errors[1].starts_with("The binding setup refers to tool in {scie.files:hash.tool}: "),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn validate_missing_env_file() {
+        let errors = validate(serde_json::json!({
+            "name": "test",
+            "env_file": "app.env",
+            "files": [{"name": "python"}],
+            "boot": {"commands": {"": {"exe": "{python}/bin/python"}}}
+        }))
+        .unwrap_err();
+        assert_eq!(
+            vec!["The env_file app.env does not name a file stored in this scie.".to_string()],
+            errors
+        );
+    }
+
+    #[test]
+    fn validate_missing_zstd_dictionary() {
+        let errors = validate(serde_json::json!({
+            "name": "test",
+            "files": [{"name": "python.tar.zst", "use_zstd_dictionary": true}],
+            "boot": {"commands": {"": {"exe": "{python.tar.zst}/bin/python"}}}
+        }))
+        .unwrap_err();
+        assert_eq!(
+            vec![
+                "The file python.tar.zst uses the zstd dictionary but the lift has no \
+                zstd_dictionary."
+                    .to_string()
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn validate_scie_placeholder_typo() {
+        let errors = validate(serde_json::json!({
+            "name": "test",
+            "files": [{"name": "python"}],
+            "boot": {"commands": {"": {"exe": "{python}/bin/python", "args": ["{scie.bas}"]}}}
+        }))
+        .unwrap_err();
+        assert_eq!(1, errors.len(), "{errors:?}");
+        assert!(
+            errors[0].ends_with("did you mean '{scie.base}'?"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn validate_missing_binding() {
+        let errors = validate(serde_json::json!({
+            "name": "test",
+            "files": [{"name": "python"}],
+            "boot": {
+                "commands": {
+                    "server": {
+                        "exe": "{python}/bin/python",
+                        "env": {"PORT": "{scie.bindings.configure:PORT}"},
+                        "stderr": "{scie.bindings.logs}/server.err"
+                    }
+                },
+                "bindings": {"config": {"exe": "{python}/bin/python"}}
+            }
+        }))
+        .unwrap_err();
+        assert_eq!(
+            vec![
+                "The command server refers to the binding configure in \
+                {scie.bindings.configure:PORT}, but there is no such binding."
+                    .to_string(),
+                "The command server refers to the binding logs in {scie.bindings.logs}/server.err, \
+                but there is no such binding."
+                    .to_string(),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn validate_lift_base() {
+        assert_eq!(
+            Err(vec![
+                "The lift base {scie.env.BASE={scie.lift}}/nce cannot contain {scie.lift} since \
+                the lift manifest is stored under the base."
+                    .to_string()
+            ]),
+            validate(serde_json::json!({
+                "name": "test",
+                "base": "{scie.env.BASE={scie.lift}}/nce",
+                "files": [],
+                "boot": {"commands": {"": {"exe": "/bin/true"}}}
+            }))
+        );
+    }
+
+    #[test]
+    fn validate_blob_archive() {
+        assert_eq!(
+            Err(vec![
+                "The file python.tar.gz has the name of a tar.gz archive but is declared as a \
+                blob. Declare its type as tar.gz or else rename it."
+                    .to_string()
+            ]),
+            validate(serde_json::json!({
+                "name": "test",
+                "files": [
+                    {"name": "python.tar.gz", "type": "blob"},
+                    {"name": "app.pex", "type": "blob"}
+                ],
+                "boot": {"commands": {"": {"exe": "{python.tar.gz}/bin/python"}}}
+            }))
+        );
+    }
 }
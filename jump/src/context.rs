@@ -3,21 +3,23 @@
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Debug, Formatter};
+use std::io::Write;
 use std::path::{Component, Path, PathBuf};
 use std::process::Child;
+use std::time::Duration;
 
 use bstr::ByteSlice;
 use logging_timer::time;
 
 use crate::atomic::{atomic_path, Target};
-use crate::config::{Cmd, Fmt};
+use crate::config::{Cmd, EnvFileFormat, FileType, Fmt};
 use crate::installer::Installer;
 use crate::lift::{File, Lift};
-use crate::placeholders::{self, Item, Placeholder, ScieBindingEnv};
+use crate::placeholders::{self, Item, Placeholder, ScieBindingCmd, ScieBindingEnv};
 use crate::process::{EnvVar, Process};
-use crate::{config, CurrentExe, EnvVars, Jump, Source};
+use crate::{config, exit_code, fingerprint, toml_env, CurrentExe, EnvVars, Jump, Source};
 
 fn expanduser(path: &Path) -> Result<PathBuf, String> {
     if !<[u8]>::from_path(path)
@@ -84,11 +86,23 @@ impl LiftManifest {
 }
 
 pub(crate) struct LoadProcess {
+    name: String,
     lift_manifest: Option<LiftManifest>,
     process: Process,
+    timeout: Option<Duration>,
 }
 
 impl LoadProcess {
+    #[cfg(test)]
+    pub(crate) fn new(name: &str, process: Process, timeout: Option<Duration>) -> Self {
+        Self {
+            name: name.to_string(),
+            lift_manifest: None,
+            process,
+            timeout,
+        }
+    }
+
     pub(crate) fn spawn_stdout(&self, args: &[&str]) -> Result<Child, String> {
         if let Some(ref lift_manifest) = self.lift_manifest {
             lift_manifest.install()?;
@@ -99,11 +113,20 @@ impl LoadProcess {
     pub(crate) fn exe(&self) -> &OsStr {
         self.process.exe.as_os_str()
     }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
 }
 
 impl Debug for LoadProcess {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("LoadProcess")
+            .field("name", &self.name)
             .field("process", &self.process)
             .finish()
     }
@@ -114,13 +137,90 @@ pub(crate) enum FileEntry {
     Skip(usize),
     Install((File, PathBuf)),
     LoadAndInstall((LoadProcess, File, PathBuf)),
+    LoadUrl((File, PathBuf)),
     ScieTote((File, Vec<(File, PathBuf)>)),
 }
 
+/// Parses a (non-dotenv) structured env file, whose values must all be scalars.
+fn parse_structured_env<V>(
+    env_file: &Path,
+    format: EnvFileFormat,
+    parsed: Result<BTreeMap<String, V>, String>,
+    scalar: impl Fn(V) -> Option<String>,
+) -> Result<HashMap<String, String>, String> {
+    let parsed = parsed.map_err(|e| {
+        format!(
+            "Failed to parse {env_file} as a {format} env file: {e}",
+            env_file = env_file.display(),
+            format = format.as_str()
+        )
+    })?;
+    parsed
+        .into_iter()
+        .map(|(key, value)| match scalar(value) {
+            Some(value) => Ok((key, value)),
+            None => Err(format!(
+                "The value of {key} in the {format} env file {env_file} must be a string, number \
+                or boolean.",
+                env_file = env_file.display(),
+                format = format.as_str()
+            )),
+        })
+        .collect()
+}
+
+/// Loads the env vars in `env_file`, which is parsed according to its `format`.
+pub(crate) fn load_env_file(
+    env_file: &Path,
+    format: EnvFileFormat,
+) -> Result<HashMap<String, String>, String> {
+    let contents = std::fs::read_to_string(env_file).map_err(|e| {
+        format!(
+            "Failed to read env from {env_file}: {e}",
+            env_file = env_file.display()
+        )
+    })?;
+    match format {
+        EnvFileFormat::Dotenv => {
+            let mut env = HashMap::new();
+            for line in contents.lines() {
+                let trimmed = line.trim();
+                if !trimmed.is_empty() {
+                    let mut components = trimmed.splitn(2, '=');
+                    let key = components.next().ok_or_else(|| {
+                        format!("The non-empty line {line} must contain at least an env var name.")
+                    })?;
+                    let value = components.next().unwrap_or("");
+                    env.insert(key.to_string(), value.to_string());
+                }
+            }
+            Ok(env)
+        }
+        EnvFileFormat::Json => parse_structured_env(
+            env_file,
+            format,
+            serde_json::from_str(&contents).map_err(|e| e.to_string()),
+            |value| match value {
+                serde_json::Value::String(value) => Some(value),
+                serde_json::Value::Number(value) => Some(value.to_string()),
+                serde_json::Value::Bool(value) => Some(value.to_string()),
+                _ => None,
+            },
+        ),
+        EnvFileFormat::Toml => {
+            parse_structured_env(env_file, format, toml_env::parse(&contents), |value| value)
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct Binding {
+    name: String,
     target: PathBuf,
     process: Process,
+    capture_stderr: bool,
+    env_file_format: EnvFileFormat,
+    timeout: Option<Duration>,
 }
 
 impl Binding {
@@ -132,29 +232,52 @@ impl Binding {
             trace!("Installing boot binding {binding:#?}", binding = &self);
             install_required_files()?;
 
+            let extra_env = vec![("SCIE_BINDING_ENV".into(), lock.into())];
             let result = self
                 .process
-                .execute(vec![("SCIE_BINDING_ENV".into(), lock.into())]);
+                .execute_timeout(extra_env, self.capture_stderr, self.timeout);
 
             match result {
                 Err(err) => Err(format!("Failed to launch boot binding: {err}")),
-                Ok(exit_status) if !exit_status.success() => {
-                    Err(format!("Boot binding command failed: {exit_status}"))
+                Ok(None) => Err(format!(
+                    "The boot binding {name} timed out after {timeout:?}.",
+                    name = self.name,
+                    timeout = self.timeout.unwrap_or_default()
+                )),
+                Ok(Some((exit_status, stderr))) if !exit_status.success() => {
+                    let mut message = format!("Boot binding command failed: {exit_status}");
+                    let stderr = String::from_utf8_lossy(&stderr);
+                    for line in stderr.lines() {
+                        message.push_str(&format!("\n[{name}] {line}", name = self.name));
+                    }
+                    Err(message)
                 }
-                _ => std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(lock)
+                // N.B.: The stderr of a successful binding is passed through just as it would be
+                // were it not captured.
+                Ok(Some((_, stderr))) => std::io::stderr()
+                    .write_all(&stderr)
                     .map_err(|e| {
                         format!(
-                            "Failed to touch lock file {path}: {e}",
-                            path = lock.display()
+                            "Failed to pass through the stderr of the boot binding {name}: {e}",
+                            name = self.name
                         )
+                    })
+                    .and_then(|_| {
+                        std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(lock)
+                            .map_err(|e| {
+                                format!(
+                                    "Failed to touch lock file {path}: {e}",
+                                    path = lock.display()
+                                )
+                            })
                     }),
             }?;
             // We eagerly load the env file before we exit the lock such that malformed env files
             // are detected and the lock is not poisoned.
-            Self::load_env_file(lock)
+            load_env_file(lock, self.env_file_format)
         })? {
             Ok(env)
         } else {
@@ -163,29 +286,68 @@ impl Binding {
     }
 
     fn load_env(&self) -> Result<HashMap<String, String>, String> {
-        Self::load_env_file(self.target.as_path())
+        load_env_file(self.target.as_path(), self.env_file_format)
     }
+}
 
-    fn load_env_file(env_file: &Path) -> Result<HashMap<String, String>, String> {
-        let contents = std::fs::read_to_string(env_file).map_err(|e| {
-            format!(
-                "Failed to read binding env from {env_file}: {e}",
-                env_file = env_file.display()
-            )
-        })?;
-        let mut env = HashMap::new();
-        for line in contents.lines() {
-            let trimmed = line.trim();
-            if !trimmed.is_empty() {
-                let mut components = trimmed.splitn(2, '=');
-                let key = components.next().ok_or_else(|| {
-                    format!("The non-empty line {line} must contain at least an env var name.")
-                })?;
-                let value = components.next().unwrap_or("");
-                env.insert(key.to_string(), value.to_string());
-            }
+#[derive(Debug, Eq, PartialEq)]
+enum EnvMode {
+    // {scie.env.<name>=<word>}: The env var value if set, else the word.
+    Default,
+    // {scie.env.<name>:+<word>}: The word if the env var is set and non-empty, else empty.
+    Alternate,
+}
+
+// The separator of the entries in PATH-like env var values.
+const PATH_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+#[derive(Debug, Eq, PartialEq)]
+struct ParsedEnv {
+    name: String,
+    // {scie.env.<name>[<index>]}: Selects the index-th entry of a PATH-like env var value.
+    index: Option<usize>,
+    mode: EnvMode,
+    word: String,
+}
+
+impl ParsedEnv {
+    fn resolve<'b>(&'b self, value: Option<&'b str>) -> &'b str {
+        // N.B.: An index past the last entry is treated just like an unset env var.
+        let value = match self.index {
+            Some(index) => value.and_then(|value| value.split(PATH_SEPARATOR).nth(index)),
+            None => value,
+        };
+        match (&self.mode, value) {
+            (EnvMode::Default, Some(value)) => value,
+            (EnvMode::Default, None) => self.word.as_str(),
+            (EnvMode::Alternate, Some(value)) if !value.is_empty() => self.word.as_str(),
+            (EnvMode::Alternate, _) => "",
+        }
+    }
+}
+
+/// A command exe that lives inside an archive or directory file.
+///
+/// The exe can only be checked for once its archive is installed; so the check is deferred until
+/// then.
+#[derive(Debug)]
+pub(crate) struct ArchiveMember {
+    archive: String,
+    member: String,
+    path: PathBuf,
+}
+
+impl ArchiveMember {
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.path.exists() {
+            Ok(())
+        } else {
+            Err(format!(
+                "The archive '{archive}' does not contain '{member}'.",
+                archive = self.archive,
+                member = self.member
+            ))
         }
-        Ok(env)
     }
 }
 
@@ -193,6 +355,7 @@ pub(crate) struct SelectedCmd {
     pub(crate) process: Process,
     pub(crate) files: Vec<FileEntry>,
     pub(crate) argv1_consumed: bool,
+    pub(crate) exe_member: Option<ArchiveMember>,
 }
 
 pub(crate) struct Context<'a> {
@@ -205,21 +368,245 @@ pub(crate) struct Context<'a> {
     lift_manifest: LiftManifest,
     lift_manifest_dependants: HashSet<Process>,
     lift_manifest_installed: bool,
-    bound: HashMap<&'a str, Binding>,
+    bound: HashMap<(&'a str, Vec<&'a str>), Binding>,
     installed: HashSet<File>,
+    ambient_env: HashMap<OsString, OsString>,
+    // Whether the ambient env is the process env and so should be scrubbed of the env vars that
+    // select the command for the benefit of subprocesses.
+    process_env: bool,
+    env_file_defaults: BTreeMap<String, String>,
+    bindings_key: String,
+    dev_key: String,
+    binding_failed: bool,
 }
 
 fn try_as_str(os_str: &OsStr) -> Option<&str> {
     <[u8]>::from_os_str(os_str).and_then(|bytes| std::str::from_utf8(bytes).ok())
 }
 
+#[cfg(windows)]
+fn invoked_basename(invoked_as: &Path) -> Option<&str> {
+    invoked_as.file_stem().and_then(try_as_str)
+}
+
+#[cfg(unix)]
+fn invoked_basename(invoked_as: &Path) -> Option<&str> {
+    invoked_as.file_name().and_then(try_as_str)
+}
+
+/// Returns the name to use for BusyBox-style command lookup.
+///
+/// An explicit `scie_name` (from `SCIE_NAME`) overrides the name derived from `invoked_as`.
+fn invoked_name<'a>(invoked_as: &'a Path, scie_name: Option<&'a OsStr>) -> Option<&'a str> {
+    if let Some(scie_name) = scie_name {
+        try_as_str(scie_name)
+    } else {
+        invoked_basename(invoked_as)
+    }
+}
+
+// N.B.: A bare `invoked_as` was found on the PATH; so we fall back to the directory containing
+// the scie itself.
+fn invoked_dir<'p>(scie: &'p Path, invoked_as: &'p Path) -> &'p Path {
+    match invoked_as.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => scie.parent().unwrap_or_else(|| Path::new("")),
+    }
+}
+
+// N.B.: The XDG base directory spec says relative paths are invalid and should be ignored.
+#[cfg(unix)]
+fn xdg_cache_home(ambient_env: &HashMap<OsString, OsString>) -> Option<PathBuf> {
+    ambient_env
+        .get(OsStr::new("XDG_CACHE_HOME"))
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+#[cfg(windows)]
+fn xdg_cache_home(_ambient_env: &HashMap<OsString, OsString>) -> Option<PathBuf> {
+    None
+}
+
+// N.B.: A bare scie file name lives in the current directory.
+fn scie_dir(scie: &Path) -> Result<&Path, String> {
+    let dir = scie.parent().ok_or_else(|| {
+        format!(
+            "The scie at {scie} has no parent directory.",
+            scie = scie.display()
+        )
+    })?;
+    if dir.as_os_str().is_empty() {
+        Ok(Path::new("."))
+    } else {
+        Ok(dir)
+    }
+}
+
+// N.B.: `atomic_path` only accepts absolute paths; so a base derived from a relative directory is
+// anchored to the current directory.
+fn absolute_dir(dir: &Path) -> Result<PathBuf, String> {
+    if dir.is_absolute() {
+        return Ok(dir.to_path_buf());
+    }
+    let cwd = env::current_dir().map_err(|e| {
+        format!(
+            "Failed to determine the current directory to resolve {dir} against: {e}",
+            dir = dir.display()
+        )
+    })?;
+    Ok(cwd.join(dir).components().collect())
+}
+
+// A lift base can use the `{scie.dir}` placeholder just like a command can; e.g.: to keep the nce
+// cache beside a scie on a USB stick.
+fn reify_base(base: &Path, scie: &Path) -> Result<PathBuf, String> {
+    match base.to_str() {
+        Some(base_str) if base_str.contains("{scie.dir}") => {
+            let scie_dir = absolute_dir(scie_dir(scie)?)?;
+            Ok(PathBuf::from(
+                base_str.replace("{scie.dir}", path_to_str(&scie_dir)?),
+            ))
+        }
+        _ => Ok(base.to_path_buf()),
+    }
+}
+
+// N.B.: The user cache dir already respects XDG_CACHE_HOME where the platform calls for it; so
+// XDG_CACHE_HOME is only consulted directly when there is no user cache dir.
+fn default_base(
+    ambient_env: &HashMap<OsString, OsString>,
+    user_cache_dir: Option<PathBuf>,
+) -> (PathBuf, &'static str) {
+    if let Some(dir) = user_cache_dir {
+        (dir.join("nce"), "the user cache dir")
+    } else if let Some(dir) = xdg_cache_home(ambient_env) {
+        (dir.join("nce"), "XDG_CACHE_HOME")
+    } else {
+        (PathBuf::from("~/.nce"), "the HOME fallback")
+    }
+}
+
+fn resolve_base(
+    ambient_env: &HashMap<OsString, OsString>,
+    lift: &Lift,
+    scie: &Path,
+    invoked_as: &Path,
+) -> Result<PathBuf, String> {
+    let chosen = |base: PathBuf, source: &str| -> Result<PathBuf, String> {
+        debug!(
+            "Using the nce base {base} from {source}.",
+            base = base.display()
+        );
+        Ok(base)
+    };
+    if let Some(base) = ambient_env.get(OsStr::new("SCIE_BASE")) {
+        return chosen(expanduser(Path::new(base))?, "SCIE_BASE");
+    }
+    let relative_base = ambient_env
+        .get(OsStr::new("SCIE_RELATIVE_BASE"))
+        .map(|value| value == "1")
+        .unwrap_or(lift.relative_base);
+    if relative_base {
+        let base = if let Some(base) = &lift.base {
+            expanduser(base)?
+        } else {
+            PathBuf::from(".nce")
+        };
+        return chosen(
+            absolute_dir(invoked_dir(scie, invoked_as))?.join(base),
+            "the relative base",
+        );
+    }
+    let (base, source) = if let Some(base) = &lift.base {
+        // N.B.: A lift base of `./<path>` is relative to the directory containing the scie.
+        if let Ok(path) = base.strip_prefix(".") {
+            (
+                absolute_dir(scie_dir(scie)?)?.join(path),
+                "the lift manifest base relative to the scie",
+            )
+        } else {
+            (reify_base(base, scie)?, "the lift manifest base")
+        }
+    } else {
+        default_base(ambient_env, dirs::cache_dir())
+    };
+    chosen(expanduser(base.as_path())?, source)
+}
+
+// A dev scie can be repacked with changed files but an identical lift manifest; so its unhashed
+// files are keyed by the modification time and size of the scie itself.
+fn dev_key(scie: &Path) -> Result<String, String> {
+    let metadata = scie.metadata().map_err(|e| {
+        format!(
+            "Failed to read the metadata of the dev scie {scie}: {e}",
+            scie = scie.display()
+        )
+    })?;
+    let mtime = metadata
+        .modified()
+        .map_err(|e| e.to_string())
+        .and_then(|modified| {
+            modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| {
+            format!(
+                "Failed to read the modification time of the dev scie {scie}: {e}",
+                scie = scie.display()
+            )
+        })?;
+    Ok(format!(
+        "{size}-{secs}.{nanos:09}",
+        size = metadata.len(),
+        secs = mtime.as_secs(),
+        nanos = mtime.subsec_nanos()
+    ))
+}
+
 impl<'a> Context<'a> {
-    #[time("debug", "Context::{}")]
     fn new(
         scie: &'a Path,
+        invoked_as: &Path,
+        jump: &'a Jump,
+        lift: &'a Lift,
+        installer: &'a Installer,
+    ) -> Result<Self, String> {
+        Self::with_env(
+            scie,
+            invoked_as,
+            jump,
+            lift,
+            installer,
+            env::vars_os().collect(),
+        )
+    }
+
+    /// Creates a context that sees the given `ambient_env` in place of the process environment.
+    #[time("debug", "Context::{}")]
+    fn with_env(
+        scie: &'a Path,
+        invoked_as: &Path,
+        jump: &'a Jump,
+        lift: &'a Lift,
+        installer: &'a Installer,
+        ambient_env: HashMap<OsString, OsString>,
+    ) -> Result<Self, String> {
+        let mut context = Self::bootstrap(scie, invoked_as, jump, lift, installer, ambient_env)?;
+        context.adopt_base_binding()?;
+        context.load_env_file_defaults()?;
+        Ok(context)
+    }
+
+    /// Creates a context that has not yet run any base binding.
+    fn bootstrap(
+        scie: &'a Path,
+        invoked_as: &Path,
         jump: &'a Jump,
         lift: &'a Lift,
         installer: &'a Installer,
+        ambient_env: HashMap<OsString, OsString>,
     ) -> Result<Self, String> {
         let mut files_by_name = BTreeMap::new();
         for file in &lift.files {
@@ -228,17 +615,26 @@ impl<'a> Context<'a> {
                 files_by_name.insert(key.as_str(), file);
             }
         }
-        let base = if let Ok(base) = env::var("SCIE_BASE") {
-            PathBuf::from(base)
-        } else if let Some(base) = &lift.base {
-            base.clone()
-        } else if let Some(dir) = dirs::cache_dir() {
-            dir.join("nce")
+        let base = resolve_base(&ambient_env, lift, scie, invoked_as)?;
+        let lift_manifest = base.join(&lift.hash).join("lift.json");
+        // Shared bindings are keyed by their definitions alone; so scies that differ only in their
+        // files or commands share binding state.
+        let bindings_key = if lift.shared_bindings {
+            let bindings = serde_json::to_vec(&lift.boot.bindings).map_err(|e| {
+                format!(
+                    "Failed to serialize the boot bindings of {name} to key them: {e}",
+                    name = lift.name
+                )
+            })?;
+            fingerprint::digest(&bindings)
         } else {
-            PathBuf::from("~/.nce")
+            lift.hash.clone()
+        };
+        let dev_key = if lift.dev {
+            dev_key(scie)?
+        } else {
+            String::new()
         };
-        let base = expanduser(base.as_path())?;
-        let lift_manifest = base.join(&lift.hash).join("lift.json");
         Ok(Context {
             scie,
             lift,
@@ -255,9 +651,78 @@ impl<'a> Context<'a> {
             lift_manifest_installed: false,
             bound: HashMap::new(),
             installed: HashSet::new(),
+            ambient_env,
+            process_env: false,
+            env_file_defaults: BTreeMap::new(),
+            bindings_key,
+            dev_key,
+            binding_failed: false,
         })
     }
 
+    // The base binding runs under the bootstrap base and can then emit a SCIE_BASE to move the rest
+    // of the scie's cache to.
+    fn adopt_base_binding(&mut self) -> Result<(), String> {
+        let lift = self.lift;
+        let Some(base_binding) = lift.base_binding.as_deref() else {
+            return Ok(());
+        };
+        if self.ambient_env.contains_key(OsStr::new("SCIE_BASE")) {
+            return Ok(());
+        }
+        let binding_env = self.bind(base_binding, &[])?;
+        if let Some(base) = binding_env.get("SCIE_BASE") {
+            let base = expanduser(Path::new(base))?;
+            debug!(
+                "Adopting the base {base} emitted by the {base_binding} binding.",
+                base = base.display()
+            );
+            self.lift_manifest.path = base.join(&self.lift.hash).join("lift.json");
+            // N.B.: Bindings run and files installed so far live under the bootstrap base; so
+            // they must be redone under the adopted base.
+            self.lift_manifest_installed = false;
+            self.bound.clear();
+            self.installed.clear();
+            self.base = base;
+        }
+        Ok(())
+    }
+
+    // N.B.: The env file is installed under the final base; so it is only loaded after any base
+    // binding has run.
+    fn load_env_file_defaults(&mut self) -> Result<(), String> {
+        let Some(name) = self.lift.env_file.as_deref() else {
+            return Ok(());
+        };
+        let file = *self
+            .files_by_name
+            .get(name)
+            .ok_or_else(|| format!("The env_file {name} does not name a file in this scie."))?;
+        if file.file_type != FileType::Blob {
+            return Err(format!(
+                "The env_file {name} must be a blob but it is of type {file_type:?}.",
+                file_type = file.file_type
+            ));
+        }
+        self.replacements.insert(file);
+        let files = self.file_entries()?;
+        self.installer.install(&files)?;
+        self.mark_installed(files);
+        if self.installer.is_dry_run() {
+            debug!("Skipping loading the env_file {name} for a dry run.");
+            return Ok(());
+        }
+        let env = load_env_file(&self.get_path(file), EnvFileFormat::Dotenv)
+            .map_err(|e| format!("Failed to load the env_file {name}: {e}"))?;
+        for (key, value) in env {
+            self.ambient_env
+                .entry(OsString::from(&key))
+                .or_insert_with(|| OsString::from(&value));
+            self.env_file_defaults.insert(key, value);
+        }
+        Ok(())
+    }
+
     fn prepare_process(&mut self, cmd: &'a Cmd) -> Result<Process, String> {
         let mut needs_lift_manifest = false;
         let (exe, needs_manifest) = self.reify_string(&cmd.exe)?;
@@ -269,7 +734,18 @@ impl<'a> Context<'a> {
             needs_lift_manifest |= needs_manifest;
             args.push(reified_arg.into());
         }
-        let mut vars = vec![];
+        let mut args_append = vec![];
+        for arg in &cmd.args_append {
+            let (reified_arg, needs_manifest) = self.reify_string(arg)?;
+            needs_lift_manifest |= needs_manifest;
+            args_append.push(reified_arg.into());
+        }
+        // N.B.: The env file defaults come first so that the command's own env takes precedence.
+        let mut vars = self
+            .env_file_defaults
+            .iter()
+            .map(|(key, value)| EnvVar::Default((key.into(), value.into())))
+            .collect::<Vec<_>>();
         for (key, value) in cmd.env.iter() {
             let final_value = match value {
                 Some(val) => {
@@ -282,10 +758,19 @@ impl<'a> Context<'a> {
             vars.push(EnvVar::try_from((key, final_value))?);
         }
 
+        let (stdout, needs_manifest) = self.reify_optional_string(&cmd.stdout)?;
+        needs_lift_manifest |= needs_manifest;
+        let (stderr, needs_manifest) = self.reify_optional_string(&cmd.stderr)?;
+        needs_lift_manifest |= needs_manifest;
+
         let process = Process {
             env: EnvVars { vars },
             exe: exe.into(),
             args,
+            args_append,
+            stdout,
+            stderr,
+            inherit_fds: cmd.inherit_fds.clone(),
         };
         if needs_lift_manifest {
             self.lift_manifest_dependants.insert(process.clone());
@@ -293,21 +778,38 @@ impl<'a> Context<'a> {
         Ok(process)
     }
 
+    fn reify_optional_string(
+        &mut self,
+        value: &'a Option<String>,
+    ) -> Result<(Option<OsString>, bool), String> {
+        if let Some(val) = value {
+            let (reified_value, needs_manifest) = self.reify_string(val)?;
+            Ok((Some(reified_value.into()), needs_manifest))
+        } else {
+            Ok((None, false))
+        }
+    }
+
     fn prepare(&mut self, cmd: &'a Cmd) -> Result<(Process, Vec<FileEntry>), String> {
         let process = self.prepare_process(cmd)?;
+        let file_entries = self.file_entries()?;
+        Ok((process, file_entries))
+    }
 
+    // The entries for all the files referenced so far that are not yet installed.
+    fn file_entries(&mut self) -> Result<Vec<FileEntry>, String> {
         let mut load_entries = vec![];
         for file in &self.lift.files {
             if self.replacements.contains(&file) && !self.installed.contains(file) {
                 if let Source::LoadBinding(binding_name) = &file.source {
                     let path = self.get_path(file);
-                    let file_source_process = self.prepare_process(
-                        self.lift
-                            .boot
-                            .bindings
-                            .get(binding_name)
-                            .ok_or_else(|| format!("No boot binding named {binding_name}."))?,
-                    )?;
+                    let binding = self
+                        .lift
+                        .boot
+                        .bindings
+                        .get(binding_name)
+                        .ok_or_else(|| format!("No boot binding named {binding_name}."))?;
+                    let file_source_process = self.prepare_process(binding)?;
                     let lift_manifest = if !self.lift_manifest_installed
                         && self.lift_manifest_dependants.contains(&file_source_process)
                     {
@@ -317,22 +819,34 @@ impl<'a> Context<'a> {
                     };
                     load_entries.push(FileEntry::LoadAndInstall((
                         LoadProcess {
+                            name: binding_name.to_string(),
                             lift_manifest,
                             process: file_source_process,
+                            timeout: binding.timeout_secs.map(Duration::from_secs),
                         },
                         file.clone(),
                         path,
                     )))
+                } else if let Source::Url(_) = &file.source {
+                    load_entries.push(FileEntry::LoadUrl((file.clone(), self.get_path(file))))
                 }
             }
         }
 
         let mut scie_tote = vec![];
         let mut file_entries = vec![];
+        let mut paths = HashSet::new();
         for (index, file) in self.lift.files.iter().enumerate() {
             if self.replacements.contains(&file) && !self.installed.contains(file) {
                 let path = self.get_path(file);
-                if file.size == 0 {
+                if !paths.insert(path.clone()) {
+                    // Files extracted by hash alone can share a path; so we only extract once.
+                    file_entries.push(FileEntry::Skip(if file.source == Source::Scie {
+                        file.size
+                    } else {
+                        0
+                    }))
+                } else if file.size == 0 {
                     scie_tote.push((file.clone(), path));
                 } else if Source::Scie == file.source {
                     file_entries.push(FileEntry::Install((file.clone(), path)));
@@ -365,7 +879,7 @@ impl<'a> Context<'a> {
         // extracted for use in the load process.
         file_entries.append(&mut load_entries);
 
-        Ok((process, file_entries))
+        Ok(file_entries)
     }
 
     fn select_cmd(
@@ -373,23 +887,83 @@ impl<'a> Context<'a> {
         name: &str,
         argv1_consumed: bool,
     ) -> Result<Option<SelectedCmd>, String> {
-        if let Some(cmd) = self.lift.boot.commands.get(name) {
+        if let Some(mut cmd) = self.lift.boot.commands.get(name) {
+            // N.B.: The whole chain of stages is checked before any stage runs; so a misconfigured
+            // chain fails before it has any side effects.
+            let mut stages = vec![(name, cmd)];
+            while let Some(next) = cmd.then.as_deref() {
+                let names = stages.iter().map(|(stage, _)| *stage).collect::<Vec<_>>();
+                if names.contains(&next) {
+                    return Err(format!(
+                        "The command {name} has a cycle in its stages: {stages} -> {next}",
+                        name = names[0],
+                        stages = names.join(" -> ")
+                    ));
+                }
+                cmd = self.lift.boot.commands.get(next).ok_or_else(|| {
+                    format!(
+                        "The command {stage} says to run {next} next, but there is no such \
+                        command.",
+                        stage = names[names.len() - 1]
+                    )
+                })?;
+                stages.push((next, cmd));
+            }
+            stages.pop();
+            for (stage, stage_cmd) in stages {
+                self.run_stage(stage, stage_cmd)?;
+            }
             let (process, files) = self.prepare(cmd)?;
             self.maybe_install_lift_manifest(&process)?;
+            let exe_member = self.archive_member(cmd, &process)?;
             return Ok(Some(SelectedCmd {
                 process,
                 files,
                 argv1_consumed,
+                exe_member,
             }));
         }
         Ok(None)
     }
 
-    fn select_command(&mut self, invoked_as: &Path) -> Result<Option<SelectedCmd>, String> {
-        if let Some(cmd) = env::var_os("SCIE_BOOT") {
-            // Avoid subprocesses that re-execute this SCIE unintentionally getting in an infinite
-            // loop.
-            env::remove_var("SCIE_BOOT");
+    fn run_stage(&mut self, name: &str, cmd: &'a Cmd) -> Result<(), String> {
+        let (process, files) = self.prepare(cmd)?;
+        if self.installer.is_dry_run() {
+            debug!("Skipping stage {name} for a dry run: {process:#?}");
+            return Ok(());
+        }
+        self.maybe_install_lift_manifest(&process)?;
+        let exe_member = self.archive_member(cmd, &process)?;
+        self.installer.install(files.as_slice())?;
+        self.mark_installed(files);
+        if let Some(exe_member) = exe_member {
+            exe_member.check()?;
+        }
+        debug!("Running stage {name}: {process:#?}");
+        match process.execute(std::iter::empty()) {
+            Err(err) => Err(format!("Failed to launch stage {name}: {err}")),
+            Ok(exit_status) if !exit_status.success() => {
+                Err(format!("The stage {name} failed: {exit_status}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn consume_var(&self, name: &str) -> Option<OsString> {
+        if self.process_env {
+            env::remove_var(name);
+        }
+        self.ambient_env.get(OsStr::new(name)).cloned()
+    }
+
+    fn select_command(
+        &mut self,
+        invoked_as: &Path,
+        argv1: Option<&str>,
+    ) -> Result<Option<SelectedCmd>, String> {
+        // Avoid subprocesses that re-execute this SCIE unintentionally getting in an infinite
+        // loop.
+        if let Some(cmd) = self.consume_var("SCIE_BOOT") {
             let name = cmd.into_string().map_err(|value| {
                 format!("Failed to decode environment variable SCIE_BOOT: {value:?}")
             })?;
@@ -398,33 +972,87 @@ impl<'a> Context<'a> {
         if let Some(selected_cmd) = self.select_cmd("", false)? {
             return Ok(Some(selected_cmd));
         }
-
-        #[cfg(windows)]
-        let basename = invoked_as.file_stem().and_then(try_as_str);
-
-        #[cfg(unix)]
-        let basename = invoked_as.file_name().and_then(try_as_str);
-
-        if let Some(basename) = basename {
-            if let Some(selected_command) = self.select_cmd(basename, false)? {
+        // Like SCIE_BOOT, we don't propagate SCIE_NAME to subprocesses that may re-execute this
+        // SCIE.
+        let scie_name = self.consume_var("SCIE_NAME");
+        if let Some(name) = invoked_name(invoked_as, scie_name.as_deref()) {
+            if let Some(selected_command) = self.select_cmd(name, false)? {
                 return Ok(Some(selected_command));
             }
         }
-        if let Some(argv1) = env::args().nth(1) {
-            return self.select_cmd(&argv1, true);
+        if let Some(argv1) = argv1 {
+            return self.select_cmd(argv1, true);
         }
         Ok(None)
     }
 
+    // N.B.: Only exes of the form `{<archive>}/<member>` are recognized; so exes with dynamic
+    // archive names or members go unchecked.
+    fn archive_member(
+        &self,
+        cmd: &Cmd,
+        process: &Process,
+    ) -> Result<Option<ArchiveMember>, String> {
+        let parsed = placeholders::parse(&cmd.exe)?;
+        let (name, rest) = match &parsed.items[..] {
+            [Item::Placeholder(Placeholder::FileName(name)), rest @ ..] => (*name, rest),
+            _ => return Ok(None),
+        };
+        let file = match self.files_by_name.get(name) {
+            Some(file) if matches!(file.file_type, FileType::Archive(_) | FileType::Directory) => {
+                file
+            }
+            _ => return Ok(None),
+        };
+        let mut member = String::new();
+        for item in rest {
+            match item {
+                Item::Text(text) => member.push_str(text),
+                Item::LeftBrace => member.push('{'),
+                Item::Placeholder(_) => return Ok(None),
+            }
+        }
+        let member = member.trim_start_matches(|c: char| c == '/' || c == '\\');
+        if member.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(ArchiveMember {
+            archive: file.name.clone(),
+            member: member.to_string(),
+            path: PathBuf::from(&process.exe),
+        }))
+    }
+
     fn get_path(&self, file: &File) -> PathBuf {
-        self.base.join(&file.hash).join(&file.name)
+        // N.B.: The files of dev scies may have no hash; so we key those by the lift manifest hash
+        // and the dev key.
+        if file.hash.is_empty() {
+            return self
+                .base
+                .join(&self.lift.hash)
+                .join("dev")
+                .join(&self.dev_key)
+                .join(&file.name);
+        }
+        let path = self.base.join(&file.hash);
+        match file.file_type {
+            FileType::Archive(_) | FileType::Directory if self.lift.extract_by_hash => path,
+            _ => path.join(&file.name),
+        }
     }
 
     fn get_bindings_dir(&self) -> PathBuf {
-        self.base.join(&self.lift.hash).join("bindings")
+        self.base.join(&self.bindings_key).join("bindings")
+    }
+
+    fn get_locks_dir(&self) -> PathBuf {
+        self.base.join(&self.bindings_key).join("locks")
     }
 
     fn maybe_install_lift_manifest(&mut self, process: &Process) -> Result<(), String> {
+        if self.installer.is_dry_run() {
+            return Ok(());
+        }
         if !self.lift_manifest_installed && self.lift_manifest_dependants.contains(process) {
             self.lift_manifest.install()?;
             self.lift_manifest_installed = true;
@@ -432,64 +1060,138 @@ impl<'a> Context<'a> {
         Ok(())
     }
 
-    fn parse_env(&mut self, env: &'a str) -> Result<(String, String, bool), String> {
+    fn parse_env(&mut self, env: &'a str) -> Result<(ParsedEnv, bool), String> {
         let (parsed_env, needs_lift_manifest) = self.reify_string(env)?;
-        let (name, default) = match parsed_env.splitn(2, '=').collect::<Vec<_>>()[..] {
-            [name] => (name, ""),
-            [name, default] => (name, default),
-            _ => {
-                return Err(
-                    "Expected {{scie.env.<name>}} <name> placeholder to be a non-empty \
-                            string"
-                        .to_string(),
-                )
+        let default = parsed_env.find('=');
+        let alternate = parsed_env.find(":+");
+        let (name, mode, word) = match (default, alternate) {
+            (Some(default), Some(alternate)) if alternate < default => (
+                &parsed_env[..alternate],
+                EnvMode::Alternate,
+                &parsed_env[alternate + 2..],
+            ),
+            (None, Some(alternate)) => (
+                &parsed_env[..alternate],
+                EnvMode::Alternate,
+                &parsed_env[alternate + 2..],
+            ),
+            (Some(default), _) => (
+                &parsed_env[..default],
+                EnvMode::Default,
+                &parsed_env[default + 1..],
+            ),
+            (None, None) => (parsed_env.as_str(), EnvMode::Default, ""),
+        };
+        let (name, index) = match name
+            .strip_suffix(']')
+            .and_then(|name| name.rsplit_once('['))
+        {
+            Some((name, index)) => {
+                let index = index.parse::<usize>().map_err(|e| {
+                    format!(
+                        "Expected the index of {{scie.env.{name}[{index}]}} to be a non-negative \
+                        integer: {e}"
+                    )
+                })?;
+                (name, Some(index))
             }
+            None => (name, None),
         };
-        Ok((name.to_string(), default.to_string(), needs_lift_manifest))
+        if name.is_empty() {
+            return Err(
+                "Expected {{scie.env.<name>}} <name> placeholder to be a non-empty string"
+                    .to_string(),
+            );
+        }
+        Ok((
+            ParsedEnv {
+                name: name.to_string(),
+                index,
+                mode,
+                word: word.to_string(),
+            },
+            needs_lift_manifest,
+        ))
     }
 
-    fn bind(&mut self, name: &'a str) -> Result<HashMap<String, String>, String> {
-        if let Some(binding) = self.bound.get(name) {
+    // Bindings passed arguments are run once per distinct argument list. The arguments are part of
+    // the binding process and so of the process hash that keys its lock.
+    fn bind(&mut self, name: &'a str, args: &[&'a str]) -> Result<HashMap<String, String>, String> {
+        let key = (name, args.to_vec());
+        if let Some(binding) = self.bound.get(&key) {
             binding.load_env()
         } else {
-            let (process, files) = self.prepare(
-                self.lift
-                    .boot
-                    .bindings
-                    .get(name)
-                    .ok_or_else(|| format!("No boot binding named {name}."))?,
-            )?;
+            let cmd = self
+                .lift
+                .boot
+                .bindings
+                .get(name)
+                .ok_or_else(|| format!("No boot binding named {name}."))?;
+            let (mut process, files) = self.prepare(cmd)?;
+            process.args.extend(args.iter().map(OsString::from));
             let process_hash = process.fingerprint()?;
             let boot_binding = Binding {
-                target: self
-                    .base
-                    .join(&self.lift.hash)
-                    .join("locks")
-                    .join(format!("{name}-{process_hash}")),
+                name: name.to_string(),
+                target: self.get_locks_dir().join(format!("{name}-{process_hash}")),
                 process,
+                capture_stderr: self
+                    .ambient_env
+                    .contains_key(OsStr::new("SCIE_CAPTURE_BINDING_STDERR")),
+                env_file_format: cmd.env_file_format.unwrap_or_default(),
+                timeout: cmd.timeout_secs.map(Duration::from_secs),
             };
-            let binding_env = boot_binding.execute(|| {
+            if self.installer.is_dry_run() {
+                // A binding that already ran can still supply its env, but we never run one.
+                return if boot_binding.target.is_file() {
+                    boot_binding.load_env()
+                } else {
+                    Ok(HashMap::new())
+                };
+            }
+            let binding_env = match boot_binding.execute(|| {
                 self.maybe_install_lift_manifest(&boot_binding.process)?;
                 self.installer.install(files.as_slice())
-            })?;
-            self.bound.insert(name, boot_binding);
-            for file_entry in files {
-                match file_entry {
-                    FileEntry::Skip(_) => {}
-                    FileEntry::Install((file, _)) => {
-                        self.installed.insert(file);
-                    }
-                    FileEntry::LoadAndInstall((_, file, _)) => {
+            }) {
+                Ok(binding_env) => binding_env,
+                Err(err) => {
+                    self.binding_failed = true;
+                    return Err(err);
+                }
+            };
+            self.bound.insert(key, boot_binding);
+            self.mark_installed(files);
+            Ok(binding_env)
+        }
+    }
+
+    /// The exit code to report a failure to select a command with.
+    fn exit_code(&self) -> i32 {
+        if self.binding_failed {
+            exit_code::BINDING_ERROR
+        } else {
+            exit_code::SELECTION_ERROR
+        }
+    }
+
+    fn mark_installed(&mut self, files: Vec<FileEntry>) {
+        for file_entry in files {
+            match file_entry {
+                FileEntry::Skip(_) => {}
+                FileEntry::Install((file, _)) => {
+                    self.installed.insert(file);
+                }
+                FileEntry::LoadAndInstall((_, file, _)) => {
+                    self.installed.insert(file);
+                }
+                FileEntry::LoadUrl((file, _)) => {
+                    self.installed.insert(file);
+                }
+                FileEntry::ScieTote((_, tote_entries)) => {
+                    for (file, _) in tote_entries {
                         self.installed.insert(file);
                     }
-                    FileEntry::ScieTote((_, tote_entries)) => {
-                        for (file, _) in tote_entries {
-                            self.installed.insert(file);
-                        }
-                    }
                 }
             }
-            Ok(binding_env)
         }
     }
 
@@ -520,20 +1222,30 @@ impl<'a> Context<'a> {
                         .files_by_name
                         .get(parsed_name.as_str())
                         .ok_or_else(|| {
-                            format!("No file named {parsed_name} is stored in this scie.")
+                            placeholders::unknown_scie_placeholder(&parsed_name).unwrap_or_else(
+                                || format!("No file named {parsed_name} is stored in this scie."),
+                            )
                         })?;
                     let path = self.get_path(file);
                     reified.push_str(path_to_str(&path)?);
                     self.replacements.insert(file);
                 }
                 Item::Placeholder(Placeholder::Env(env)) => {
-                    let (name, default, needs_manifest) = self.parse_env(env)?;
+                    let (parsed_env, needs_manifest) = self.parse_env(env)?;
                     lift_manifest_required |= needs_manifest;
-                    let env_var = env::var_os(&name).unwrap_or_else(|| default.into());
-                    let value = env_var.into_string().map_err(|value| {
-                        format!("Failed to decode env var {name} as utf-8 value: {value:?}")
-                    })?;
-                    reified.push_str(&value)
+                    let value = self
+                        .ambient_env
+                        .get(OsStr::new(parsed_env.name.as_str()))
+                        .map(|value| {
+                            value.to_str().ok_or_else(|| {
+                                format!(
+                                    "Failed to decode env var {name} as utf-8 value: {value:?}",
+                                    name = parsed_env.name
+                                )
+                            })
+                        })
+                        .transpose()?;
+                    reified.push_str(parsed_env.resolve(value))
                 }
                 Item::Placeholder(Placeholder::Scie) => reified.push_str(path_to_str(self.scie)?),
                 Item::Placeholder(Placeholder::ScieBase) => {
@@ -542,24 +1254,39 @@ impl<'a> Context<'a> {
                 Item::Placeholder(Placeholder::ScieBindings) => {
                     reified.push_str(path_to_str(self.get_bindings_dir().as_path())?);
                 }
-                Item::Placeholder(Placeholder::ScieBindingCmd(name)) => {
-                    self.bind(name)?;
+                Item::Placeholder(Placeholder::ScieBindingCmd(ScieBindingCmd {
+                    binding,
+                    args,
+                })) => {
+                    self.bind(binding, &args)?;
                     reified.push_str(path_to_str(self.get_bindings_dir().as_path())?);
                 }
-                Item::Placeholder(Placeholder::ScieBindingEnv(ScieBindingEnv { binding, env })) => {
-                    let binding_env = self.bind(binding)?;
-                    let (name, default, needs_manifest) = self.parse_env(env)?;
+                Item::Placeholder(Placeholder::ScieBindingEnv(ScieBindingEnv {
+                    binding,
+                    args,
+                    env,
+                })) => {
+                    let binding_env = self.bind(binding, &args)?;
+                    let (parsed_env, needs_manifest) = self.parse_env(env)?;
                     lift_manifest_required |= needs_manifest;
                     let value = binding_env
-                        .get(name.as_str())
-                        .map(String::to_owned)
-                        .unwrap_or(default);
-                    reified.push_str(&value)
+                        .get(parsed_env.name.as_str())
+                        .map(String::as_str);
+                    reified.push_str(parsed_env.resolve(value))
+                }
+                Item::Placeholder(Placeholder::ScieDir) => {
+                    reified.push_str(path_to_str(scie_dir(self.scie)?)?)
+                }
+                Item::Placeholder(Placeholder::ScieFilesCount) => {
+                    reified.push_str(self.lift.files.len().to_string().as_str())
                 }
                 Item::Placeholder(Placeholder::ScieLift) => {
                     lift_manifest_required = true;
                     reified.push_str(path_to_str(&self.lift_manifest.path)?);
                 }
+                Item::Placeholder(Placeholder::SciePid) => {
+                    reified.push_str(std::process::id().to_string().as_str())
+                }
                 Item::Placeholder(Placeholder::SciePlatform) => reified.push_str(
                     format!(
                         "{os}-{arch}",
@@ -572,30 +1299,126 @@ impl<'a> Context<'a> {
                     reified.push_str(env::consts::ARCH)
                 }
                 Item::Placeholder(Placeholder::SciePlatformOs) => reified.push_str(env::consts::OS),
+                Item::Placeholder(Placeholder::ScieSizeTotal) => {
+                    let total: usize = self
+                        .lift
+                        .files
+                        .iter()
+                        .filter(|file| file.source == Source::Scie)
+                        .map(|file| file.size)
+                        .sum();
+                    reified.push_str(total.to_string().as_str())
+                }
             }
         }
         Ok((reified, lift_manifest_required))
     }
 }
 
+/// Resolves the nce cache base the scie uses before any base binding runs.
+pub(crate) fn base(current_exe: &CurrentExe, lift: &Lift) -> Result<PathBuf, String> {
+    resolve_base(
+        &env::vars_os().collect(),
+        lift,
+        &current_exe.exe,
+        &current_exe.invoked_as,
+    )
+}
+
+/// Finds the command `select_command` would select without selecting it.
+///
+/// This allows decisions that must be made before the `Context` is created, like whether to load
+/// env files, to depend on the command that will run.
+pub(crate) fn peek_command<'a>(
+    current_exe: &CurrentExe,
+    lift: &'a Lift,
+    name: Option<&str>,
+    ambient_env: &HashMap<OsString, OsString>,
+    argv1: Option<&str>,
+) -> Option<&'a Cmd> {
+    let commands = &lift.boot.commands;
+    if let Some(name) = name {
+        return commands.get(name);
+    }
+    if let Some(name) = ambient_env.get(OsStr::new("SCIE_BOOT")) {
+        return name.to_str().and_then(|name| commands.get(name));
+    }
+    if let Some(cmd) = commands.get("") {
+        return Some(cmd);
+    }
+    let scie_name = ambient_env.get(OsStr::new("SCIE_NAME"));
+    if let Some(cmd) = invoked_name(&current_exe.invoked_as, scie_name.map(OsString::as_os_str))
+        .and_then(|name| commands.get(name))
+    {
+        return Some(cmd);
+    }
+    argv1.and_then(|argv1| commands.get(argv1))
+}
+
+/// Selects the command with the given name or else the command the environment and argv select.
 pub(crate) fn select_command(
     current_exe: &CurrentExe,
     jump: &Jump,
     lift: &Lift,
     installer: &Installer,
+    name: Option<&str>,
+) -> Result<Option<SelectedCmd>, (String, i32)> {
+    let mut context = Context::bootstrap(
+        &current_exe.exe,
+        &current_exe.invoked_as,
+        jump,
+        lift,
+        installer,
+        env::vars_os().collect(),
+    )
+    .map_err(|e| (e, exit_code::CONFIG_ERROR))?;
+    context.process_env = true;
+    let result = context
+        .adopt_base_binding()
+        .and_then(|_| context.load_env_file_defaults())
+        .and_then(|_| match name {
+            Some(name) => context.select_cmd(name, false),
+            None => context.select_command(&current_exe.invoked_as, env::args().nth(1).as_deref()),
+        });
+    result.map_err(|e| (e, context.exit_code()))
+}
+
+/// Selects the command with the given name, where the empty name selects the default command.
+///
+/// The command is reified against the given `ambient_env` or else the process environment.
+pub(crate) fn select_named_command(
+    scie: &Path,
+    jump: &Jump,
+    lift: &Lift,
+    installer: &Installer,
+    name: &str,
+    ambient_env: Option<HashMap<OsString, OsString>>,
 ) -> Result<Option<SelectedCmd>, String> {
-    let mut context = Context::new(&current_exe.exe, jump, lift, installer)?;
-    context.select_command(&current_exe.invoked_as)
+    let mut context = if let Some(ambient_env) = ambient_env {
+        Context::with_env(scie, scie, jump, lift, installer, ambient_env)?
+    } else {
+        Context::new(scie, scie, jump, lift, installer)?
+    };
+    context.select_cmd(name, false)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::collections::HashMap;
+    use std::ffi::{OsStr, OsString};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
-    use super::Context;
-    use crate::config::{Boot, FileType};
+    use super::{
+        default_base, invoked_name, load_env_file, peek_command, resolve_base,
+        select_named_command, Binding, Context, FileEntry,
+    };
+    use crate::config::{ArchiveType, Boot, Cmd, EnvFileFormat, FileType};
     use crate::installer::Installer;
-    use crate::{File, Jump, Lift, Source};
+    use crate::process::EnvVar;
+    use crate::{
+        config, exit_code, testing, CurrentExe, EnvVars, File, Jump, Lift, Process, Source,
+    };
 
     #[test]
     fn env() {
@@ -605,32 +1428,31 @@ mod tests {
         };
         let tempdir = tempfile::tempdir().unwrap();
         let lift = Lift {
-            name: "test".to_string(),
-            description: None,
             base: Some(tempdir.path().to_path_buf()),
             load_dotenv: true,
-            size: 137,
-            hash: "abc".to_string(),
             boot: Boot {
                 commands: Default::default(),
                 bindings: Default::default(),
             },
             files: vec![File {
                 name: "file".to_string(),
-                key: None,
                 size: 37,
                 hash: "def".to_string(),
-                file_type: FileType::Blob,
-                executable: None,
-                eager_extract: false,
-                source: Source::Scie,
+                ..File::default()
             }],
-            other: None,
+            ..testing::lift()
         };
         let installer = Installer::new(&[]);
-        let mut context = Context::new(Path::new("scie_path"), &jump, &lift, &installer).unwrap();
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
 
-        assert!(std::env::var_os("__DNE__").is_none());
         assert_eq!(
             ("".to_string(), false),
             context.reify_string("{scie.env.__DNE__}").unwrap()
@@ -640,12 +1462,12 @@ mod tests {
             context.reify_string("{scie.env.__DNE__=default}").unwrap()
         );
 
-        std::env::set_var("__DNE__", "foo");
+        context.ambient_env.insert("__DNE__".into(), "foo".into());
         assert_eq!(
             ("foo".to_string(), false),
             context.reify_string("{scie.env.__DNE__=default}").unwrap()
         );
-        std::env::remove_var("__DNE__");
+        context.ambient_env.remove(OsStr::new("__DNE__"));
 
         assert_eq!(
             ("scie_path".to_string(), false),
@@ -680,20 +1502,1403 @@ mod tests {
             context.reify_string("{scie.env.__DNE__={file}}").unwrap()
         );
 
-        assert!(std::env::var_os("__DNE2__").is_none());
         assert_eq!(
             ("42".to_string(), false),
             context
                 .reify_string("{scie.env.__DNE__={scie.env.__DNE2__=42}}")
                 .unwrap()
         );
-        std::env::set_var("__DNE2__", "bar");
+        context.ambient_env.insert("__DNE2__".into(), "bar".into());
         assert_eq!(
             ("bar".to_string(), false),
             context
                 .reify_string("{scie.env.__DNE__={scie.env.__DNE2__=42}}")
                 .unwrap()
         );
-        std::env::remove_var("__DNE2__");
+    }
+
+    #[test]
+    fn ambient_env() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let lift = Lift {
+            base: Some(Path::new("/lift/base").to_path_buf()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            vec![("__INJECTED__".into(), "injected".into())]
+                .into_iter()
+                .collect(),
+        )
+        .unwrap();
+
+        // Any process env var will do to show the process env is not consulted.
+        if let Some(ambient) = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .find(|name| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        {
+            assert_eq!(
+                ("default".to_string(), false),
+                context
+                    .reify_string(format!("{{scie.env.{ambient}=default}}").as_str())
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            ("injected".to_string(), false),
+            context.reify_string("{scie.env.__INJECTED__}").unwrap()
+        );
+        assert_eq!(
+            ("/lift/base".to_string(), false),
+            context.reify_string("{scie.base}").unwrap()
+        );
+    }
+
+    #[test]
+    fn injected_selection() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let cmd = |exe: &str| Cmd {
+            exe: exe.to_string(),
+            ..Default::default()
+        };
+        let lift = Lift {
+            base: Some(Path::new("/lift/base").to_path_buf()),
+            boot: Boot {
+                commands: vec![
+                    ("booted".to_string(), cmd("booted")),
+                    ("named".to_string(), cmd("named")),
+                    ("argv1".to_string(), cmd("argv1")),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let select = |env: &[(&str, &str)], argv1: Option<&str>| {
+            let mut context = Context::with_env(
+                Path::new("scie_path"),
+                Path::new("scie_path"),
+                &jump,
+                &lift,
+                &installer,
+                env.iter()
+                    .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+                    .collect(),
+            )
+            .unwrap();
+            context
+                .select_command(Path::new("scie_path"), argv1)
+                .unwrap()
+                .map(|selected| (selected.process.exe, selected.argv1_consumed))
+        };
+
+        assert_eq!(
+            Some((OsString::from("booted"), false)),
+            select(&[("SCIE_BOOT", "booted"), ("SCIE_NAME", "named")], None)
+        );
+        assert_eq!(
+            Some((OsString::from("named"), false)),
+            select(&[("SCIE_NAME", "named")], Some("argv1"))
+        );
+        assert_eq!(
+            Some((OsString::from("argv1"), true)),
+            select(&[], Some("argv1"))
+        );
+        assert_eq!(None, select(&[], None));
+    }
+
+    #[test]
+    fn env_alternate() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let lift = Lift {
+            base: Some(Path::new("/base").to_path_buf()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            ("".to_string(), false),
+            context.reify_string("{scie.env.DEBUG:+--verbose}").unwrap()
+        );
+
+        context.ambient_env.insert("DEBUG".into(), "".into());
+        assert_eq!(
+            ("".to_string(), false),
+            context.reify_string("{scie.env.DEBUG:+--verbose}").unwrap()
+        );
+
+        context.ambient_env.insert("DEBUG".into(), "1".into());
+        assert_eq!(
+            ("--verbose".to_string(), false),
+            context.reify_string("{scie.env.DEBUG:+--verbose}").unwrap()
+        );
+        assert_eq!(
+            ("--log=/base/debug.log".to_string(), false),
+            context
+                .reify_string("{scie.env.DEBUG:+--log={scie.base}/debug.log}")
+                .unwrap()
+        );
+        assert_eq!(
+            ("--level=42".to_string(), false),
+            context
+                .reify_string("{scie.env.DEBUG:+--level={scie.env.LEVEL=42}}")
+                .unwrap()
+        );
+
+        // The first operator wins.
+        assert_eq!(
+            ("1".to_string(), false),
+            context.reify_string("{scie.env.DEBUG=a:+b}").unwrap()
+        );
+        assert_eq!(
+            ("a=b".to_string(), false),
+            context.reify_string("{scie.env.DEBUG:+a=b}").unwrap()
+        );
+    }
+
+    #[test]
+    fn env_index() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let lift = Lift {
+            base: Some(Path::new("/base").to_path_buf()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+        context.ambient_env.insert(
+            "DIRS".into(),
+            format!("/usr/local/bin{PATH_SEPARATOR}/usr/bin").into(),
+        );
+
+        assert_eq!(
+            ("/usr/local/bin".to_string(), false),
+            context.reify_string("{scie.env.DIRS[0]}").unwrap()
+        );
+        assert_eq!(
+            ("/usr/bin".to_string(), false),
+            context.reify_string("{scie.env.DIRS[1]}").unwrap()
+        );
+        assert_eq!(
+            ("".to_string(), false),
+            context.reify_string("{scie.env.DIRS[2]}").unwrap()
+        );
+        assert_eq!(
+            ("/bin".to_string(), false),
+            context.reify_string("{scie.env.DIRS[2]=/bin}").unwrap()
+        );
+        assert_eq!(
+            ("/usr/bin".to_string(), false),
+            context.reify_string("{scie.env.DIRS[1]=/bin}").unwrap()
+        );
+        assert_eq!(
+            ("/bin".to_string(), false),
+            context.reify_string("{scie.env.UNSET[0]=/bin}").unwrap()
+        );
+        assert!(context.reify_string("{scie.env.DIRS[-1]}").is_err());
+    }
+
+    #[test]
+    fn extract_by_hash() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let archive = |name: &str| File {
+            name: name.to_string(),
+            size: 37,
+            hash: "def".to_string(),
+            file_type: FileType::Archive(ArchiveType::Zip),
+            ..File::default()
+        };
+        let lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            extract_by_hash: true,
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            files: vec![archive("foo.zip"), archive("bar.zip")],
+            ..testing::lift()
+        };
+        let cmd = Cmd {
+            exe: "{foo.zip}/exe".to_string(),
+            args: vec!["{bar.zip}".to_string()],
+            ..Default::default()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let expected = tempdir.path().join("def").to_str().unwrap().to_string();
+        assert_eq!(
+            (expected.clone(), false),
+            context.reify_string("{foo.zip}").unwrap()
+        );
+        assert_eq!(
+            (expected, false),
+            context.reify_string("{bar.zip}").unwrap()
+        );
+        let (_, file_entries) = context.prepare(&cmd).unwrap();
+        assert_eq!(
+            1,
+            file_entries
+                .iter()
+                .filter(|entry| matches!(entry, FileEntry::Install(_)))
+                .count()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn staged_boot() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let cmd = |script: &str, then: Option<&str>| Cmd {
+            exe: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            then: then.map(str::to_string),
+            ..Default::default()
+        };
+        let mut lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            boot: Boot {
+                commands: vec![
+                    (
+                        "".to_string(),
+                        cmd("echo prelude > {scie.base}/prelude", Some("main")),
+                    ),
+                    ("main".to_string(), cmd("cat {scie.base}/prelude", None)),
+                    (
+                        "cycle".to_string(),
+                        cmd("touch {scie.base}/cycle", Some("cycle")),
+                    ),
+                    (
+                        "dangling".to_string(),
+                        cmd("touch {scie.base}/dangling", Some("dne")),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let selected_cmd = context.select_cmd("", false).unwrap().unwrap();
+        assert_eq!(
+            "prelude\n",
+            std::fs::read_to_string(tempdir.path().join("prelude")).unwrap()
+        );
+        assert_eq!(
+            vec![
+                "-c".to_string(),
+                format!("cat {base}/prelude", base = tempdir.path().display())
+            ],
+            selected_cmd
+                .process
+                .args
+                .iter()
+                .map(|arg| arg.to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        );
+
+        assert!(context.select_cmd("cycle", false).is_err());
+        assert!(context.select_cmd("dangling", false).is_err());
+        assert!(!tempdir.path().join("cycle").exists());
+        assert!(!tempdir.path().join("dangling").exists());
+
+        lift.boot
+            .commands
+            .insert("".to_string(), cmd("exit 1", Some("main")));
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+        assert!(context.select_cmd("", false).is_err());
+    }
+
+    #[test]
+    fn base_binding() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let bootstrap_base = tempdir.path().join("bootstrap");
+        let binding_base = tempdir.path().join("binding");
+        let lift = Lift {
+            base: Some(bootstrap_base.clone()),
+            base_binding: Some("base".to_string()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: vec![(
+                    "base".to_string(),
+                    Cmd {
+                        exe: "sh".to_string(),
+                        args: vec![
+                            "-c".to_string(),
+                            format!(
+                                "echo SCIE_BASE={base} >> $SCIE_BINDING_ENV",
+                                base = binding_base.display()
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+
+        let context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(binding_base, context.base);
+        assert_eq!(
+            binding_base.join("abc").join("lift.json"),
+            context.lift_manifest.path
+        );
+        assert!(bootstrap_base.join("abc").join("locks").is_dir());
+        assert!(context.bound.is_empty());
+        assert!(context.installed.is_empty());
+
+        let explicit_base = tempdir.path().join("explicit");
+        let context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            vec![("SCIE_BASE".into(), explicit_base.clone().into())]
+                .into_iter()
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(explicit_base, context.base);
+    }
+
+    #[test]
+    fn pid() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let lift = Lift {
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let (pid, needs_lift_manifest) = context.reify_string("{scie.pid}").unwrap();
+        assert!(!needs_lift_manifest);
+        assert_eq!(std::process::id(), pid.parse::<u32>().unwrap());
+    }
+
+    #[test]
+    fn scie_dir() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let lift = Lift {
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let reify = |scie: &str| {
+            Context::with_env(
+                Path::new(scie),
+                Path::new(scie),
+                &jump,
+                &lift,
+                &installer,
+                HashMap::new(),
+            )
+            .unwrap()
+            .reify_string("{scie.dir}")
+        };
+
+        assert_eq!(
+            ("/opt/scies".to_string(), false),
+            reify("/opt/scies/tool").unwrap()
+        );
+        assert_eq!((".".to_string(), false), reify("tool").unwrap());
+        let err = reify("/").unwrap_err();
+        assert!(err.contains("has no parent directory"), "{err}");
+    }
+
+    #[test]
+    fn stats() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let file = |name: &str, size: usize, source: Source| File {
+            name: name.to_string(),
+            size,
+            hash: name.to_string(),
+            source,
+            ..File::default()
+        };
+        let lift = Lift {
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            files: vec![
+                file("foo", 37, Source::Scie),
+                file("bar", 5, Source::Scie),
+                file("baz", 100, Source::LoadBinding("fetch".to_string())),
+            ],
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            ("3 files totalling 42 bytes".to_string(), false),
+            context
+                .reify_string("{scie.files.count} files totalling {scie.size.total} bytes")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn archive_member() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let cmd = |exe: &str| Cmd {
+            exe: exe.to_string(),
+            ..Default::default()
+        };
+        let lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            boot: Boot {
+                commands: vec![
+                    ("tool".to_string(), cmd("{sdk}/bin/tool")),
+                    ("blob".to_string(), cmd("{tool.sh}")),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: Default::default(),
+            },
+            files: vec![
+                File {
+                    name: "sdk".to_string(),
+                    size: 37,
+                    hash: "def".to_string(),
+                    file_type: FileType::Archive(ArchiveType::Tar),
+                    ..File::default()
+                },
+                File {
+                    name: "tool.sh".to_string(),
+                    size: 37,
+                    hash: "ghi".to_string(),
+                    executable: Some(true),
+                    ..File::default()
+                },
+            ],
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        assert!(context
+            .select_cmd("blob", false)
+            .unwrap()
+            .unwrap()
+            .exe_member
+            .is_none());
+
+        let exe_member = context
+            .select_cmd("tool", false)
+            .unwrap()
+            .unwrap()
+            .exe_member
+            .unwrap();
+        let sdk = tempdir.path().join("def").join("sdk");
+        std::fs::create_dir_all(&sdk).unwrap();
+        assert_eq!(
+            "The archive 'sdk' does not contain 'bin/tool'.",
+            exe_member.check().unwrap_err()
+        );
+
+        std::fs::create_dir_all(sdk.join("bin")).unwrap();
+        std::fs::write(sdk.join("bin").join("tool"), "").unwrap();
+        exe_member.check().unwrap();
+    }
+
+    #[test]
+    fn peek_default_command() {
+        let cmd = |exe: &str, load_dotenv: Option<bool>| Cmd {
+            exe: exe.to_string(),
+            load_dotenv,
+            ..Default::default()
+        };
+        let lift = Lift {
+            load_dotenv: true,
+            boot: Boot {
+                commands: vec![
+                    ("".to_string(), cmd("batch", Some(false))),
+                    ("shell".to_string(), cmd("shell", None)),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let current_exe = CurrentExe {
+            exe: "scie".into(),
+            invoked_as: "shell".into(),
+        };
+        let peeked = peek_command(&current_exe, &lift, None, &HashMap::new(), None).unwrap();
+        assert_eq!("batch", peeked.exe);
+        assert_eq!(Some(false), peeked.load_dotenv);
+        assert_eq!(
+            "shell",
+            peek_command(&current_exe, &lift, Some("shell"), &HashMap::new(), None)
+                .unwrap()
+                .exe
+        );
+        assert_eq!(
+            "shell",
+            peek_command(
+                &current_exe,
+                &lift,
+                None,
+                &vec![("SCIE_BOOT".into(), "shell".into())]
+                    .into_iter()
+                    .collect(),
+                None
+            )
+            .unwrap()
+            .exe
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn binding_stderr() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let binding = |capture_stderr: bool| Binding {
+            name: "configure".to_string(),
+            target: tempdir.path().join(format!("configure-{capture_stderr}")),
+            process: Process {
+                env: EnvVars { vars: vec![] },
+                exe: "sh".into(),
+                args: vec![
+                    "-c".into(),
+                    "echo 'Missing config.' >&2; echo 'Aborting.' >&2; exit 1".into(),
+                ],
+                args_append: vec![],
+                stdout: None,
+                stderr: None,
+                inherit_fds: vec![],
+            },
+            capture_stderr,
+            env_file_format: EnvFileFormat::Dotenv,
+            timeout: None,
+        };
+
+        let err = binding(true).execute(|| Ok(())).unwrap_err();
+        assert_eq!(
+            "Boot binding command failed: exit status: 1\n\
+            [configure] Missing config.\n\
+            [configure] Aborting.",
+            err
+        );
+
+        let err = binding(false).execute(|| Ok(())).unwrap_err();
+        assert_eq!("Boot binding command failed: exit status: 1", err);
+
+        let succeeding = Binding {
+            target: tempdir.path().join("configure-succeeding"),
+            process: Process {
+                args: vec![
+                    "-c".into(),
+                    "echo 'Configured.' >&2; echo FOO=bar >> \"$SCIE_BINDING_ENV\"".into(),
+                ],
+                ..binding(true).process
+            },
+            ..binding(true)
+        };
+        assert_eq!(
+            Some(&"bar".to_string()),
+            succeeding.execute(|| Ok(())).unwrap().get("FOO")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn binding_timeout() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let binding = |script: &str, timeout: Duration| Binding {
+            name: "configure".to_string(),
+            target: tempdir
+                .path()
+                .join(format!("configure-{}", timeout.as_millis())),
+            process: Process {
+                env: EnvVars { vars: vec![] },
+                exe: "sh".into(),
+                args: vec!["-c".into(), script.into()],
+                args_append: vec![],
+                stdout: None,
+                stderr: None,
+                inherit_fds: vec![],
+            },
+            capture_stderr: true,
+            env_file_format: EnvFileFormat::Dotenv,
+            timeout: Some(timeout),
+        };
+
+        let hung = binding(
+            "echo 'Hanging.' >&2; exec sleep 60",
+            Duration::from_millis(100),
+        );
+        let start = std::time::Instant::now();
+        let err = hung.execute(|| Ok(())).unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(30), "{err}");
+        assert!(
+            err.contains("The boot binding configure timed out after 100ms."),
+            "{err}"
+        );
+        assert!(!hung.target.exists());
+        assert!(!hung.target.with_extension("work").exists());
+
+        let prompt = binding(
+            "echo FOO=bar >> \"$SCIE_BINDING_ENV\"",
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            Some(&"bar".to_string()),
+            prompt.execute(|| Ok(())).unwrap().get("FOO")
+        );
+    }
+
+    #[test]
+    fn env_file_formats() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let expected = vec![
+            ("JAVA_HOME".to_string(), "/opt/java".to_string()),
+            ("PORT".to_string(), "8080".to_string()),
+            ("VERBOSE".to_string(), "true".to_string()),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+        let json = tempdir.path().join("env.json");
+        std::fs::write(
+            &json,
+            r#"{"JAVA_HOME": "/opt/java", "PORT": 8080, "VERBOSE": true}"#,
+        )
+        .unwrap();
+        assert_eq!(expected, load_env_file(&json, EnvFileFormat::Json).unwrap());
+        assert_eq!(Some(EnvFileFormat::Json), EnvFileFormat::from_path(&json));
+
+        let toml = tempdir.path().join("env.toml");
+        std::fs::write(
+            &toml,
+            "JAVA_HOME = \"/opt/java\"\nPORT = 8080\nVERBOSE = true\n",
+        )
+        .unwrap();
+        assert_eq!(expected, load_env_file(&toml, EnvFileFormat::Toml).unwrap());
+
+        let nested = tempdir.path().join("nested.json");
+        std::fs::write(&nested, r#"{"PATH": ["/usr/bin"]}"#).unwrap();
+        let err = load_env_file(&nested, EnvFileFormat::Json).unwrap_err();
+        assert!(err.contains("The value of PATH"), "{err}");
+
+        let cmd: Cmd =
+            serde_json::from_str(r#"{"exe": "configure", "env_file_format": "json"}"#).unwrap();
+        assert_eq!(Some(EnvFileFormat::Json), cmd.env_file_format);
+    }
+
+    #[test]
+    fn base_sources() {
+        let mut lift = Lift {
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let scie = Path::new("/opt/scies/tool");
+        let env = |vars: &[(&str, &str)]| {
+            vars.iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+                .collect::<HashMap<_, _>>()
+        };
+        let base = |env: &HashMap<OsString, OsString>, lift: &Lift| {
+            resolve_base(env, lift, scie, scie).unwrap()
+        };
+
+        let fallback = base(&env(&[]), &lift);
+        assert!(fallback.ends_with("nce"), "{}", fallback.display());
+        assert_eq!(
+            fallback,
+            base(&env(&[("XDG_CACHE_HOME", "/xdg/cache")]), &lift)
+        );
+
+        let user_cache_dir = Some(PathBuf::from("/user/cache"));
+        assert_eq!(
+            (PathBuf::from("/user/cache/nce"), "the user cache dir"),
+            default_base(&env(&[("XDG_CACHE_HOME", "/xdg/cache")]), user_cache_dir)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            (PathBuf::from("/xdg/cache/nce"), "XDG_CACHE_HOME"),
+            default_base(&env(&[("XDG_CACHE_HOME", "/xdg/cache")]), None)
+        );
+        assert_eq!(
+            (PathBuf::from("~/.nce"), "the HOME fallback"),
+            default_base(&env(&[("XDG_CACHE_HOME", "relative/cache")]), None)
+        );
+        assert_eq!(
+            (PathBuf::from("~/.nce"), "the HOME fallback"),
+            default_base(&env(&[]), None)
+        );
+
+        lift.base = Some("/lift/base".into());
+        assert_eq!(
+            Path::new("/lift/base"),
+            base(&env(&[("XDG_CACHE_HOME", "/xdg/cache")]), &lift)
+        );
+        assert_eq!(
+            Path::new("/scie/base"),
+            base(
+                &env(&[
+                    ("SCIE_BASE", "/scie/base"),
+                    ("XDG_CACHE_HOME", "/xdg/cache")
+                ]),
+                &lift
+            )
+        );
+    }
+
+    #[test]
+    fn relative_base() {
+        let mut lift = Lift {
+            relative_base: true,
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let scie = Path::new("/opt/scies/tool");
+        let no_env = HashMap::new();
+
+        let first = resolve_base(&no_env, &lift, scie, Path::new("/usr/local/bin/tool")).unwrap();
+        let second = resolve_base(&no_env, &lift, scie, Path::new("/home/user/bin/tool")).unwrap();
+        assert_eq!(Path::new("/usr/local/bin/.nce"), first);
+        assert_eq!(Path::new("/home/user/bin/.nce"), second);
+        assert_eq!(
+            Path::new("/opt/scies/.nce"),
+            resolve_base(&no_env, &lift, scie, Path::new("tool")).unwrap()
+        );
+
+        let cwd = std::env::current_dir().unwrap();
+        let base = resolve_base(&no_env, &lift, scie, Path::new("./bin/tool")).unwrap();
+        assert!(base.is_absolute(), "{base:?}");
+        assert_eq!(cwd.join("bin").join(".nce"), base);
+
+        lift.base = Some("cache".into());
+        assert_eq!(
+            Path::new("/usr/local/bin/cache"),
+            resolve_base(&no_env, &lift, scie, Path::new("/usr/local/bin/tool")).unwrap()
+        );
+
+        let explicit_base = vec![("SCIE_BASE".into(), "/tmp/base".into())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            Path::new("/tmp/base"),
+            resolve_base(
+                &explicit_base,
+                &lift,
+                scie,
+                Path::new("/usr/local/bin/tool")
+            )
+            .unwrap()
+        );
+
+        lift.relative_base = false;
+        let relative_env = vec![("SCIE_RELATIVE_BASE".into(), "1".into())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            Path::new("/home/user/bin/cache"),
+            resolve_base(&relative_env, &lift, scie, Path::new("/home/user/bin/tool")).unwrap()
+        );
+    }
+
+    #[test]
+    fn scie_dir_base() {
+        let usb = Path::new("/media/usb");
+        let scie = usb.join("tool");
+        let mut lift = Lift {
+            base: Some("./nce".into()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: Default::default(),
+            },
+            ..testing::lift()
+        };
+        let no_env = HashMap::new();
+
+        // A `./` base is relative to the scie and not to the path it was invoked as.
+        assert_eq!(
+            usb.join("nce"),
+            resolve_base(&no_env, &lift, &scie, Path::new("/usr/local/bin/tool")).unwrap()
+        );
+        assert_eq!(
+            usb.join("nce"),
+            resolve_base(&no_env, &lift, &scie, Path::new("tool")).unwrap()
+        );
+        let cwd = std::env::current_dir().unwrap();
+        let base =
+            resolve_base(&no_env, &lift, Path::new("bin/tool"), Path::new("./tool")).unwrap();
+        assert!(base.is_absolute(), "{base:?}");
+        assert_eq!(cwd.join("bin").join("nce"), base);
+
+        lift.base = Some("{scie.dir}/cache".into());
+        assert_eq!(
+            usb.join("cache"),
+            resolve_base(&no_env, &lift, &scie, Path::new("/usr/local/bin/tool")).unwrap()
+        );
+        assert_eq!(
+            cwd.join("cache"),
+            resolve_base(&no_env, &lift, Path::new("tool"), Path::new("tool")).unwrap()
+        );
+
+        lift.base = Some("/lift/base".into());
+        assert_eq!(
+            Path::new("/lift/base"),
+            resolve_base(&no_env, &lift, &scie, Path::new("/usr/local/bin/tool")).unwrap()
+        );
+    }
+
+    #[test]
+    fn scie_name() {
+        let invoked_as = Path::new("/usr/local/bin/tool");
+        assert_eq!(Some("tool"), invoked_name(invoked_as, None));
+        assert_eq!(
+            Some("other"),
+            invoked_name(invoked_as, Some(OsStr::new("other")))
+        );
+        assert_eq!(
+            Some("other"),
+            invoked_name(Path::new("tool.exe.bak"), Some(OsStr::new("other")))
+        );
+    }
+
+    #[test]
+    fn dry_select() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let base = tempdir.path().join("nce");
+        let cmd = |exe: &str, args: Vec<String>, then: Option<&str>| Cmd {
+            exe: exe.to_string(),
+            args,
+            then: then.map(str::to_string),
+            ..Default::default()
+        };
+        let lift = Lift {
+            base: Some(base.clone()),
+            boot: Boot {
+                commands: vec![
+                    (
+                        "".to_string(),
+                        cmd(
+                            "{python}/bin/python",
+                            vec!["{scie.lift}".to_string()],
+                            Some("serve"),
+                        ),
+                    ),
+                    (
+                        "serve".to_string(),
+                        cmd(
+                            "{python}/bin/python",
+                            vec!["{scie.bindings.configure}".to_string()],
+                            None,
+                        ),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: vec![(
+                    "configure".to_string(),
+                    cmd("{python}/bin/python", vec![], None),
+                )]
+                .into_iter()
+                .collect(),
+            },
+            files: vec![File {
+                name: "python".to_string(),
+                size: 37,
+                hash: "def".to_string(),
+                file_type: FileType::Archive(ArchiveType::Tar),
+                ..File::default()
+            }],
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]).dry_run(true);
+        let selected =
+            select_named_command(Path::new("scie_path"), &jump, &lift, &installer, "", None)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            base.join("def").join("python").join("bin").join("python"),
+            Path::new(&selected.process.exe)
+        );
+        assert_eq!(
+            vec![base.join("abc").join("bindings").into_os_string()],
+            selected.process.args
+        );
+        installer.install(&selected.files).unwrap();
+        assert!(!base.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_codes() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let cmd = |exe: &str, args: &[&str]| Cmd {
+            exe: exe.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        let lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            boot: Boot {
+                commands: vec![
+                    (
+                        "configured".to_string(),
+                        cmd("{scie.bindings.configure}", &[]),
+                    ),
+                    ("missing".to_string(), cmd("{scie.bindings.dne}", &[])),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: vec![("configure".to_string(), cmd("sh", &["-c", "exit 1"]))]
+                    .into_iter()
+                    .collect(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let context = || {
+            Context::with_env(
+                Path::new("scie_path"),
+                Path::new("scie_path"),
+                &jump,
+                &lift,
+                &installer,
+                HashMap::new(),
+            )
+            .unwrap()
+        };
+
+        let mut selection_context = context();
+        assert!(selection_context.select_cmd("missing", false).is_err());
+        assert_eq!(exit_code::SELECTION_ERROR, selection_context.exit_code());
+
+        let mut binding_context = context();
+        assert!(binding_context.select_cmd("configured", false).is_err());
+        assert_eq!(exit_code::BINDING_ERROR, binding_context.exit_code());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dry_run_bindings() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let marker = tempdir.path().join("marker");
+        let cmd = |exe: &str, args: &[&str]| Cmd {
+            exe: exe.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        let lift = Lift {
+            base: Some(tempdir.path().join("nce")),
+            boot: Boot {
+                commands: vec![("".to_string(), cmd("{scie.bindings.configure}", &[]))]
+                    .into_iter()
+                    .collect(),
+                bindings: vec![(
+                    "configure".to_string(),
+                    cmd("sh", &["-c", &format!("touch {}", marker.display())]),
+                )]
+                .into_iter()
+                .collect(),
+            },
+            ..testing::lift()
+        };
+
+        let installer = Installer::new(&[]).dry_run(true);
+        select_named_command(Path::new("scie_path"), &jump, &lift, &installer, "", None)
+            .unwrap()
+            .unwrap();
+        assert!(!marker.exists());
+
+        let installer = Installer::new(&[]);
+        select_named_command(Path::new("scie_path"), &jump, &lift, &installer, "", None)
+            .unwrap()
+            .unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn shared_bindings() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let lift = |hash: &str, file: &str| Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            shared_bindings: true,
+            hash: hash.to_string(),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: vec![(
+                    "configure".to_string(),
+                    Cmd {
+                        exe: "sh".to_string(),
+                        args: vec!["-c".to_string(), "true".to_string()],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            },
+            files: vec![File {
+                name: file.to_string(),
+                size: 1,
+                hash: file.to_string(),
+                ..File::default()
+            }],
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let locks_dir = |lift: &Lift| {
+            Context::with_env(
+                Path::new("scie_path"),
+                Path::new("scie_path"),
+                &jump,
+                lift,
+                &installer,
+                HashMap::new(),
+            )
+            .unwrap()
+            .get_locks_dir()
+        };
+
+        let mut v1 = lift("abc", "app-1.0");
+        let mut v2 = lift("def", "app-2.0");
+        assert_eq!(locks_dir(&v1), locks_dir(&v2));
+
+        v1.shared_bindings = false;
+        v2.shared_bindings = false;
+        assert_eq!(tempdir.path().join("abc").join("locks"), locks_dir(&v1));
+        assert_eq!(tempdir.path().join("def").join("locks"), locks_dir(&v2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn binding_args() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let runs = tempdir.path().join("runs");
+        let lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            boot: Boot {
+                commands: Default::default(),
+                bindings: vec![(
+                    "tag".to_string(),
+                    Cmd {
+                        exe: "sh".to_string(),
+                        args: vec![
+                            "-c".to_string(),
+                            format!(
+                                "echo TAG=$0-tagged >> $SCIE_BINDING_ENV; echo $0 >> {runs}",
+                                runs = runs.display()
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            },
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let (reified, _) = context
+            .reify_string(
+                "{scie.bindings.tag(a):TAG} {scie.bindings.tag(b):TAG} {scie.bindings.tag(a):TAG}",
+            )
+            .unwrap();
+        assert_eq!("a-tagged b-tagged a-tagged", reified);
+        assert_eq!("a\nb\n", std::fs::read_to_string(&runs).unwrap());
+        // Each distinct argument list gets its own lock, ignoring the `.lck` files that guard them.
+        let locks = std::fs::read_dir(context.get_locks_dir())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_none())
+            .count();
+        assert_eq!(2, locks);
+    }
+
+    #[test]
+    fn env_file() {
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+        let env_file = b"FOO=bar\nBAZ=qux\n";
+        let lift = Lift {
+            base: Some(tempdir.path().to_path_buf()),
+            env_file: Some("app.env".to_string()),
+            boot: Boot {
+                commands: vec![(
+                    "".to_string(),
+                    Cmd {
+                        exe: "sh".to_string(),
+                        env: vec![(
+                            config::EnvVar::Replace("BAZ".to_string()),
+                            Some("cmd".to_string()),
+                        )]
+                        .into_iter()
+                        .collect(),
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                bindings: Default::default(),
+            },
+            files: vec![testing::blob("app.env", env_file)],
+            ..testing::lift()
+        };
+        let installer = Installer::new(env_file);
+        let mut context = Context::with_env(
+            Path::new("scie_path"),
+            Path::new("scie_path"),
+            &jump,
+            &lift,
+            &installer,
+            vec![("FOO".into(), "ambient".into())].into_iter().collect(),
+        )
+        .unwrap();
+
+        // The env file only supplies defaults; so the real env wins.
+        assert_eq!(
+            ("ambient".to_string(), false),
+            context.reify_string("{scie.env.FOO}").unwrap()
+        );
+        assert_eq!(
+            ("qux".to_string(), false),
+            context.reify_string("{scie.env.BAZ}").unwrap()
+        );
+
+        let selected_cmd = context.select_cmd("", false).unwrap().unwrap();
+        assert_eq!(
+            vec![
+                EnvVar::Default(("BAZ".into(), "qux".into())),
+                EnvVar::Default(("FOO".into(), "bar".into())),
+                EnvVar::Replace(("BAZ".into(), "cmd".into())),
+            ],
+            selected_cmd.process.env.vars
+        );
+    }
+
+    #[test]
+    fn dev_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        std::fs::write(&scie, b"scie").unwrap();
+        filetime::set_file_mtime(&scie, filetime::FileTime::from_unix_time(1, 0)).unwrap();
+
+        let jump = Jump {
+            size: 42,
+            version: "0.1.0".to_string(),
+        };
+        let file = File {
+            name: "file".to_string(),
+            size: 37,
+            hash: "".to_string(),
+            ..File::default()
+        };
+        let lift = Lift {
+            dev: true,
+            files: vec![file.clone()],
+            ..testing::lift()
+        };
+        let installer = Installer::new(&[]);
+        let ambient_env: HashMap<OsString, OsString> =
+            [("SCIE_BASE".into(), tempdir.path().join("base").into())]
+                .into_iter()
+                .collect();
+        let get_path = || {
+            Context::with_env(&scie, &scie, &jump, &lift, &installer, ambient_env.clone())
+                .unwrap()
+                .get_path(&file)
+        };
+
+        let path = get_path();
+        assert!(
+            path.starts_with(tempdir.path().join("base").join("abc").join("dev")),
+            "{path:?}"
+        );
+        assert_eq!(path, get_path());
+
+        // A repacked scie of the same size gets fresh copies of its unhashed files.
+        std::fs::write(&scie, b"SCIE").unwrap();
+        filetime::set_file_mtime(&scie, filetime::FileTime::from_unix_time(2, 0)).unwrap();
+        assert_ne!(path, get_path());
     }
 }
@@ -0,0 +1,262 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use crate::config::{Cmd, EnvVar};
+use crate::lift::Lift;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added(name) => write!(f, "+ {name}"),
+            Change::Removed(name) => write!(f, "- {name}"),
+            Change::Changed(name) => write!(f, "~ {name}"),
+        }
+    }
+}
+
+/// The differences between two lift manifests.
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub files: Vec<Change>,
+    pub commands: Vec<Change>,
+    pub bindings: Vec<Change>,
+    pub env: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+            && self.commands.is_empty()
+            && self.bindings.is_empty()
+            && self.env.is_empty()
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (section, changes) in [
+            ("files", &self.files),
+            ("commands", &self.commands),
+            ("bindings", &self.bindings),
+            ("env", &self.env),
+        ] {
+            if !changes.is_empty() {
+                writeln!(f, "{section}:")?;
+                for change in changes {
+                    writeln!(f, "  {change}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn diff_maps<V>(
+    old: &BTreeMap<String, V>,
+    new: &BTreeMap<String, V>,
+    changed: impl Fn(&V, &V) -> bool,
+) -> Vec<Change> {
+    let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| match (old.get(key), new.get(key)) {
+            (None, Some(_)) => Some(Change::Added(key.to_string())),
+            (Some(_), None) => Some(Change::Removed(key.to_string())),
+            (Some(old_value), Some(new_value)) if changed(old_value, new_value) => {
+                Some(Change::Changed(key.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn commands(commands: &BTreeMap<String, Cmd>) -> BTreeMap<String, &Cmd> {
+    commands
+        .iter()
+        .map(|(name, cmd)| {
+            let name = if name.is_empty() {
+                "<default>".to_string()
+            } else {
+                name.to_string()
+            };
+            (name, cmd)
+        })
+        .collect()
+}
+
+// N.B.: Env changes are reported separately; so they are not considered a change to the command.
+fn cmd_changed(old: &Cmd, new: &Cmd) -> bool {
+    let strip_env = |cmd: &Cmd| {
+        serde_json::to_value(Cmd {
+            env: BTreeMap::new(),
+            ..cmd.clone()
+        })
+        .ok()
+    };
+    strip_env(old) != strip_env(new)
+}
+
+fn env_name(env_var: &EnvVar) -> String {
+    match env_var {
+        EnvVar::Default(name) => name.to_string(),
+        EnvVar::Replace(name) => format!("={name}"),
+        EnvVar::Append(name) => format!("+{name}"),
+        EnvVar::Prepend(name) => format!("{name}+"),
+    }
+}
+
+fn diff_env(old: &BTreeMap<String, &Cmd>, new: &BTreeMap<String, &Cmd>) -> Vec<Change> {
+    let mut changes = vec![];
+    for (name, new_cmd) in new {
+        let Some(old_cmd) = old.get(name) else {
+            continue;
+        };
+        let named = |env: &BTreeMap<EnvVar, Option<String>>| {
+            env.iter()
+                .map(|(env_var, value)| (format!("{name} {}", env_name(env_var)), value.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        changes.extend(diff_maps(
+            &named(&old_cmd.env),
+            &named(&new_cmd.env),
+            |old_value, new_value| old_value != new_value,
+        ));
+    }
+    changes
+}
+
+/// Compares the `old` lift manifest to the `new` one.
+///
+/// Files are matched by name and considered changed when their hashes differ. Env changes are
+/// only reported for commands and bindings present in both lifts.
+pub fn diff(old: &Lift, new: &Lift) -> Diff {
+    let files = |lift: &Lift| {
+        lift.files
+            .iter()
+            .map(|file| (file.name.clone(), file.hash.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (old_commands, new_commands) = (commands(&old.boot.commands), commands(&new.boot.commands));
+    let (old_bindings, new_bindings) = (commands(&old.boot.bindings), commands(&new.boot.bindings));
+    let mut env = diff_env(&old_commands, &new_commands);
+    env.extend(diff_env(&old_bindings, &new_bindings));
+    Diff {
+        files: diff_maps(&files(old), &files(new), |old_hash, new_hash| {
+            old_hash != new_hash
+        }),
+        commands: diff_maps(&old_commands, &new_commands, |old_cmd, new_cmd| {
+            cmd_changed(old_cmd, new_cmd)
+        }),
+        bindings: diff_maps(&old_bindings, &new_bindings, |old_cmd, new_cmd| {
+            cmd_changed(old_cmd, new_cmd)
+        }),
+        env,
+    }
+}
+
+/// Compares the lift manifest of the scie at `other_scie` to the given `lift`.
+pub fn diff_scie(other_scie: &Path, lift: &Lift) -> Result<Diff, String> {
+    let data = std::fs::read(other_scie).map_err(|e| {
+        format!(
+            "Failed to read the scie at {path} to compare against: {e}",
+            path = other_scie.display()
+        )
+    })?;
+    let (_, other_lift) = crate::lift::load_scie(other_scie, &data)?;
+    Ok(diff(&other_lift, lift))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{diff, Change};
+    use crate::config::{Boot, Cmd, EnvVar};
+    use crate::{testing, File, Lift};
+
+    fn file(name: &str, hash: &str) -> File {
+        File {
+            name: name.to_string(),
+            size: 42,
+            hash: hash.to_string(),
+            ..File::default()
+        }
+    }
+
+    fn cmd(exe: &str, env: &[(&str, &str)]) -> Cmd {
+        Cmd {
+            exe: exe.to_string(),
+            env: env
+                .iter()
+                .map(|(name, value)| (EnvVar::Default(name.to_string()), Some(value.to_string())))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn lift(files: Vec<File>, commands: Vec<(&str, Cmd)>) -> Lift {
+        Lift {
+            boot: Boot {
+                commands: commands
+                    .into_iter()
+                    .map(|(name, cmd)| (name.to_string(), cmd))
+                    .collect(),
+                bindings: BTreeMap::new(),
+            },
+            files,
+            ..testing::lift()
+        }
+    }
+
+    #[test]
+    fn identical() {
+        let old = lift(
+            vec![file("a", "1")],
+            vec![("", cmd("{a}", &[("FOO", "bar")]))],
+        );
+        let new = lift(
+            vec![file("a", "1")],
+            vec![("", cmd("{a}", &[("FOO", "bar")]))],
+        );
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn changes() {
+        let old = lift(
+            vec![file("a", "1"), file("b", "2")],
+            vec![
+                ("", cmd("{a}", &[("FOO", "bar")])),
+                ("tool", cmd("{b}", &[])),
+            ],
+        );
+        let new = lift(
+            vec![file("a", "1"), file("b", "3")],
+            vec![
+                ("", cmd("{a}", &[("FOO", "baz")])),
+                ("other", cmd("{b}", &[])),
+            ],
+        );
+        let diff = diff(&old, &new);
+        assert_eq!(vec![Change::Changed("b".to_string())], diff.files);
+        assert_eq!(
+            vec![
+                Change::Added("other".to_string()),
+                Change::Removed("tool".to_string())
+            ],
+            diff.commands
+        );
+        assert!(diff.bindings.is_empty());
+        assert_eq!(vec![Change::Changed("<default> FOO".to_string())], diff.env);
+    }
+}
@@ -0,0 +1,28 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+//! The exit codes a scie uses to report its own failures.
+//!
+//! When a scie successfully executes its selected command, the exit code is that of the command.
+//! The codes here are only used when the scie fails before or while handing off to the command and
+//! are chosen from the BSD `sysexits.h` range to stay clear of the small codes commands typically
+//! use. Once the scie has loaded, the `SCIE=<boot_command>` boot commands and the boot-pack report
+//! their own failures with `FAILURE`.
+
+/// A failure not covered by one of the more specific codes below.
+pub const FAILURE: i32 = 1;
+
+/// The command to run could not be selected; e.g.: there is no command with the requested name.
+pub const SELECTION_ERROR: i32 = 64;
+
+/// A boot binding command failed to launch or else exited unsuccessfully.
+pub const BINDING_ERROR: i32 = 70;
+
+/// The files needed by the selected command could not be extracted.
+pub const EXTRACTION_ERROR: i32 = 74;
+
+/// The scie or its lift manifest could not be loaded or is invalid.
+pub const CONFIG_ERROR: i32 = 78;
+
+/// The selected command was prepared but could not be executed.
+pub const EXEC_ERROR: i32 = 126;
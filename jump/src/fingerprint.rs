@@ -1,8 +1,9 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
-use std::io::Read;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use logging_timer::time;
 use sha2::{Digest, Sha256};
@@ -22,6 +23,198 @@ pub fn digest_file(path: &Path) -> Result<(usize, String), String> {
     digest_reader(file)
 }
 
+/// An on-disk cache of file digests that saves re-reading large files that have not changed.
+///
+/// Entries are keyed by a file's absolute path, modification time and size. This means a file
+/// re-written in place with the same size and within the resolution of its file system's
+/// modification times will be served its stale digest. That is unlikely for the payload files of a
+/// scie, but when in doubt, don't use the cache.
+#[derive(Clone, Debug)]
+pub struct FingerprintCache {
+    dir: PathBuf,
+}
+
+impl FingerprintCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The fingerprint cache in the user's cache directory, if they have one.
+    pub fn user() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join("scie-jump").join("fingerprints")))
+    }
+
+    fn entry(&self, path: &Path) -> Result<Option<PathBuf>, String> {
+        let path = path.canonicalize().map_err(|e| {
+            format!(
+                "Failed to resolve the absolute path of {path}: {e}",
+                path = path.display()
+            )
+        })?;
+        let Some(mtime) = trusted_mtime(&path)? else {
+            return Ok(None);
+        };
+        let Ok(mtime) = mtime.duration_since(SystemTime::UNIX_EPOCH) else {
+            return Ok(None);
+        };
+        let size = path
+            .metadata()
+            .map_err(|e| {
+                format!(
+                    "Failed to determine the size of {path}: {e}",
+                    path = path.display()
+                )
+            })?
+            .len();
+        let key = format!(
+            "{path}\0{secs}.{nanos:09}\0{size}",
+            path = path.display(),
+            secs = mtime.as_secs(),
+            nanos = mtime.subsec_nanos()
+        );
+        Ok(Some(self.dir.join(digest(key.as_bytes()))))
+    }
+
+    fn write_entry(
+        &self,
+        entry: &Path,
+        path: &Path,
+        (size, hash): &(usize, String),
+    ) -> Result<(), String> {
+        // N.B.: The path is recorded so that prune can tell when the file is gone.
+        let path = path.canonicalize().map_err(|e| {
+            format!(
+                "Failed to resolve the absolute path of {path}: {e}",
+                path = path.display()
+            )
+        })?;
+        std::fs::create_dir_all(&self.dir).map_err(|e| {
+            format!(
+                "Failed to create the fingerprint cache dir {dir}: {e}",
+                dir = self.dir.display()
+            )
+        })?;
+        let mut entry_file = tempfile::NamedTempFile::new_in(&self.dir)
+            .map_err(|e| format!("Failed to create a fingerprint cache entry: {e}"))?;
+        writeln!(entry_file, "{size} {hash}\n{path}", path = path.display())
+            .map_err(|e| format!("Failed to write a fingerprint cache entry: {e}"))?;
+        entry_file.persist(entry).map_err(|e| {
+            format!(
+                "Failed to store the fingerprint cache entry {entry}: {e}",
+                entry = entry.display()
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Digests the file at `path` unless a digest is already cached for it.
+    pub fn digest_file(&self, path: &Path) -> Result<(usize, String), String> {
+        let entry = self.entry(path).unwrap_or_else(|e| {
+            warn!("Not using the fingerprint cache: {e}");
+            None
+        });
+        if let Some(entry) = entry.as_deref() {
+            if let Some(fingerprint) = read_entry(entry) {
+                debug!(
+                    "Using the cached fingerprint of {path}.",
+                    path = path.display()
+                );
+                return Ok(fingerprint);
+            }
+        }
+        let fingerprint = digest_file(path)?;
+        if let Some(entry) = entry.as_deref() {
+            if let Err(e) = self.write_entry(entry, path, &fingerprint) {
+                warn!("{e}");
+            }
+        }
+        Ok(fingerprint)
+    }
+
+    /// Removes the entries for files that no longer exist or that have changed since they were
+    /// cached and returns how many were removed.
+    pub fn prune(&self) -> Result<usize, String> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read the fingerprint cache dir {dir} to prune it: {e}",
+                    dir = self.dir.display()
+                ))
+            }
+        };
+        let mut pruned = 0;
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| {
+                    format!(
+                        "Failed to read an entry of the fingerprint cache dir {dir}: {e}",
+                        dir = self.dir.display()
+                    )
+                })?
+                .path();
+            let current = std::fs::read_to_string(&entry)
+                .ok()
+                .and_then(|contents| contents.lines().nth(1).map(PathBuf::from))
+                .filter(|path| path.exists())
+                .and_then(|path| self.entry(&path).ok().flatten());
+            if current.as_deref() != Some(entry.as_path()) {
+                std::fs::remove_file(&entry).map_err(|e| {
+                    format!(
+                        "Failed to prune the fingerprint cache entry {entry}: {e}",
+                        entry = entry.display()
+                    )
+                })?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+fn read_entry(entry: &Path) -> Option<(usize, String)> {
+    let contents = std::fs::read_to_string(entry).ok()?;
+    let (size, hash) = contents.lines().next()?.split_once(' ')?;
+    Some((size.parse().ok()?, hash.to_string()))
+}
+
+/// A reader that digests all the bytes read through it.
+pub(crate) struct DigestingReader<R> {
+    reader: R,
+    hasher: Sha256,
+    size: usize,
+}
+
+impl<R: Read> DigestingReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Reads any remaining bytes and returns the total size and hash of all bytes read.
+    pub(crate) fn finish(mut self) -> Result<(usize, String), String> {
+        std::io::copy(&mut self, &mut std::io::sink())
+            .map_err(|e| format!("Failed to digest the remainder of the stream: {e}"))?;
+        Ok((
+            self.size,
+            format!("{digest:x}", digest = self.hasher.finalize()),
+        ))
+    }
+}
+
+impl<R: Read> Read for DigestingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.size += read;
+        Ok(read)
+    }
+}
+
 #[time("debug", "fingerprint::{}")]
 pub fn digest_reader<R: Read>(mut reader: R) -> Result<(usize, String), String> {
     let mut hasher = Sha256::new();
@@ -37,3 +230,140 @@ pub fn digest_reader<R: Read>(mut reader: R) -> Result<(usize, String), String>
     let hash = format!("{digest:x}", digest = hasher.finalize());
     Ok((file_size, hash))
 }
+
+// Build and run hosts can disagree about the time; so we allow for a small amount of skew.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+fn is_plausible_mtime(mtime: SystemTime, now: SystemTime) -> bool {
+    match mtime.duration_since(now) {
+        Ok(ahead) => ahead <= MAX_CLOCK_SKEW,
+        Err(_) => true,
+    }
+}
+
+/// Returns the modification time of the given path if it can be trusted.
+///
+/// Cache freshness decisions should always be made by comparing content hashes. Where a timestamp
+/// comparison is unavoidable, this guards against clock skew by refusing to trust (with a warning)
+/// modification times that lie implausibly far in the future.
+pub fn trusted_mtime(path: &Path) -> Result<Option<SystemTime>, String> {
+    let mtime = path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| {
+            format!(
+                "Failed to read the modification time of {path}: {e}",
+                path = path.display()
+            )
+        })?;
+    if is_plausible_mtime(mtime, SystemTime::now()) {
+        Ok(Some(mtime))
+    } else {
+        warn!(
+            "The modification time of {path} is in the future; not trusting it. Check for clock \
+            skew between the host that created it and this one.",
+            path = path.display()
+        );
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{digest, is_plausible_mtime, trusted_mtime, FingerprintCache};
+
+    #[test]
+    fn plausible_mtime() {
+        let now = SystemTime::now();
+        assert!(is_plausible_mtime(now, now));
+        assert!(is_plausible_mtime(now - Duration::from_secs(3600), now));
+        assert!(is_plausible_mtime(now + Duration::from_secs(1), now));
+        assert!(!is_plausible_mtime(now + Duration::from_secs(3600), now));
+    }
+
+    #[test]
+    fn trusted_mtime_present() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("cached");
+        std::fs::write(&path, "data").unwrap();
+        assert!(trusted_mtime(&path).unwrap().is_some());
+        assert!(trusted_mtime(&tempdir.path().join("dne")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn trusted_mtime_future() {
+        use nix::sys::time::{TimeVal, TimeValLike};
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("cached");
+        std::fs::write(&path, "data").unwrap();
+
+        let future = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            + Duration::from_secs(24 * 60 * 60);
+        let future = TimeVal::seconds(future.as_secs() as i64);
+        nix::sys::stat::utimes(&path, &future, &future).unwrap();
+        assert_eq!(None, trusted_mtime(&path).unwrap());
+    }
+
+    #[test]
+    fn fingerprint_cache() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cache_dir = tempdir.path().join("cache");
+        let cache = FingerprintCache::new(cache_dir.clone());
+        let entries = || std::fs::read_dir(&cache_dir).unwrap().count();
+
+        let path = tempdir.path().join("payload");
+        std::fs::write(&path, "data").unwrap();
+        let expected = (4, digest(b"data"));
+        assert_eq!(expected, cache.digest_file(&path).unwrap());
+        assert_eq!(1, entries());
+
+        // A hit is served from the cache; so it sees a stale entry.
+        let entry = std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        std::fs::write(&entry, "4 stale\n").unwrap();
+        assert_eq!((4, "stale".to_string()), cache.digest_file(&path).unwrap());
+        assert_eq!(1, entries());
+
+        // A change in size is a miss.
+        std::fs::write(&path, "more data").unwrap();
+        assert_eq!((9, digest(b"more data")), cache.digest_file(&path).unwrap());
+        assert_eq!(2, entries());
+    }
+
+    #[test]
+    fn fingerprint_cache_prune() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cache_dir = tempdir.path().join("cache");
+        let cache = FingerprintCache::new(cache_dir.clone());
+        let entries = || std::fs::read_dir(&cache_dir).unwrap().count();
+        assert_eq!(0, cache.prune().unwrap());
+
+        let kept = tempdir.path().join("kept");
+        std::fs::write(&kept, "kept").unwrap();
+        cache.digest_file(&kept).unwrap();
+        let changed = tempdir.path().join("changed");
+        std::fs::write(&changed, "data").unwrap();
+        cache.digest_file(&changed).unwrap();
+        let removed = tempdir.path().join("removed");
+        std::fs::write(&removed, "data").unwrap();
+        cache.digest_file(&removed).unwrap();
+        assert_eq!(3, entries());
+
+        std::fs::write(&changed, "more data").unwrap();
+        std::fs::remove_file(&removed).unwrap();
+        assert_eq!(2, cache.prune().unwrap());
+        assert_eq!(1, entries());
+        assert_eq!((4, digest(b"kept")), cache.digest_file(&kept).unwrap());
+        assert_eq!(1, entries());
+    }
+}
@@ -1,17 +1,187 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsStr;
 use std::fs::{OpenOptions, Permissions};
-use std::io::{Cursor, Read, Seek};
-use std::path::Path;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::ChildStdout;
+use std::time::{Instant, SystemTime};
 
 use logging_timer::time;
 use tempfile::TempDir;
 
 use crate::atomic::{atomic_path, Target};
 use crate::config::{ArchiveType, Compression, FileType};
-use crate::context::FileEntry;
-use crate::fingerprint;
+use crate::context::{FileEntry, LoadProcess};
+use crate::process::Watchdog;
+use crate::{fingerprint, File, Lift, Source};
+
+// N.B.: Both gzip members and zstd frames can be concatenated; so each record is compressed on its
+// own and the log as a whole decompresses to all the records in order.
+fn compress_record(log: &Path, record: Vec<u8>) -> Result<Vec<u8>, String> {
+    let compressed = match log.extension().and_then(OsStr::to_str) {
+        Some("gz") => {
+            let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+            encoder
+                .write_all(record.as_slice())
+                .and_then(|_| encoder.finish())
+        }
+        Some("zst") => zstd::stream::encode_all(record.as_slice(), 0),
+        _ => return Ok(record),
+    };
+    compressed.map_err(|e| {
+        format!(
+            "Failed to compress a record for {log}: {e}",
+            log = log.display()
+        )
+    })
+}
+
+/// Decompresses the bytes of a tar archive compressed in a way the `scie-jump` does not support
+/// itself.
+///
+/// Files name the decompressor to use with their "decompressor" field.
+pub struct Decompressor(Box<dyn for<'r> Fn(Box<dyn Read + 'r>) -> Box<dyn Read + 'r>>);
+
+impl Decompressor {
+    pub fn new<F>(decompress: F) -> Self
+    where
+        F: for<'r> Fn(Box<dyn Read + 'r>) -> Box<dyn Read + 'r> + 'static,
+    {
+        Self(Box::new(decompress))
+    }
+
+    fn decompress<'r>(&self, bytes: impl Read + 'r) -> Box<dyn Read + 'r> {
+        (self.0)(Box::new(bytes))
+    }
+}
+
+impl std::fmt::Debug for Decompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Decompressor")
+    }
+}
+
+/// Decompressors keyed by the names files use to refer to them.
+pub type Decompressors = HashMap<String, Decompressor>;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct UnpackOptions<'a> {
+    zstd_dictionary: Option<&'a [u8]>,
+    // Decompresses the archive before it is unpacked as a tar in place of any built-in
+    // decompression.
+    decompressor: Option<&'a Decompressor>,
+    max_extract_size: Option<u64>,
+    // When non-empty, only these archive members are extracted.
+    extract_members: &'a [String],
+    // Set for dev scies whose files may have no hash to verify.
+    dev: bool,
+    // Mode bits to remove from the modes set explicitly on the files and directories created in
+    // the cache. Everything else the process creates is covered by its umask.
+    umask: u32,
+}
+
+impl UnpackOptions<'_> {
+    fn verify(&self, expected_hash: &str) -> bool {
+        !(self.dev && expected_hash.is_empty())
+    }
+}
+
+/// A reader that refuses to read more than a maximum number of bytes.
+///
+/// Reads past the limit fail and mark the reader as exceeded; so callers can detect the limit was
+/// the cause of failure even when the error is wrapped in a less helpful one.
+struct LimitedReader<R> {
+    reader: R,
+    remaining: Option<u64>,
+    exceeded: bool,
+}
+
+impl<R: Read> LimitedReader<R> {
+    fn new(reader: R, limit: Option<u64>) -> Self {
+        Self {
+            reader,
+            remaining: limit,
+            exceeded: false,
+        }
+    }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        if let Some(remaining) = self.remaining {
+            if read as u64 > remaining {
+                self.exceeded = true;
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "decompression exceeded limit",
+                ));
+            }
+            self.remaining = Some(remaining - read as u64);
+        }
+        Ok(read)
+    }
+}
+
+/// The output of a binding loading a file.
+///
+/// The binding is killed should it still be running at its timeout, even while its output is being
+/// read, and reaching the end of the output is an error unless the binding then exited
+/// successfully. Since the output is always read to its end before an unpacked file is moved into
+/// place, a file is never installed from a binding that timed out or failed.
+struct LoadStream<'a> {
+    binding: &'a LoadProcess,
+    file: &'a File,
+    stdout: ChildStdout,
+    watchdog: Option<Watchdog>,
+}
+
+impl LoadStream<'_> {
+    fn check_exit(&mut self) -> std::io::Result<()> {
+        let watchdog = if let Some(watchdog) = self.watchdog.take() {
+            watchdog
+        } else {
+            return Ok(());
+        };
+        let error = match watchdog.wait()? {
+            None => format!(
+                "The binding {name} timed out after {timeout:?} when loading {file}.",
+                name = self.binding.name(),
+                timeout = self.binding.timeout().unwrap_or_default(),
+                file = self.file.name
+            ),
+            Some(exit_status) if !exit_status.success() => format!(
+                "Failed to load file {file:?}: {exit_status:?}",
+                file = self.file
+            ),
+            _ => return Ok(()),
+        };
+        Err(std::io::Error::new(std::io::ErrorKind::Other, error))
+    }
+}
+
+impl Read for LoadStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.check_exit()?;
+        }
+        Ok(read)
+    }
+}
+
+fn exceeded_limit_error(archive_type: ArchiveType, options: UnpackOptions) -> String {
+    format!(
+        "Failed to unpack {archive_type:?}: decompression exceeded limit of {limit} bytes set by \
+        SCIE_MAX_EXTRACT_BYTES.",
+        limit = options.max_extract_size.unwrap_or_default()
+    )
+}
 
 fn check_hash<R: Read + Seek>(
     file_type: &str,
@@ -39,87 +209,538 @@ fn check_hash<R: Read + Seek>(
 }
 
 #[time("debug", "installer::{}")]
-fn unpack_tar<R: Read>(archive_type: ArchiveType, tar_stream: R, dst: &Path) -> Result<(), String> {
-    let mut tar = tar::Archive::new(tar_stream);
-    tar.unpack(dst)
-        .map_err(|e| format!("Failed to unpack {archive_type:?}: {e}"))
+fn unpack_tar<R: Read>(
+    archive_type: ArchiveType,
+    tar_stream: R,
+    dst: &Path,
+    options: UnpackOptions,
+) -> Result<(), String> {
+    let mut limited_stream = LimitedReader::new(tar_stream, options.max_extract_size);
+    let mut archive = tar::Archive::new(&mut limited_stream);
+    // N.B.: GNU long names and links and PAX extended headers (including PAX paths) are always
+    // handled when iterating entries. We spell out the remaining settings so that changes in the
+    // tar crate defaults cannot change what lands in the cache. Permissions are applied without
+    // their setuid, setgid and sticky bits and extended attributes are not unpacked.
+    archive.set_preserve_permissions(false);
+    archive.set_mask(options.umask);
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(false);
+    archive.set_overwrite(true);
+    let result = if options.extract_members.is_empty() {
+        unpack_tar_entries(archive_type, &mut archive, dst)
+    } else {
+        unpack_tar_members(archive_type, &mut archive, dst, options.extract_members)
+    };
+    if limited_stream.exceeded {
+        return Err(exceeded_limit_error(archive_type, options));
+    }
+    result
+}
+
+fn outside_destination_error(archive_type: ArchiveType, member: &str) -> String {
+    format!(
+        "The {archive_type:?} member {member} is unsafe, refusing to extract path outside \
+        destination."
+    )
+}
+
+// N.B.: The tar crate handles old GNU sparse entries but not the PAX sparse formats; so entries in
+// those formats would otherwise be unpacked as their raw sparse map and data.
+fn check_pax_sparse<R: Read>(
+    archive_type: ArchiveType,
+    entry: &mut tar::Entry<R>,
+    path: &Path,
+) -> Result<(), String> {
+    let extensions = entry.pax_extensions().map_err(|e| {
+        format!(
+            "Failed to read the {archive_type:?} PAX extensions of {path}: {e}",
+            path = path.display()
+        )
+    })?;
+    if let Some(extensions) = extensions {
+        for extension in extensions {
+            let extension = extension.map_err(|e| {
+                format!(
+                    "Failed to read a {archive_type:?} PAX extension of {path}: {e}",
+                    path = path.display()
+                )
+            })?;
+            if let Ok(key) = extension.key() {
+                if key.starts_with("GNU.sparse.") {
+                    return Err(format!(
+                        "The {archive_type:?} member {path} is a PAX format sparse file, which is \
+                        not supported. Re-create the archive without sparse file support; e.g.: \
+                        without `tar --sparse`.",
+                        path = path.display()
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn tar_entry_path<R: Read>(
+    archive_type: ArchiveType,
+    entry: &mut tar::Entry<R>,
+) -> Result<PathBuf, String> {
+    let path = entry
+        .path()
+        .map_err(|e| format!("Failed to read {archive_type:?} entry path: {e}"))?
+        .into_owned();
+    check_pax_sparse(archive_type, entry, &path)?;
+    if path.components().any(|component| {
+        matches!(
+            component,
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir
+        )
+    }) {
+        return Err(outside_destination_error(
+            archive_type,
+            &path.display().to_string(),
+        ));
+    }
+    Ok(path)
+}
+
+fn unpack_tar_entry<R: Read>(
+    archive_type: ArchiveType,
+    entry: &mut tar::Entry<R>,
+    path: &Path,
+    dst: &Path,
+) -> Result<(), String> {
+    entry.unpack_in(dst).map(|_| ()).map_err(|e| {
+        format!(
+            "Failed to unpack {path} from {archive_type:?}: {e}",
+            path = path.display()
+        )
+    })
+}
+
+fn unpack_tar_entries<R: Read>(
+    archive_type: ArchiveType,
+    archive: &mut tar::Archive<R>,
+    dst: &Path,
+) -> Result<(), String> {
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read {archive_type:?} entries: {e}"))?;
+    // N.B.: Like `tar::Archive::unpack`, we unpack directories last; so their permissions cannot
+    // prevent unpacking their contents.
+    let mut directories = vec![];
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read {archive_type:?} entry: {e}"))?;
+        let path = tar_entry_path(archive_type, &mut entry)?;
+        if entry.header().entry_type().is_dir() {
+            directories.push((path, entry));
+        } else {
+            unpack_tar_entry(archive_type, &mut entry, &path, dst)?;
+        }
+    }
+    for (path, mut entry) in directories {
+        unpack_tar_entry(archive_type, &mut entry, &path, dst)?;
+    }
+    Ok(())
+}
+
+fn unpack_tar_members<R: Read>(
+    archive_type: ArchiveType,
+    archive: &mut tar::Archive<R>,
+    dst: &Path,
+    members: &[String],
+) -> Result<(), String> {
+    let mut remaining = members.iter().map(String::as_str).collect::<BTreeSet<_>>();
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read {archive_type:?} entries: {e}"))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read {archive_type:?} entry: {e}"))?;
+        let path = tar_entry_path(archive_type, &mut entry)?;
+        let selected = path
+            .to_str()
+            .map(|name| remaining.remove(name.trim_start_matches("./")))
+            .unwrap_or(false);
+        if selected {
+            unpack_tar_entry(archive_type, &mut entry, &path, dst)?;
+            if remaining.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+    Err(format!(
+        "The {archive_type:?} does not contain the members: {members}",
+        members = remaining.into_iter().collect::<Vec<_>>().join(", ")
+    ))
+}
+
+fn unpack_zip_members<R: Read + Seek>(
+    archive_type: ArchiveType,
+    zip: &mut zip::ZipArchive<R>,
+    dst: &Path,
+    options: UnpackOptions,
+    remaining: &mut Option<u64>,
+) -> Result<(), String> {
+    for member in options.extract_members {
+        let mut entry = zip
+            .by_name(member)
+            .map_err(|e| format!("Failed to find {member} in the {archive_type:?}: {e}"))?;
+        unpack_zip_entry(archive_type, &mut entry, dst, options, remaining)?;
+    }
+    Ok(())
+}
+
+fn unpack_zip_entry(
+    archive_type: ArchiveType,
+    entry: &mut zip::read::ZipFile,
+    dst: &Path,
+    options: UnpackOptions,
+    remaining: &mut Option<u64>,
+) -> Result<(), String> {
+    let member = entry.name().to_string();
+    let enclosed_name = entry
+        .enclosed_name()
+        .ok_or_else(|| outside_destination_error(archive_type, &member))?;
+    // N.B.: We push components individually since Windows extended-length paths do not treat
+    // `/` as a separator.
+    let mut path = dst.to_path_buf();
+    path.extend(enclosed_name.components());
+    if entry.is_dir() {
+        std::fs::create_dir_all(&path)
+    } else {
+        // N.B.: We count the bytes actually decompressed since the sizes recorded in the zip
+        // headers are not to be trusted.
+        let mut limited = LimitedReader::new(&mut *entry, *remaining);
+        let copied = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .and_then(|_| std::fs::File::create(&path))
+            .and_then(|mut out| std::io::copy(&mut limited, &mut out))
+            .map(|_| ());
+        if limited.exceeded {
+            return Err(exceeded_limit_error(archive_type, options));
+        }
+        *remaining = limited.remaining;
+        copied
+    }
+    .map_err(|e| {
+        format!(
+            "Failed to extract {member} from the {archive_type:?} to {path}: {e}",
+            path = path.display()
+        )
+    })?;
+    if let Some(mode) = entry.unix_mode() {
+        set_entry_mode(&path, mode & 0o7777 & !options.umask)?;
+    }
+    Ok(())
+}
+
+fn unpack_zip<R: Read + Seek>(
+    archive_type: ArchiveType,
+    bytes: R,
+    dst: &Path,
+    options: UnpackOptions,
+) -> Result<(), String> {
+    let mut zip =
+        zip::ZipArchive::new(bytes).map_err(|e| format!("Failed to open {archive_type:?}: {e}"))?;
+    let mut remaining = options.max_extract_size;
+    if !options.extract_members.is_empty() {
+        return unpack_zip_members(archive_type, &mut zip, dst, options, &mut remaining);
+    }
+    for index in 0..zip.len() {
+        let mut entry = zip
+            .by_index(index)
+            .map_err(|e| format!("Failed to read {archive_type:?} entry {index}: {e}"))?;
+        unpack_zip_entry(archive_type, &mut entry, dst, options, &mut remaining)?;
+    }
+    Ok(())
+}
+
+fn decompress<'a, R: Read + 'a>(
+    compression: Compression,
+    bytes: R,
+    dst: &Path,
+    options: UnpackOptions<'a>,
+) -> Result<Box<dyn Read + 'a>, String> {
+    Ok(match compression {
+        Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(bytes)),
+        Compression::Gzip => Box::new(flate2::read::GzDecoder::new(bytes)),
+        Compression::Lzw => Box::new(crate::lzw::Decoder::new(bytes).map_err(|e| {
+            format!(
+                "Failed to create an LZW decoder for unpacking to {dst}: {e}",
+                dst = dst.display()
+            )
+        })?),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new(bytes)),
+        Compression::Zlib => Box::new(flate2::read::ZlibDecoder::new(bytes)),
+        Compression::Zstd => {
+            let buffered_bytes = BufReader::new(bytes);
+            let zstd_decoder = if let Some(dictionary) = options.zstd_dictionary {
+                zstd::stream::Decoder::with_dictionary(buffered_bytes, dictionary)
+            } else {
+                zstd::stream::Decoder::with_buffer(buffered_bytes)
+            }
+            .map_err(|e| {
+                format!(
+                    "Failed to create a zstd decoder for unpacking to {dst}: {e}",
+                    dst = dst.display()
+                )
+            })?;
+            Box::new(zstd_decoder)
+        }
+    })
+}
+
+fn unpack_compressed_tar<'a, R: Read + 'a>(
+    archive: ArchiveType,
+    compression: Compression,
+    bytes: R,
+    dst: &Path,
+    work_dir: &Path,
+    options: UnpackOptions<'a>,
+) -> Result<(), String> {
+    let decoder = decompress(compression, bytes, dst, options)?;
+    unpack_tar(archive, decoder, work_dir, options)
+}
+
+fn unpack_decompressed_tar<'a, R: Read + 'a>(
+    archive: ArchiveType,
+    decompressor: &Decompressor,
+    bytes: R,
+    dst: &Path,
+    work_dir: &Path,
+    options: UnpackOptions<'a>,
+) -> Result<(), String> {
+    if archive != ArchiveType::Tar {
+        return Err(format!(
+            "Only tar archives can have a decompressor, the archive to unpack to {dst} is of \
+            type {archive:?}.",
+            dst = dst.display()
+        ));
+    }
+    unpack_tar(archive, decompressor.decompress(bytes), work_dir, options)
 }
 
 #[time("debug", "installer::{}")]
 fn unpack_archive<R: Read + Seek, T, F>(
     archive: ArchiveType,
+    mode: Option<u32>,
     bytes_source: F,
     expected_hash: &str,
     dst: &Path,
+    options: UnpackOptions,
 ) -> Result<Option<T>, String>
 where
     F: FnOnce() -> Result<(R, T), String>,
 {
     atomic_path(dst, Target::Directory, |work_dir| {
         let (bytes, result) = bytes_source()?;
-        let hashed_bytes = check_hash(archive.as_ext(), bytes, expected_hash, dst)?;
-        match archive {
-            ArchiveType::Zip => {
-                let mut zip = zip::ZipArchive::new(hashed_bytes)
-                    .map_err(|e| format!("Failed to open {archive:?}: {e}"))?;
-                zip.extract(work_dir)
-                    .map_err(|e| format!("Failed to extract {archive:?}: {e}"))
-            }
-            ArchiveType::Tar => unpack_tar(archive, hashed_bytes, work_dir),
-            ArchiveType::CompressedTar(Compression::Bzip2) => {
-                let bzip2_decoder = bzip2::read::BzDecoder::new(hashed_bytes);
-                unpack_tar(archive, bzip2_decoder, work_dir)
+        let hashed_bytes = if options.verify(expected_hash) {
+            check_hash(archive.as_ext(), bytes, expected_hash, dst)?
+        } else {
+            bytes
+        };
+        if let Some(decompressor) = options.decompressor {
+            unpack_decompressed_tar(archive, decompressor, hashed_bytes, dst, work_dir, options)
+        } else {
+            match archive {
+                ArchiveType::Zip => unpack_zip(archive, hashed_bytes, work_dir, options),
+                ArchiveType::Tar => unpack_tar(archive, hashed_bytes, work_dir, options),
+                ArchiveType::CompressedTar(compression) => unpack_compressed_tar(
+                    archive,
+                    compression,
+                    hashed_bytes,
+                    dst,
+                    work_dir,
+                    options,
+                ),
             }
-            ArchiveType::CompressedTar(Compression::Gzip) => {
-                let gz_decoder = flate2::read::GzDecoder::new(hashed_bytes);
-                unpack_tar(archive, gz_decoder, work_dir)
-            }
-            ArchiveType::CompressedTar(Compression::Xz) => {
-                let xz_decoder = xz2::read::XzDecoder::new(hashed_bytes);
-                unpack_tar(archive, xz_decoder, work_dir)
-            }
-            ArchiveType::CompressedTar(Compression::Zlib) => {
-                let zlib_decoder = flate2::read::ZlibDecoder::new(hashed_bytes);
-                unpack_tar(archive, zlib_decoder, work_dir)
-            }
-            ArchiveType::CompressedTar(Compression::Zstd) => {
-                let zstd_decoder = zstd::stream::Decoder::new(hashed_bytes).map_err(|e| {
-                    format!(
-                        "Failed to create a zstd decoder for unpacking to {dst}: {e}",
-                        dst = dst.display()
-                    )
-                })?;
-                unpack_tar(archive, zstd_decoder, work_dir)
+        }?;
+        if let Some(mode) = mode {
+            set_mode(work_dir, mode & !options.umask)?;
+        }
+        Ok::<T, String>(result)
+    })
+}
+
+/// Unpacks a tar-based archive directly from a stream, checking its hash as it is unpacked.
+///
+/// Since the stream is not seekable, the hash can only be checked once the archive has been
+/// unpacked to the work directory; so the unpacked contents are discarded if the hash does not
+/// match.
+#[time("debug", "installer::{}")]
+fn unpack_archive_stream<R: Read, T, F>(
+    archive: ArchiveType,
+    mode: Option<u32>,
+    stream_source: F,
+    expected_hash: &str,
+    dst: &Path,
+    options: UnpackOptions,
+) -> Result<Option<T>, String>
+where
+    F: FnOnce() -> Result<(R, T), String>,
+{
+    atomic_path(dst, Target::Directory, |work_dir| {
+        let (stream, result) = stream_source()?;
+        let mut digesting_stream = fingerprint::DigestingReader::new(stream);
+        // N.B.: Any decompressor reads through the digesting stream; so the hash checked is still
+        // that of the archive bytes as loaded.
+        if let Some(decompressor) = options.decompressor {
+            unpack_decompressed_tar(
+                archive,
+                decompressor,
+                &mut digesting_stream,
+                dst,
+                work_dir,
+                options,
+            )
+        } else {
+            match archive {
+                ArchiveType::Zip => Err(format!(
+                    "Cannot unpack {archive:?} to {dst} from a stream.",
+                    dst = dst.display()
+                )),
+                ArchiveType::Tar => unpack_tar(archive, &mut digesting_stream, work_dir, options),
+                ArchiveType::CompressedTar(compression) => unpack_compressed_tar(
+                    archive,
+                    compression,
+                    &mut digesting_stream,
+                    dst,
+                    work_dir,
+                    options,
+                ),
             }
         }?;
+        let (size, actual_hash) = digesting_stream.finish()?;
+        if options.verify(expected_hash) && expected_hash != actual_hash.as_str() {
+            return Err(format!(
+                "The {file_type} destination {dst} of size {size} had unexpected hash: \
+                {actual_hash}",
+                file_type = archive.as_ext(),
+                dst = dst.display(),
+            ));
+        }
+        debug!(
+            "The {file_type} destination {dst} of size {size} had expected hash",
+            file_type = archive.as_ext(),
+            dst = dst.display()
+        );
+        if let Some(mode) = mode {
+            set_mode(work_dir, mode & !options.umask)?;
+        }
         Ok::<T, String>(result)
     })
 }
 
 #[cfg(not(target_family = "unix"))]
-fn executable_permissions() -> Option<Permissions> {
+fn executable_permissions(_umask: u32) -> Option<Permissions> {
     None
 }
 
 #[cfg(target_family = "unix")]
-fn executable_permissions() -> Option<Permissions> {
+fn executable_permissions(umask: u32) -> Option<Permissions> {
     use std::os::unix::fs::PermissionsExt;
-    Some(Permissions::from_mode(0o755))
+    Some(Permissions::from_mode(0o755 & !umask))
+}
+
+#[cfg(not(target_family = "unix"))]
+fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+    debug!(
+        "Ignoring mode {mode:04o} for {path} since file modes are not supported on this platform.",
+        path = path.display()
+    );
+    Ok(())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn set_entry_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+// N.B.: Unlike `set_mode`, this only sets the mode of the given path and not of its contents.
+#[cfg(target_family = "unix")]
+fn set_entry_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, Permissions::from_mode(mode)).map_err(|e| {
+        format!(
+            "Failed to set mode {mode:04o} on {path}: {e}",
+            path = path.display()
+        )
+    })
+}
+
+// N.B.: Only regular files get the mode. Directories keep theirs since a mode like `0600` would
+// leave them untraversable, and symlinks are skipped since setting their permissions would change
+// their targets instead.
+#[cfg(target_family = "unix")]
+fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    for entry in walkdir::WalkDir::new(path).contents_first(true) {
+        let entry = entry.map_err(|e| {
+            format!(
+                "Failed to walk {path} to set mode {mode:04o}: {e}",
+                path = path.display()
+            )
+        })?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        std::fs::set_permissions(entry.path(), Permissions::from_mode(mode)).map_err(|e| {
+            format!(
+                "Failed to set mode {mode:04o} on {path}: {e}",
+                path = entry.path().display()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// How to write out a blob's bytes.
+#[derive(Clone, Copy, Debug, Default)]
+struct BlobOptions {
+    executable: bool,
+    compression: Option<Compression>,
+}
+
+impl From<&File> for BlobOptions {
+    fn from(file: &File) -> Self {
+        Self {
+            executable: file.executable.unwrap_or(false),
+            compression: file.compression,
+        }
+    }
 }
 
 #[time("debug", "installer::{}")]
 fn unpack_blob<R: Read + Seek, T, F>(
-    executable: bool,
+    blob: BlobOptions,
+    mode: Option<u32>,
     bytes_source: F,
     expected_hash: &str,
     dst: &Path,
+    options: UnpackOptions,
 ) -> Result<Option<T>, String>
 where
     F: FnOnce() -> Result<(R, T), String>,
 {
     atomic_path(dst, Target::File, |blob_dst| {
         let (bytes, result) = bytes_source()?;
-        let mut hashed_bytes = check_hash("blob", bytes, expected_hash, dst)?;
+        let hashed_bytes = if options.verify(expected_hash) {
+            check_hash("blob", bytes, expected_hash, dst)?
+        } else {
+            bytes
+        };
+        // N.B.: Only decompression can produce more bytes than the hash vouches for; so only
+        // compressed blobs are subject to the extraction limit.
+        let mut blob_bytes: LimitedReader<Box<dyn Read + '_>> =
+            if let Some(compression) = blob.compression {
+                LimitedReader::new(
+                    decompress(compression, hashed_bytes, dst, options)?,
+                    options.max_extract_size,
+                )
+            } else {
+                LimitedReader::new(Box::new(hashed_bytes), None)
+            };
         let mut blob_out = OpenOptions::new()
             .write(true)
             .create_new(true)
@@ -130,8 +751,8 @@ where
                     blob_dst = blob_dst.display()
                 )
             })?;
-        if executable {
-            if let Some(permissions) = executable_permissions() {
+        if blob.executable && mode.is_none() {
+            if let Some(permissions) = executable_permissions(options.umask) {
                 blob_out.set_permissions(permissions).map_err(|e| {
                     format!(
                         "Failed to set executable premissions on {dst}: {e}",
@@ -140,41 +761,458 @@ where
                 })?;
             }
         }
-        std::io::copy(&mut hashed_bytes, &mut blob_out)
+        let copied = std::io::copy(&mut blob_bytes, &mut blob_out);
+        if blob_bytes.exceeded {
+            return Err(format!(
+                "Failed to unpack blob to {dst}: decompression exceeded limit of {limit} bytes set \
+                by SCIE_MAX_EXTRACT_BYTES.",
+                dst = dst.display(),
+                limit = options.max_extract_size.unwrap_or_default()
+            ));
+        }
+        copied
             .map(|_| ())
             .map_err(|e| format!("Failed to unpack blob to {dst}: {e}", dst = dst.display()))?;
+        if let Some(mode) = mode {
+            set_mode(blob_dst, mode & !options.umask)?;
+        }
         Ok::<T, String>(result)
     })
 }
 
 fn unpack<R: Read + Seek, T, F>(
     file_type: FileType,
-    executable: bool,
+    blob: BlobOptions,
+    mode: Option<u32>,
     bytes: F,
     expected_hash: &str,
     dst: &Path,
+    options: UnpackOptions,
 ) -> Result<Option<T>, String>
 where
     F: FnOnce() -> Result<(R, T), String>,
 {
     match file_type {
-        FileType::Archive(archive_type) => unpack_archive(archive_type, bytes, expected_hash, dst),
-        FileType::Blob => unpack_blob(executable, bytes, expected_hash, dst),
-        FileType::Directory => unpack_archive(ArchiveType::Zip, bytes, expected_hash, dst),
+        FileType::Archive(archive_type) => {
+            unpack_archive(archive_type, mode, bytes, expected_hash, dst, options)
+        }
+        FileType::Blob => unpack_blob(blob, mode, bytes, expected_hash, dst, options),
+        FileType::Directory => {
+            unpack_archive(ArchiveType::Zip, mode, bytes, expected_hash, dst, options)
+        }
     }
 }
 
-pub(crate) struct Installer<'a> {
+/// Returns the bytes of `file` found at `location` in the scie payload.
+///
+/// A truncated payload, as found in a corrupt scie, is reported as an error instead of a panic.
+fn payload_slice<'a>(payload: &'a [u8], file: &File, location: usize) -> Result<&'a [u8], String> {
+    let end = location.checked_add(file.size).ok_or_else(|| {
+        format!(
+            "The scie payload is corrupt: file {name} of size {size} at {location} overflows the \
+            payload address space.",
+            name = file.name,
+            size = file.size
+        )
+    })?;
+    payload.get(location..end).ok_or_else(|| {
+        format!(
+            "The scie payload is truncated: file {name} needs bytes {location}..{end} but the \
+            payload is only {len} bytes.",
+            name = file.name,
+            len = payload.len()
+        )
+    })
+}
+
+/// Returns the verified bytes of a file stored directly in the scie payload.
+///
+/// This is for files the scie-jump itself needs to read; so the file must not be loaded via a
+/// binding or stored in a scie-tote.
+pub(crate) fn scie_file<'a>(
     payload: &'a [u8],
+    lift: &Lift,
+    name: &str,
+    purpose: &str,
+) -> Result<&'a [u8], String> {
+    scie_file_range(payload, lift, name, purpose).map(|range| &payload[range])
+}
+
+fn scie_file_range(
+    payload: &[u8],
+    lift: &Lift,
+    name: &str,
+    purpose: &str,
+) -> Result<Range<usize>, String> {
+    let file = lift
+        .file(name)
+        .ok_or_else(|| format!("The {purpose} {name} does not name a file in this scie."))?;
+    if file.source != Source::Scie {
+        return Err(format!(
+            "The {purpose} {name} must be stored in the scie but it is loaded via {source:?}.",
+            source = file.source
+        ));
+    }
+    if file.size == 0 {
+        return Err(format!(
+            "The {purpose} {name} is stored in the scie-tote but it must be stored directly in \
+            the scie."
+        ));
+    }
+    let location = lift
+        .files
+        .iter()
+        .take_while(|other| !std::ptr::eq(*other, file))
+        .filter(|other| other.source == Source::Scie)
+        .map(|other| other.size)
+        .sum();
+    let bytes = payload_slice(payload, file, location)?;
+    let hash = fingerprint::digest(bytes);
+    if file.hash != hash {
+        return Err(format!("The {purpose} {name} had unexpected hash: {hash}"));
+    }
+    Ok(location..location + bytes.len())
+}
+
+/// The path of a file in a directory of loose files.
+///
+/// The file is looked up by its name and then, failing that, by its hash.
+fn loose_path(directory: &Path, file: &File) -> PathBuf {
+    let by_name = directory.join(&file.name);
+    if by_name.is_file() || file.hash.is_empty() {
+        by_name
+    } else {
+        directory.join(&file.hash)
+    }
+}
+
+/// Opens a file in a directory of loose files.
+fn loose_file(directory: &Path, file: &File) -> Result<std::fs::File, String> {
+    let path = loose_path(directory, file);
+    std::fs::File::open(&path).map_err(|e| {
+        format!(
+            "Failed to read {name} from the loose file {path}: {e}",
+            name = file.name,
+            path = path.display()
+        )
+    })
+}
+
+// A range of bytes in a memory mapped file.
+struct MappedRange {
+    mmap: memmap::Mmap,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for MappedRange {
+    fn as_ref(&self) -> &[u8] {
+        &self.mmap[self.range.clone()]
+    }
+}
+
+// The bytes of a file to install.
+enum FileBytes<'a> {
+    // A file stored in the scie payload.
+    Payload(Cursor<&'a [u8]>),
+    // A local file; e.g.: a loose file, a `file://` file or a `scie://` file extracted from the
+    // source scie's scie-tote.
+    File(std::fs::File),
+    // A `scie://` file stored directly in the source scie's payload.
+    Mapped(Cursor<MappedRange>),
+}
+
+impl Read for FileBytes<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            FileBytes::Payload(payload) => payload.read(buf),
+            FileBytes::File(file) => file.read(buf),
+            FileBytes::Mapped(mapped) => mapped.read(buf),
+        }
+    }
+}
+
+impl Seek for FileBytes<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            FileBytes::Payload(payload) => payload.seek(pos),
+            FileBytes::File(file) => file.seek(pos),
+            FileBytes::Mapped(mapped) => mapped.seek(pos),
+        }
+    }
+}
+
+/// Opens the bytes of a file loaded from one of the [`crate::lift::URL_SCHEMES`].
+///
+/// The bytes are streamed from the source file instead of being read into memory.
+fn load_url(file: &File) -> Result<FileBytes<'static>, String> {
+    let Source::Url((scheme, path)) = &file.source else {
+        return Err(format!(
+            "The file {name} is not loaded from a URL: {source:?}",
+            name = file.name,
+            source = file.source
+        ));
+    };
+    let source = std::fs::File::open(path).map_err(|e| {
+        format!(
+            "Failed to read {name} from {scheme}://{path}: {e}",
+            name = file.name
+        )
+    })?;
+    match scheme.as_str() {
+        "file" => Ok(FileBytes::File(source)),
+        "scie" => {
+            let data = unsafe { memmap::Mmap::map(&source) }.map_err(|e| {
+                format!(
+                    "Failed to mmap {scheme}://{path} to read {name}: {e}",
+                    name = file.name
+                )
+            })?;
+            let (jump, lift) = crate::lift::load_scie(Path::new(path), &data)?;
+            let payload_range = jump.size..data.len() - lift.size;
+            let payload = &data[payload_range.clone()];
+            let stored = lift.file(&file.name).ok_or_else(|| {
+                format!(
+                    "The source file {name} does not name a file in the scie at {path}.",
+                    name = file.name
+                )
+            })?;
+            if stored.size == 0 && stored.source == Source::Scie {
+                // N.B.: Files with no size are stored in the scie-tote, which is always the last
+                // file in the scie.
+                let tote = lift.files.last().ok_or_else(|| {
+                    format!("The scie at {path} has a scie-tote entry but no scie-tote.")
+                })?;
+                let tote_bytes = scie_file(payload, &lift, &tote.name, "scie-tote")?;
+                let mut tote_zip = zip::ZipArchive::new(Cursor::new(tote_bytes))
+                    .map_err(|e| format!("Failed to open the scie-tote of {path}: {e}"))?;
+                let mut member = tote_zip.by_name(&stored.name).map_err(|e| {
+                    format!(
+                        "Failed to find {name} in the scie-tote of {path}: {e}",
+                        name = stored.name
+                    )
+                })?;
+                let mut extracted = tempfile::tempfile().map_err(|e| {
+                    format!(
+                        "Failed to create a temporary file to extract {name} from {path} to: {e}",
+                        name = stored.name
+                    )
+                })?;
+                std::io::copy(&mut member, &mut extracted)
+                    .and_then(|_| extracted.rewind())
+                    .map_err(|e| {
+                        format!(
+                            "Failed to extract {name} from the scie-tote of {path}: {e}",
+                            name = stored.name
+                        )
+                    })?;
+                return Ok(FileBytes::File(extracted));
+            }
+            let range = scie_file_range(payload, &lift, &file.name, "source file")?;
+            let range = payload_range.start + range.start..payload_range.start + range.end;
+            Ok(FileBytes::Mapped(Cursor::new(MappedRange {
+                mmap: data,
+                range,
+            })))
+        }
+        _ => Err(format!(
+            "The file {name} uses the unsupported source scheme {scheme}://.",
+            name = file.name
+        )),
+    }
+}
+
+// Where the installer reads the bytes of the files stored in the scie from.
+enum Payload<'a> {
+    // The scie payload, with its files laid out back to back in lift manifest order.
+    Embedded(&'a [u8]),
+    // A directory of loose files; e.g.: as produced by `SCIE=split`.
+    Directory(PathBuf),
+}
+
+pub(crate) struct Installer<'a> {
+    payload: Payload<'a>,
+    zstd_dictionary: Option<Cow<'a, [u8]>>,
+    decompressors: Decompressors,
+    options: UnpackOptions<'a>,
+    audit_log: Option<PathBuf>,
+    dry_run: bool,
 }
 
 impl<'a> Installer<'a> {
     pub(crate) fn new(payload: &'a [u8]) -> Self {
-        Self { payload }
+        Self::with_payload(Payload::Embedded(payload))
+    }
+
+    /// Creates an installer that reads the files stored in the scie from loose files in
+    /// `directory` instead of from the scie payload.
+    pub(crate) fn from_directory(directory: PathBuf) -> Self {
+        Self::with_payload(Payload::Directory(directory))
+    }
+
+    fn with_payload(payload: Payload<'a>) -> Self {
+        Self {
+            payload,
+            zstd_dictionary: None,
+            decompressors: Decompressors::new(),
+            options: UnpackOptions::default(),
+            audit_log: None,
+            dry_run: false,
+        }
+    }
+
+    fn file_bytes(&self, file: &File, location: usize) -> Result<FileBytes<'a>, String> {
+        match &self.payload {
+            Payload::Embedded(payload) => {
+                let payload: &'a [u8] = *payload;
+                payload_slice(payload, file, location)
+                    .map(|bytes| FileBytes::Payload(Cursor::new(bytes)))
+            }
+            Payload::Directory(directory) => loose_file(directory, file).map(FileBytes::File),
+        }
+    }
+
+    /// Configures the installer to install nothing; so command selection and reification can be
+    /// performed without touching the filesystem.
+    pub(crate) fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Configures the installer to skip verifying files with no hash, as found in dev scies.
+    pub(crate) fn dev(mut self, dev: bool) -> Self {
+        self.options.dev = dev;
+        self
+    }
+
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Configures the installer with decompressors for the files whose "decompressor" names
+    /// them.
+    pub(crate) fn decompressors(mut self, decompressors: Decompressors) -> Self {
+        self.decompressors = decompressors;
+        self
+    }
+
+    fn unpack_options<'b>(&'b self, file: &'b File) -> Result<UnpackOptions<'b>, String> {
+        let decompressor = file
+            .decompressor
+            .as_ref()
+            .map(|name| {
+                self.decompressors.get(name).ok_or_else(|| {
+                    format!(
+                        "The file {file} needs the decompressor {name} but no such decompressor \
+                        is registered.",
+                        file = file.name
+                    )
+                })
+            })
+            .transpose()?;
+        Ok(UnpackOptions {
+            zstd_dictionary: if file.use_zstd_dictionary {
+                self.zstd_dictionary.as_deref()
+            } else {
+                None
+            },
+            decompressor,
+            extract_members: file.extract_members.as_slice(),
+            ..self.options
+        })
+    }
+
+    /// Configures the installer to append a JSON line to the given audit log for each file it
+    /// freshly extracts.
+    pub(crate) fn audit_log(mut self, audit_log: Option<PathBuf>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    fn audit(&self, file: &File, dst: &Path, size: u64) -> Result<(), String> {
+        let audit_log = if let Some(audit_log) = self.audit_log.as_ref() {
+            audit_log
+        } else {
+            return Ok(());
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut line = serde_json::to_vec(&serde_json::json!({
+            "name": file.name,
+            "hash": file.hash,
+            "dst": dst,
+            "size": size,
+            "timestamp": timestamp,
+        }))
+        .map_err(|e| format!("Failed to serialize the audit record for {dst:?}: {e}"))?;
+        line.push(b'\n');
+        let record = compress_record(audit_log, line)?;
+        // N.B.: Each record is written with a single append so that concurrent scies sharing an
+        // audit log do not interleave their records.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(audit_log)
+            .and_then(|mut log| log.write_all(record.as_slice()))
+            .map_err(|e| {
+                format!(
+                    "Failed to append to the audit log at {audit_log}: {e}",
+                    audit_log = audit_log.display()
+                )
+            })
+    }
+
+    /// Configures the installer to refuse to unpack archives larger than the given size.
+    pub(crate) fn max_extract_size(mut self, max_extract_size: Option<u64>) -> Self {
+        self.options.max_extract_size = max_extract_size;
+        self
+    }
+
+    /// Configures the installer to remove the mode bits set in `umask` from the modes it sets on
+    /// the files and directories it creates.
+    ///
+    /// N.B.: The files and directories created with default modes get the process umask instead.
+    pub(crate) fn umask(mut self, umask: u32) -> Self {
+        self.options.umask = umask;
+        self
+    }
+
+    /// Configures the installer to use the lift's zstd dictionary, if any, when unpacking zstd
+    /// compressed archives.
+    pub(crate) fn zstd_dictionary(mut self, lift: &Lift) -> Result<Self, String> {
+        if let Some(name) = lift.zstd_dictionary.as_ref() {
+            let dictionary = match &self.payload {
+                Payload::Embedded(payload) => {
+                    Cow::Borrowed(scie_file(*payload, lift, name, "zstd dictionary")?)
+                }
+                Payload::Directory(directory) => {
+                    let file = lift.file(name).ok_or_else(|| {
+                        format!("The zstd dictionary {name} does not name a file in this scie.")
+                    })?;
+                    let mut bytes = vec![];
+                    loose_file(directory, file)?
+                        .read_to_end(&mut bytes)
+                        .map_err(|e| format!("Failed to read the zstd dictionary {name}: {e}"))?;
+                    let hash = fingerprint::digest(&bytes);
+                    if file.hash != hash {
+                        return Err(format!(
+                            "The zstd dictionary {name} had unexpected hash: {hash}"
+                        ));
+                    }
+                    Cow::Owned(bytes)
+                }
+            };
+            self.zstd_dictionary = Some(dictionary);
+        }
+        Ok(self)
     }
 
     #[time("debug", "Installer::{}")]
     pub(crate) fn install(&self, files: &[FileEntry]) -> Result<(), String> {
+        if self.dry_run {
+            return Ok(());
+        }
         let mut scie_tote = vec![];
         let mut location = 0;
         for file_entry in files {
@@ -184,37 +1222,53 @@ impl<'a> Installer<'a> {
                     if file.size == 0 {
                         scie_tote.push((file, file.file_type, dst.clone()));
                     } else {
-                        let bytes = &self.payload[location..(location + file.size)];
-                        unpack(
+                        if unpack(
                             file.file_type,
-                            file.executable.unwrap_or(false),
-                            || Ok((Cursor::new(bytes), ())),
+                            BlobOptions::from(file),
+                            file.mode,
+                            || Ok((self.file_bytes(file, location)?, ())),
                             file.hash.as_str(),
                             dst,
-                        )?;
+                            self.unpack_options(file)?,
+                        )?
+                        .is_some()
+                        {
+                            self.audit(file, dst, file.size as u64)?;
+                        }
                     }
                     file.size
                 }
                 FileEntry::LoadAndInstall((binding, file, dst)) => {
-                    let buffer_source = || {
+                    let spawn_load = || {
                         info!(
                             "Loading {file} via {exe:?}...",
                             file = file.name,
                             exe = binding.exe()
                         );
-                        let mut buffer = tempfile::tempfile().map_err(|e| {
-                            format!(
-                                "Failed to establish a temporary file buffer for loading {file:?} via \
-                                {binding:?}: {e}"
-                            )
-                        })?;
+                        let deadline = binding.timeout().map(|timeout| Instant::now() + timeout);
                         let mut child =
                             binding.spawn_stdout(vec![file.name.as_str()].as_slice())?;
-                        let mut stdout = child.stdout.take().ok_or_else(|| {
+                        let stdout = child.stdout.take().ok_or_else(|| {
                             format!(
                                 "Failed to grab stdout attempting to load {file:?} via binding."
                             )
                         })?;
+                        let stream = LoadStream {
+                            binding,
+                            file,
+                            stdout,
+                            watchdog: Some(Watchdog::new(child, deadline)),
+                        };
+                        Ok::<_, String>((stream, ()))
+                    };
+                    let buffer_source = || {
+                        let mut buffer = tempfile::tempfile().map_err(|e| {
+                            format!(
+                                "Failed to establish a temporary file buffer for loading {file:?} via \
+                                {binding:?}: {e}"
+                            )
+                        })?;
+                        let (mut stdout, spawned) = spawn_load()?;
                         std::io::copy(&mut stdout, &mut buffer)
                             .map_err(|e| format!("Failed to load {file:?} via {binding:?}: {e}"))?;
                         buffer.rewind().map_err(|e| {
@@ -223,23 +1277,49 @@ impl<'a> Installer<'a> {
                                 {binding:?}: {e}"
                             )
                         })?;
-                        Ok((buffer, child))
+                        Ok((buffer, spawned))
+                    };
+                    // Tar-based archives can be unpacked straight from the load stream, but blobs and
+                    // zips must be buffered first.
+                    let loaded = match file.file_type {
+                        FileType::Archive(archive_type) if archive_type != ArchiveType::Zip => {
+                            unpack_archive_stream(
+                                archive_type,
+                                file.mode,
+                                &spawn_load,
+                                file.hash.as_str(),
+                                dst,
+                                self.unpack_options(file)?,
+                            )?
+                        }
+                        file_type => unpack(
+                            file_type,
+                            BlobOptions::from(file),
+                            file.mode,
+                            buffer_source,
+                            file.hash.as_str(),
+                            dst,
+                            self.unpack_options(file)?,
+                        )?,
                     };
-                    if let Some(mut child) = unpack(
+                    if loaded.is_some() {
+                        self.audit(file, dst, file.size as u64)?;
+                    }
+                    0
+                }
+                FileEntry::LoadUrl((file, dst)) => {
+                    if unpack(
                         file.file_type,
-                        file.executable.unwrap_or(false),
-                        buffer_source,
+                        BlobOptions::from(file),
+                        file.mode,
+                        || Ok((load_url(file)?, ())),
                         file.hash.as_str(),
                         dst,
-                    )? {
-                        let exit_status = child.wait().map_err(|e| {
-                            format!(
-                                "Failed to await termination of {binding:?} when loading {file:?}: {e}"
-                            )
-                        })?;
-                        if !exit_status.success() {
-                            return Err(format!("Failed to load file {file:?}: {exit_status:?}"));
-                        }
+                        self.unpack_options(file)?,
+                    )?
+                    .is_some()
+                    {
+                        self.audit(file, dst, file.size as u64)?;
                     }
                     0
                 }
@@ -256,13 +1336,15 @@ impl<'a> Installer<'a> {
                             )
                         })?;
                         let path = scie_tote_tmpdir.path().join(&tote_file.name);
-                        let bytes = &self.payload[location..(location + tote_file.size)];
+                        let bytes = self.file_bytes(tote_file, location)?;
                         unpack(
                             tote_file.file_type,
-                            tote_file.executable.unwrap_or(false),
-                            || Ok((Cursor::new(bytes), ())),
+                            BlobOptions::from(tote_file),
+                            tote_file.mode,
+                            || Ok((bytes, ())),
                             tote_file.hash.as_str(),
                             &path,
+                            self.unpack_options(tote_file)?,
                         )?;
                         scie_tote = Some(scie_tote_tmpdir);
                         Ok(path)
@@ -270,23 +1352,37 @@ impl<'a> Installer<'a> {
 
                     for (file, dst) in entries {
                         let file_src = || {
-                            let scie_tote_path = scie_tote_src()?;
-                            let src_path = scie_tote_path.join(&file.name);
+                            // N.B.: Splitting a scie leaves the scie-tote members as loose files
+                            // alongside the others; so there is no scie-tote to unpack.
+                            let src_path = match &self.payload {
+                                Payload::Embedded(_) => scie_tote_src()?.join(&file.name),
+                                Payload::Directory(directory) => loose_path(directory, file),
+                            };
                             let file = std::fs::File::open(&src_path).map_err(|e| {
                                 format!(
                                     "Failed to open {file:?} at {src} from the unpacked scie-tote: {e}",
                                     src = src_path.display()
                                 )
                             })?;
-                            Ok((file, ()))
+                            let size = file.metadata().map(|metadata| metadata.len()).map_err(|e| {
+                                format!(
+                                    "Failed to read the size of {src} from the unpacked scie-tote: {e}",
+                                    src = src_path.display()
+                                )
+                            })?;
+                            Ok((file, size))
                         };
-                        unpack(
+                        if let Some(size) = unpack(
                             file.file_type,
-                            file.executable.unwrap_or(false),
+                            BlobOptions::from(file),
+                            file.mode,
                             file_src,
                             file.hash.as_str(),
                             dst,
-                        )?;
+                            self.unpack_options(file)?,
+                        )? {
+                            self.audit(file, dst, size)?;
+                        }
                     }
                     tote_file.size
                 }
@@ -297,3 +1393,1062 @@ impl<'a> Installer<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::cell::Cell;
+    use std::io::{Cursor, Read, Write};
+    use std::rc::Rc;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::{
+        unpack, unpack_archive_stream, BlobOptions, Decompressor, Decompressors, Installer,
+        UnpackOptions,
+    };
+    use crate::config::{ArchiveType, Compression, FileType};
+    use crate::context::{FileEntry, LoadProcess};
+    use crate::process::{EnvVars, Process};
+    use crate::{fingerprint, testing, File, Source};
+
+    fn create_tar(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, name, contents).unwrap();
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn max_extract_size() {
+        let contents = vec![0_u8; 1024 * 1024];
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder
+            .write_all(create_tar("zeros", contents.as_slice()).as_slice())
+            .unwrap();
+        let archive = encoder.finish().unwrap();
+        assert!(archive.len() < 10 * 1024);
+        let hash = fingerprint::digest(archive.as_slice());
+        let file_type = FileType::Archive(ArchiveType::CompressedTar(Compression::Gzip));
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        let err = unpack(
+            file_type,
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                max_extract_size: Some(64 * 1024),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.contains("decompression exceeded limit"), "{err}");
+        assert!(!dst.exists());
+
+        unpack(
+            file_type,
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                max_extract_size: Some(2 * 1024 * 1024),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(contents, std::fs::read(dst.join("zeros")).unwrap());
+    }
+
+    #[test]
+    fn max_extract_size_zip_lying_headers() {
+        let contents = vec![0_u8; 1024 * 1024];
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file(
+            "zeros",
+            FileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+        )
+        .unwrap();
+        zip.write_all(contents.as_slice()).unwrap();
+        let mut archive = zip.finish().unwrap().into_inner();
+        assert!(archive.len() < 10 * 1024);
+
+        // Claim the entry is a single byte in both the local and central directory headers.
+        let patch_size = |archive: &mut Vec<u8>, signature: &[u8], offset: usize| {
+            let header = archive
+                .windows(4)
+                .position(|window| window == signature)
+                .unwrap();
+            archive[header + offset..header + offset + 4].copy_from_slice(&1_u32.to_le_bytes());
+        };
+        patch_size(&mut archive, b"PK\x03\x04", 22);
+        patch_size(&mut archive, b"PK\x01\x02", 24);
+        let hash = fingerprint::digest(archive.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        let err = unpack(
+            FileType::Archive(ArchiveType::Zip),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                max_extract_size: Some(64 * 1024),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.contains("decompression exceeded limit"), "{err}");
+        assert!(!dst.exists());
+    }
+
+    #[test]
+    fn stream() {
+        let contents = b"Streamed.";
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder
+            .write_all(create_tar("file.txt", contents).as_slice())
+            .unwrap();
+        let archive = encoder.finish().unwrap();
+        let hash = fingerprint::digest(archive.as_slice());
+        let archive_type = ArchiveType::CompressedTar(Compression::Gzip);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        assert!(unpack_archive_stream(
+            archive_type,
+            None,
+            || Ok((archive.as_slice(), ())),
+            "bad",
+            &dst,
+            UnpackOptions::default(),
+        )
+        .is_err());
+        assert!(!dst.exists());
+        assert!(!dst.with_extension("work").exists());
+
+        assert_eq!(
+            Some(()),
+            unpack_archive_stream(
+                archive_type,
+                None,
+                || Ok((archive.as_slice(), ())),
+                hash.as_str(),
+                &dst,
+                UnpackOptions::default(),
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            contents.as_slice(),
+            std::fs::read(dst.join("file.txt")).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn tar_z() {
+        let archive = include_bytes!("../tests/fixtures/lorem.tar.Z");
+        let hash = fingerprint::digest(archive);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::from_ext("tar.Z").unwrap()),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        let contents = std::fs::read_to_string(dst.join("lorem.txt")).unwrap();
+        assert_eq!(250, contents.lines().count());
+    }
+
+    #[test]
+    fn zstd_dictionary() {
+        let dictionary = b"The quick brown fox jumps over the lazy dog.".repeat(10);
+
+        let contents = b"The quick brown fox jumps over the lazy dog!";
+        let tar = create_tar("fox.txt", contents);
+
+        let mut encoder =
+            zstd::stream::Encoder::with_dictionary(vec![], 3, dictionary.as_slice()).unwrap();
+        encoder.write_all(tar.as_slice()).unwrap();
+        let archive = encoder.finish().unwrap();
+        let hash = fingerprint::digest(archive.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::CompressedTar(Compression::Zstd)),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                zstd_dictionary: Some(dictionary.as_slice()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            contents.as_slice(),
+            std::fs::read(dst.join("fox.txt")).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn zstd_dictionary_scope() {
+        let dictionary = b"The quick brown fox jumps over the lazy dog.".repeat(10);
+        let mut installer = Installer::new(&[]);
+        installer.zstd_dictionary = Some(Cow::Borrowed(dictionary.as_slice()));
+
+        let file = File {
+            file_type: FileType::Archive(ArchiveType::CompressedTar(Compression::Zstd)),
+            ..File::default()
+        };
+        assert!(installer
+            .unpack_options(&file)
+            .unwrap()
+            .zstd_dictionary
+            .is_none());
+
+        let file = File {
+            use_zstd_dictionary: true,
+            ..file
+        };
+        assert_eq!(
+            Some(dictionary.as_slice()),
+            installer.unpack_options(&file).unwrap().zstd_dictionary
+        );
+    }
+
+    #[test]
+    fn decompressor() {
+        let tar = create_tar("fox.txt", b"The quick brown fox jumps over the lazy dog.");
+        let file = File {
+            file_type: FileType::Archive(ArchiveType::Tar),
+            decompressor: Some("identity".to_string()),
+            ..testing::blob("fox.tar.custom", &tar)
+        };
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let err = Installer::new(&tar)
+            .install(&[FileEntry::Install((
+                file.clone(),
+                tempdir.path().join("unregistered"),
+            ))])
+            .unwrap_err();
+        assert!(
+            err.contains(
+                "The file fox.tar.custom needs the decompressor identity but no such decompressor \
+                is registered."
+            ),
+            "{err}"
+        );
+
+        let calls = Rc::new(Cell::new(0));
+        let decompressor_calls = Rc::clone(&calls);
+        let mut decompressors = Decompressors::new();
+        decompressors.insert(
+            "identity".to_string(),
+            Decompressor::new(move |bytes| {
+                decompressor_calls.set(decompressor_calls.get() + 1);
+                bytes
+            }),
+        );
+        let dst = tempdir.path().join("archive");
+        Installer::new(&tar)
+            .decompressors(decompressors)
+            .install(&[FileEntry::Install((file, dst.clone()))])
+            .unwrap();
+        assert_eq!(1, calls.get());
+        assert_eq!(
+            "The quick brown fox jumps over the lazy dog.",
+            std::fs::read_to_string(dst.join("fox.txt")).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode_of =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let blob = b"secret";
+        let hash = fingerprint::digest(blob);
+        let dst = tempdir.path().join("key");
+        unpack(
+            FileType::Blob,
+            BlobOptions {
+                executable: true,
+                ..Default::default()
+            },
+            Some(0o600),
+            || Ok((Cursor::new(blob.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(0o600, mode_of(&dst));
+
+        let archive = create_tar("file.txt", b"contents");
+        let hash = fingerprint::digest(archive.as_slice());
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            Some(0o600),
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        assert_ne!(0o600, mode_of(&dst));
+        assert_eq!(0o600, mode_of(&dst.join("file.txt")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn umask() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode_of =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        let options = UnpackOptions {
+            umask: 0o077,
+            ..Default::default()
+        };
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let blob = b"secret";
+        let hash = fingerprint::digest(blob);
+        let dst = tempdir.path().join("blobs").join("key");
+        unpack(
+            FileType::Blob,
+            BlobOptions::default(),
+            Some(0o644),
+            || Ok((Cursor::new(blob.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            options,
+        )
+        .unwrap();
+        assert_eq!(0o600, mode_of(&dst));
+
+        let archive = create_tar("file.txt", b"contents");
+        let hash = fingerprint::digest(archive.as_slice());
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            Some(0o755),
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            options,
+        )
+        .unwrap();
+        assert_eq!(0o700, mode_of(&dst.join("file.txt")));
+
+        let dst = tempdir.path().join("archive-modes");
+        unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            options,
+        )
+        .unwrap();
+        assert_eq!(0o600, mode_of(&dst.join("file.txt")));
+    }
+
+    #[test]
+    fn audit_log() {
+        let payload = b"foobar";
+        let tempdir = tempfile::tempdir().unwrap();
+        let audit_log = tempdir.path().join("audit.log");
+        let installer = Installer::new(payload).audit_log(Some(audit_log.clone()));
+        let install = |base: &str| {
+            installer.install(&[
+                FileEntry::Install((
+                    testing::blob("foo", b"foo"),
+                    tempdir.path().join(base).join("foo"),
+                )),
+                FileEntry::Install((
+                    testing::blob("bar", b"bar"),
+                    tempdir.path().join(base).join("bar"),
+                )),
+            ])
+        };
+
+        install("base").unwrap();
+        let records = std::fs::read_to_string(&audit_log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(2, records.len());
+        assert_eq!("foo", records[0]["name"]);
+        assert_eq!(fingerprint::digest(b"foo"), records[0]["hash"]);
+        assert_eq!(3, records[0]["size"]);
+        assert!(records[0]["timestamp"].is_u64());
+        assert_eq!("bar", records[1]["name"]);
+
+        // Files that are already extracted are not audited again.
+        install("base").unwrap();
+        assert_eq!(
+            2,
+            std::fs::read_to_string(&audit_log).unwrap().lines().count()
+        );
+
+        install("other").unwrap();
+        assert_eq!(
+            4,
+            std::fs::read_to_string(&audit_log).unwrap().lines().count()
+        );
+    }
+
+    #[test]
+    fn compressed_audit_log() {
+        let payload = b"foobar";
+        let tempdir = tempfile::tempdir().unwrap();
+        let audit_log = tempdir.path().join("audit.log.gz");
+        Installer::new(payload)
+            .audit_log(Some(audit_log.clone()))
+            .install(&[
+                FileEntry::Install((testing::blob("foo", b"foo"), tempdir.path().join("foo"))),
+                FileEntry::Install((testing::blob("bar", b"bar"), tempdir.path().join("bar"))),
+            ])
+            .unwrap();
+
+        let mut records = String::new();
+        flate2::read::MultiGzDecoder::new(std::fs::File::open(&audit_log).unwrap())
+            .read_to_string(&mut records)
+            .unwrap();
+        let names = records
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["name"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["foo", "bar"], names);
+    }
+
+    #[test]
+    fn compressed_blob() {
+        let contents = b"#!/bin/sh\necho compressed\n";
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(contents).unwrap();
+        let blob = encoder.finish().unwrap();
+        let hash = fingerprint::digest(blob.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("tool");
+        unpack(
+            FileType::Blob,
+            BlobOptions {
+                executable: true,
+                compression: Some(Compression::Gzip),
+            },
+            None,
+            || Ok((Cursor::new(blob.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(contents.as_slice(), std::fs::read(&dst).unwrap().as_slice());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                0o755,
+                std::fs::metadata(&dst).unwrap().permissions().mode() & 0o777
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_blob_max_extract_size() {
+        let contents = vec![0_u8; 1024 * 1024];
+        let blob = zstd::stream::encode_all(contents.as_slice(), 0).unwrap();
+        assert!(blob.len() < 10 * 1024);
+        let hash = fingerprint::digest(blob.as_slice());
+        let blob_options = BlobOptions {
+            executable: false,
+            compression: Some(Compression::Zstd),
+        };
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("zeros");
+        let err = unpack(
+            FileType::Blob,
+            blob_options,
+            None,
+            || Ok((Cursor::new(blob.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                max_extract_size: Some(64 * 1024),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.contains("decompression exceeded limit"), "{err}");
+        assert!(!dst.exists());
+
+        unpack(
+            FileType::Blob,
+            blob_options,
+            None,
+            || Ok((Cursor::new(blob.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions {
+                max_extract_size: Some(2 * 1024 * 1024),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(contents, std::fs::read(&dst).unwrap());
+    }
+
+    #[test]
+    fn extract_members() {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in [
+            ("bin/foo", "foo"),
+            ("bin/bar", "bar"),
+            ("lib/baz.txt", "baz"),
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+        let hash = fingerprint::digest(archive.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let unpack_members = |dst: &std::path::Path, members: &[String]| {
+            unpack(
+                FileType::Archive(ArchiveType::Zip),
+                BlobOptions::default(),
+                None,
+                || Ok((Cursor::new(archive.as_slice()), ())),
+                hash.as_str(),
+                dst,
+                UnpackOptions {
+                    extract_members: members,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let dst = tempdir.path().join("subset");
+        let members = vec!["bin/foo".to_string(), "lib/baz.txt".to_string()];
+        unpack_members(&dst, &members).unwrap();
+        assert_eq!("foo", std::fs::read_to_string(dst.join("bin/foo")).unwrap());
+        assert_eq!(
+            "baz",
+            std::fs::read_to_string(dst.join("lib/baz.txt")).unwrap()
+        );
+        assert!(!dst.join("bin/bar").exists());
+
+        let dst = tempdir.path().join("all");
+        unpack_members(&dst, &[]).unwrap();
+        assert!(dst.join("bin/bar").is_file());
+
+        let dst = tempdir.path().join("missing");
+        assert!(unpack_members(&dst, &["bin/dne".to_string()]).is_err());
+        assert!(!dst.exists());
+    }
+
+    #[test]
+    fn dev_unhashed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let unpack_unhashed = |dst: &std::path::Path, dev: bool| {
+            unpack(
+                FileType::Blob,
+                BlobOptions::default(),
+                None,
+                || Ok((Cursor::new(b"unverified".as_slice()), ())),
+                "",
+                dst,
+                UnpackOptions {
+                    dev,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(unpack_unhashed(&tempdir.path().join("prod"), false).is_err());
+
+        let dst = tempdir.path().join("dev");
+        unpack_unhashed(&dst, true).unwrap();
+        assert_eq!("unverified", std::fs::read_to_string(&dst).unwrap());
+
+        let archive = create_tar("file.txt", b"contents");
+        let dst = tempdir.path().join("archive");
+        assert!(unpack_archive_stream(
+            ArchiveType::Tar,
+            None,
+            || Ok((archive.as_slice(), ())),
+            "",
+            &dst,
+            UnpackOptions {
+                dev: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .is_some());
+        assert_eq!(
+            "contents",
+            std::fs::read_to_string(dst.join("file.txt")).unwrap()
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths() {
+        use crate::atomic::extended_length_path;
+
+        let member = vec!["deeply-nested-directory"; 12].join("/") + "/file.txt";
+        assert!(member.len() > 260);
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file(member.as_str(), FileOptions::default())
+            .unwrap();
+        zip.write_all(b"deep").unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        let hash = fingerprint::digest(archive.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::Zip),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        let extracted = extended_length_path(&dst).join(member.replace('/', "\\"));
+        assert_eq!("deep", std::fs::read_to_string(extracted).unwrap());
+    }
+
+    // Creates a ustar entry preceded by a PAX extended header carrying the given extensions.
+    fn create_pax_tar(extensions: &[(&str, &str)], name: &str, contents: &[u8]) -> Vec<u8> {
+        let pax = extensions
+            .iter()
+            .map(|(key, value)| {
+                // Each record is prefixed with its own length, including the length digits.
+                let record = format!(" {key}={value}\n");
+                let mut length = record.len();
+                while length != length.to_string().len() + record.len() {
+                    length = length.to_string().len() + record.len();
+                }
+                format!("{length}{record}")
+            })
+            .collect::<String>();
+        let mut tar = tar::Builder::new(vec![]);
+        let mut pax_header = tar::Header::new_ustar();
+        pax_header.set_entry_type(tar::EntryType::XHeader);
+        pax_header.set_path("PaxHeader").unwrap();
+        pax_header.set_size(pax.len() as u64);
+        pax_header.set_mode(0o644);
+        pax_header.set_cksum();
+        tar.append(&pax_header, pax.as_bytes()).unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_path(name).unwrap();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append(&header, contents).unwrap();
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn pax_long_names() {
+        let long_name = format!("{dirs}long.txt", dirs = "dir/".repeat(30));
+        assert!(long_name.len() > 100);
+        let archive = create_pax_tar(&[("path", long_name.as_str())], "truncated", b"long");
+        let hash = fingerprint::digest(archive.as_slice());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("archive");
+        unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "long",
+            std::fs::read_to_string(dst.join(&long_name)).unwrap()
+        );
+        assert!(!dst.join("truncated").exists());
+
+        let sparse = create_pax_tar(
+            &[
+                ("GNU.sparse.major", "1"),
+                ("GNU.sparse.minor", "0"),
+                ("GNU.sparse.name", "sparse.img"),
+                ("GNU.sparse.realsize", "1048576"),
+            ],
+            "GNUSparseFile.0/sparse.img",
+            b"1\n0\n4\n",
+        );
+        let hash = fingerprint::digest(sparse.as_slice());
+        let err = unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(sparse.as_slice()), ())),
+            hash.as_str(),
+            &tempdir.path().join("sparse"),
+            UnpackOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("PAX format sparse file"), "{err}");
+    }
+
+    #[test]
+    fn path_traversal() {
+        let mut header = tar::Header::new_gnu();
+        let name = b"../escaped.txt";
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(7);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut tar = tar::Builder::new(vec![]);
+        tar.append(&header, b"escaped".as_slice()).unwrap();
+        let malicious_tar = tar.into_inner().unwrap();
+
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("../escaped.txt", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"escaped").unwrap();
+        let malicious_zip = zip.finish().unwrap().into_inner();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        for (archive_type, archive) in [
+            (ArchiveType::Tar, malicious_tar),
+            (ArchiveType::Zip, malicious_zip),
+        ] {
+            let hash = fingerprint::digest(archive.as_slice());
+            let dst = tempdir.path().join("nested").join(archive_type.as_ext());
+            let err = unpack(
+                FileType::Archive(archive_type),
+                BlobOptions::default(),
+                None,
+                || Ok((Cursor::new(archive.as_slice()), ())),
+                hash.as_str(),
+                &dst,
+                UnpackOptions::default(),
+            )
+            .unwrap_err();
+            assert!(
+                err.contains("refusing to extract path outside destination"),
+                "{err}"
+            );
+            assert!(err.contains("../escaped.txt"), "{err}");
+            assert!(!dst.exists());
+            assert!(!tempdir.path().join("nested").join("escaped.txt").exists());
+        }
+
+        let archive = create_tar("dir/file.txt", b"contents");
+        let hash = fingerprint::digest(archive.as_slice());
+        let dst = tempdir.path().join("safe");
+        unpack(
+            FileType::Archive(ArchiveType::Tar),
+            BlobOptions::default(),
+            None,
+            || Ok((Cursor::new(archive.as_slice()), ())),
+            hash.as_str(),
+            &dst,
+            UnpackOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "contents",
+            std::fs::read_to_string(dst.join("dir").join("file.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn loose_files() {
+        let blob = b"blob".to_vec();
+        let tar = create_tar("member.txt", b"member");
+        let files = [
+            testing::blob("blob", &blob),
+            File {
+                file_type: FileType::Archive(ArchiveType::Tar),
+                ..testing::blob("archive.tar", &tar)
+            },
+        ];
+        let entries = |base: &std::path::Path| {
+            files
+                .iter()
+                .map(|file| FileEntry::Install((file.clone(), base.join(&file.name))))
+                .collect::<Vec<_>>()
+        };
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let payload = [blob.as_slice(), tar.as_slice()].concat();
+        let embedded = tempdir.path().join("embedded");
+        Installer::new(&payload)
+            .install(&entries(&embedded))
+            .unwrap();
+
+        // Loose files can be found either by name or by hash.
+        let loose = tempdir.path().join("loose");
+        std::fs::create_dir(&loose).unwrap();
+        std::fs::write(loose.join("blob"), &blob).unwrap();
+        std::fs::write(loose.join(&files[1].hash), &tar).unwrap();
+        let from_directory = tempdir.path().join("from_directory");
+        Installer::from_directory(loose.clone())
+            .install(&entries(&from_directory))
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read(embedded.join("blob")).unwrap(),
+            std::fs::read(from_directory.join("blob")).unwrap()
+        );
+        assert_eq!(
+            std::fs::read(embedded.join("archive.tar").join("member.txt")).unwrap(),
+            std::fs::read(from_directory.join("archive.tar").join("member.txt")).unwrap()
+        );
+
+        // Loose files are verified just like embedded ones.
+        std::fs::write(loose.join("blob"), b"tampered").unwrap();
+        let err = Installer::from_directory(loose)
+            .install(&entries(&tempdir.path().join("tampered")))
+            .unwrap_err();
+        assert!(err.contains("unexpected hash"), "{err}");
+    }
+
+    #[test]
+    fn loose_files_scie_tote() {
+        let member = b"member".to_vec();
+        let member_file = File {
+            size: 0,
+            ..testing::blob("member", &member)
+        };
+        let tote_file = File {
+            file_type: FileType::Archive(ArchiveType::Zip),
+            ..testing::blob("scie-tote", b"not loose")
+        };
+
+        // N.B.: A split scie has its scie-tote members as loose files, but no scie-tote.
+        let tempdir = tempfile::tempdir().unwrap();
+        let loose = tempdir.path().join("loose");
+        std::fs::create_dir(&loose).unwrap();
+        std::fs::write(loose.join("member"), &member).unwrap();
+        let dst = tempdir.path().join("installed").join("member");
+        Installer::from_directory(loose)
+            .install(&[FileEntry::ScieTote((
+                tote_file,
+                vec![(member_file, dst.clone())],
+            ))])
+            .unwrap();
+        assert_eq!(member, std::fs::read(dst).unwrap());
+    }
+
+    #[test]
+    fn truncated_payload() {
+        let blob = b"blob".to_vec();
+        let file = testing::blob("blob", &blob);
+        let tempdir = tempfile::tempdir().unwrap();
+        let err = Installer::new(&blob[..2])
+            .install(&[FileEntry::Install((file, tempdir.path().join("blob")))])
+            .unwrap_err();
+        assert!(
+            err.contains(
+                "The scie payload is truncated: file blob needs bytes 0..4 but the payload is \
+                only 2 bytes."
+            ),
+            "{err}"
+        );
+
+        let file = File {
+            size: usize::MAX,
+            ..testing::blob("huge", &blob)
+        };
+        let err = Installer::new(&blob)
+            .install(&[
+                FileEntry::Skip(2),
+                FileEntry::Install((file, tempdir.path().join("huge"))),
+            ])
+            .unwrap_err();
+        assert!(
+            err.contains("The scie payload is corrupt: file huge of size"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn scie_url_source() {
+        let direct = b"stored directly".to_vec();
+        let member = b"stored in the scie-tote".to_vec();
+        let mut tote = ZipWriter::new(Cursor::new(vec![]));
+        tote.start_file("member", FileOptions::default()).unwrap();
+        tote.write_all(&member).unwrap();
+        let tote = tote.finish().unwrap().into_inner();
+
+        let mut scie = b"#!scie-jump".to_vec();
+        scie.extend(&direct);
+        scie.extend(&tote);
+        let lift = serde_json::json!({
+            "scie": {
+                "lift": {
+                    "name": "source",
+                    "files": [
+                        {
+                            "name": "direct",
+                            "type": "blob",
+                            "size": direct.len(),
+                            "hash": fingerprint::digest(&direct)
+                        },
+                        {"name": "member", "type": "blob", "hash": fingerprint::digest(&member)},
+                        {
+                            "name": "scie-tote",
+                            "type": "zip",
+                            "size": tote.len(),
+                            "hash": fingerprint::digest(&tote)
+                        }
+                    ],
+                    "boot": {"commands": {"": {"exe": "{direct}"}}}
+                },
+                "jump": {"size": 11, "version": "0.9.0"}
+            }
+        });
+        scie.extend(lift.to_string().as_bytes());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let src = tempdir.path().join("source-scie");
+        std::fs::write(&src, &scie).unwrap();
+        for (name, contents) in [("direct", &direct), ("member", &member)] {
+            let file = File {
+                source: Source::Url(("scie".to_string(), src.display().to_string())),
+                ..testing::blob(name, contents)
+            };
+            let dst = tempdir.path().join(name);
+            Installer::new(&[])
+                .install(&[FileEntry::LoadUrl((file, dst.clone()))])
+                .unwrap();
+            assert_eq!(contents, &std::fs::read(&dst).unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_binding_deadline() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tar = create_tar("member.txt", b"member");
+        let src = tempdir.path().join("archive.tar");
+        std::fs::write(&src, &tar).unwrap();
+
+        let file = File {
+            file_type: FileType::Archive(ArchiveType::Tar),
+            source: Source::LoadBinding("fetch".to_string()),
+            ..testing::blob("archive.tar", &tar)
+        };
+        let dst = tempdir.path().join("dst");
+        let load = |script: &str| {
+            let binding = LoadProcess::new(
+                "fetch",
+                Process {
+                    env: EnvVars { vars: vec![] },
+                    exe: "sh".into(),
+                    args: vec!["-c".into(), script.into(), src.clone().into()],
+                    args_append: vec![],
+                    stdout: None,
+                    stderr: None,
+                    inherit_fds: vec![],
+                },
+                Some(std::time::Duration::from_millis(500)),
+            );
+            Installer::new(&[]).install(&[FileEntry::LoadAndInstall((
+                binding,
+                file.clone(),
+                dst.clone(),
+            ))])
+        };
+
+        // N.B.: The binding emits the whole file before hanging; so it must be killed at its
+        // deadline and the file not installed.
+        let start = std::time::Instant::now();
+        let err = load(r#"cat "$0"; exec sleep 60"#).unwrap_err();
+        assert!(start.elapsed() < std::time::Duration::from_secs(30));
+        assert!(
+            err.contains("The binding fetch timed out after 500ms when loading archive.tar."),
+            "{err}"
+        );
+        assert!(!dst.exists());
+
+        let err = load(r#"cat "$0"; exit 1"#).unwrap_err();
+        assert!(err.contains("Failed to load file"), "{err}");
+        assert!(!dst.exists());
+
+        load(r#"cat "$0""#).unwrap();
+        assert_eq!(
+            "member",
+            std::fs::read_to_string(dst.join("member.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn file_url_source() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tar = create_tar("member.txt", b"member");
+        let src = tempdir.path().join("cache").join("archive.tar");
+        std::fs::create_dir_all(src.parent().unwrap()).unwrap();
+        std::fs::write(&src, &tar).unwrap();
+
+        let file = File {
+            file_type: FileType::Archive(ArchiveType::Tar),
+            source: Source::Url(("file".to_string(), src.display().to_string())),
+            ..testing::blob("archive.tar", &tar)
+        };
+        let dst = tempdir.path().join("dst");
+        // N.B.: Files loaded from a URL take up no room in the payload.
+        Installer::new(&[])
+            .install(&[FileEntry::LoadUrl((file.clone(), dst.clone()))])
+            .unwrap();
+        assert_eq!(
+            "member",
+            std::fs::read_to_string(dst.join("member.txt")).unwrap()
+        );
+
+        std::fs::write(&src, b"tampered").unwrap();
+        let err = Installer::new(&[])
+            .install(&[FileEntry::LoadUrl((file, tempdir.path().join("tampered")))])
+            .unwrap_err();
+        assert!(err.contains("unexpected hash"), "{err}");
+    }
+}
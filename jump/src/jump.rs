@@ -5,11 +5,23 @@ use std::io::{Cursor, Seek, SeekFrom};
 use std::path::Path;
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use semver::Version;
 
 pub const EOF_MAGIC: u32 = 0x534a7219;
+
+/// Marks a scie whose lift manifest is stored zstd compressed at its tail.
+///
+/// The compressed lift manifest is followed by its size in bytes as a little endian u32 and then
+/// this magic, also little endian.
+pub const COMPRESSED_LIFT_MAGIC: u32 = 0x534a7a19;
+
+/// Marks a scie whose lift manifest is stored gzip compressed at its tail.
+///
+/// The layout is the same as for a zstd compressed lift manifest.
+pub const GZIP_COMPRESSED_LIFT_MAGIC: u32 = 0x534a6719;
 pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-use crate::config::Jump;
+use crate::config::{Compression, Jump};
 
 pub fn load(data: &[u8], path: &Path) -> Result<Option<Jump>, String> {
     let mut magic = Cursor::new(&data[data.len() - 8..]);
@@ -44,3 +56,162 @@ pub fn load(data: &[u8], path: &Path) -> Result<Option<Jump>, String> {
     }
     Ok(None)
 }
+
+/// Finds the end of the scie-jump tip of a scie whose lift manifest directly follows the tip.
+///
+/// This is the case for scies with no payload and so no zip to find the end of. Only the last
+/// `maximum_trailer_size` bytes are scanned for the tip's trailing size and EOF magic.
+pub(crate) fn end_of_jump(data: &[u8], maximum_trailer_size: usize) -> Option<usize> {
+    let magic = EOF_MAGIC.to_le_bytes();
+    let start = data.len().saturating_sub(maximum_trailer_size).max(8);
+    (start..=data.len()).rev().find(|end| {
+        data[end - 4..*end] == magic
+            && u32::try_from(*end)
+                .map(|size| data[end - 8..end - 4] == size.to_le_bytes())
+                .unwrap_or(false)
+    })
+}
+
+/// Returns the compressed lift manifest at the tail of the scie `data`, and how it is compressed,
+/// if it has one.
+pub(crate) fn compressed_lift(data: &[u8]) -> Result<Option<(Compression, &[u8])>, String> {
+    if data.len() < 8 {
+        return Ok(None);
+    }
+    let magic = &data[data.len() - 4..];
+    let compression = if magic == COMPRESSED_LIFT_MAGIC.to_le_bytes() {
+        Compression::Zstd
+    } else if magic == GZIP_COMPRESSED_LIFT_MAGIC.to_le_bytes() {
+        Compression::Gzip
+    } else {
+        return Ok(None);
+    };
+    let size = Cursor::new(&data[data.len() - 8..data.len() - 4])
+        .read_u32::<LittleEndian>()
+        .map_err(|e| format!("The compressed lift manifest size is malformed: {e}"))?
+        as usize;
+    let start = (data.len() - 8).checked_sub(size).ok_or_else(|| {
+        format!(
+            "The compressed lift manifest is {size} bytes but the scie is only {scie_size} \
+            bytes.",
+            scie_size = data.len()
+        )
+    })?;
+    Ok(Some((compression, &data[start..data.len() - 8])))
+}
+
+fn parse_version(version: &str) -> Result<Version, String> {
+    Version::parse(version).map_err(|e| format!("Failed to parse scie-jump version {version}: {e}"))
+}
+
+/// Checks a lift manifest's configured scie-jump is compatible with the given scie-jump.
+///
+/// Only versions are compared since the scie-jump size is intrinsic to the scie-jump in use and a
+/// configured jump with no version is always considered compatible. Versions with the same major
+/// version are compatible, but a warning message is returned if they differ otherwise.
+pub fn check_compatible(jump: &Jump, configured_jump: &Jump) -> Result<Option<String>, String> {
+    if configured_jump.version.is_empty() || jump.version == configured_jump.version {
+        return Ok(None);
+    }
+    let version = parse_version(&jump.version)?;
+    let configured_version = parse_version(&configured_jump.version)?;
+    if version == configured_version {
+        Ok(None)
+    } else if version.major == configured_version.major {
+        Ok(Some(format!(
+            "The lift manifest specifies scie-jump {configured_version} but the current \
+            scie-jump is {version}."
+        )))
+    } else {
+        Err(format!(
+            "The lift manifest specifies scie-jump {configured_version} which is not compatible \
+            with the current scie-jump {version}."
+        ))
+    }
+}
+
+/// Checks the given scie-jump version satisfies a lift manifest's minimum scie-jump version.
+pub(crate) fn check_min_version(version: &str, min_version: &str) -> Result<(), String> {
+    let current_version = parse_version(version)?;
+    let required_version = parse_version(min_version)?;
+    if current_version < required_version {
+        return Err(format!(
+            "This scie requires scie-jump {required_version} or newer but the current scie-jump \
+            is {current_version}."
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_compatible, check_min_version, end_of_jump, EOF_MAGIC};
+    use crate::config::Jump;
+
+    fn jump(version: &str, size: usize) -> Jump {
+        Jump {
+            version: version.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn compatible_equal() {
+        assert_eq!(
+            None,
+            check_compatible(&jump("0.9.0", 42), &jump("0.9.0", 42)).unwrap()
+        );
+        assert_eq!(
+            None,
+            check_compatible(&jump("0.9.0", 42), &jump("0.9.0", 1137)).unwrap()
+        );
+        assert_eq!(
+            None,
+            check_compatible(&jump("0.9.0", 42), &jump("", 1137)).unwrap()
+        );
+    }
+
+    #[test]
+    fn compatible_minor() {
+        assert!(check_compatible(&jump("0.10.0", 42), &jump("0.9.0", 1137))
+            .unwrap()
+            .is_some());
+        assert!(check_compatible(&jump("1.2.3", 42), &jump("1.3.0", 42))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn incompatible_major() {
+        assert!(check_compatible(&jump("1.0.0", 42), &jump("0.9.0", 42)).is_err());
+        assert!(check_compatible(&jump("1.0.0", 42), &jump("2.0.0", 42)).is_err());
+        assert!(check_compatible(&jump("1.0.0", 42), &jump("bad", 42)).is_err());
+    }
+
+    #[test]
+    fn min_version_satisfied() {
+        assert!(check_min_version("0.9.0", "0.9.0").is_ok());
+        assert!(check_min_version("0.10.0", "0.9.1").is_ok());
+        assert!(check_min_version("1.0.0", "0.9.0").is_ok());
+    }
+
+    #[test]
+    fn min_version_violated() {
+        assert!(check_min_version("0.9.0", "0.9.1").is_err());
+        assert!(check_min_version("0.9.0", "1.0.0").is_err());
+        assert!(check_min_version("0.9.0", "bad").is_err());
+    }
+
+    #[test]
+    fn end_of_jump_tip() {
+        let mut data = vec![0; 24];
+        data.extend(32_u32.to_le_bytes());
+        data.extend(EOF_MAGIC.to_le_bytes());
+        assert_eq!(Some(32), end_of_jump(&data, 1024));
+
+        data.extend(b"\n{\"scie\": {}}\n");
+        assert_eq!(Some(32), end_of_jump(&data, 1024));
+        assert_eq!(None, end_of_jump(&data, 8));
+        assert_eq!(None, end_of_jump(&data[8..], 1024));
+    }
+}
@@ -12,35 +12,47 @@ mod atomic;
 mod comparable_regex;
 pub mod config;
 mod context;
+mod diff;
+pub mod exit_code;
 pub mod fingerprint;
 mod installer;
 mod jump;
 mod lift;
+mod lzw;
 mod placeholders;
 mod process;
+#[cfg(test)]
+mod testing;
+mod toml_env;
 mod zip;
 
+use std::collections::HashMap;
 use std::env;
 use std::env::current_exe;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 use log::Level;
 use logging_timer::{time, timer};
 
-pub use crate::archive::create_options;
-use crate::config::Config;
+pub use crate::archive::{create_options, source_date_epoch};
 pub use crate::config::Jump;
+use crate::config::{Cmd, Config, EnvFileFormat};
+pub use crate::diff::{diff_scie, Change, Diff};
 use crate::installer::Installer;
+pub use crate::installer::{Decompressor, Decompressors};
+pub use crate::jump::check_compatible;
 // Exposed for the package crate post-processing of the scie-jump binary.
-pub use crate::jump::EOF_MAGIC;
-pub use crate::lift::{load_lift, File, Lift, ScieBoot, Source};
-pub use crate::process::{execute, EnvVar, EnvVars, Process};
-pub use crate::zip::check_is_zip;
+pub use crate::jump::{COMPRESSED_LIFT_MAGIC, EOF_MAGIC, GZIP_COMPRESSED_LIFT_MAGIC};
+pub use crate::lift::{load_lift, load_lift_data, File, Lift, LoadOptions, ScieBoot, Source};
+pub use crate::process::{execute, open_output, EnvVar, EnvVars, Process};
+pub use crate::zip::{check_is_zip, zip_payload_bounds};
 
 pub struct SelectBoot {
     pub boots: Vec<ScieBoot>,
     pub description: Option<String>,
     pub error_message: Option<String>,
+    pub exit_code: i32,
 }
 
 const HELP: &str = "\
@@ -49,6 +61,19 @@ For SCIE=<boot_command> you can select from the following:
 boot-pack
     (-sj|--jump|--scie-jump [PATH])
     (-1|--single-lift-line|--no-single-lift-line)
+    (--no-tote)
+    (--tote-compression [stored|deflate])
+    (--store-dirs-as [zip|tar])
+    (--auto-compress)
+    (--compress-manifest)
+    (--manifest-compression [zstd|gzip])
+    (--dev)
+    (--verify)
+    (--no-fingerprint-cache)
+    (--require-jump-version [VERSION])
+    (--resolve-base [DIR])
+    (--output [PATH])
+    (--output-hash-file [PATH])
     [lift manifest]*
 
     Pack the given lift manifests into scie executables. If no manifests
@@ -57,29 +82,140 @@ boot-pack
     alternate scie-jump binary can be specified using --path. By default
     the lift manifest is appended to the tail of the scie as a single
     line JSON document, but can be made a multi-line pretty-printed JSON
-    document by passing --no-single-lift-line.
+    document by passing --no-single-lift-line. If the last file is not a
+    zip, the files are stored in a scie-tote zip unless --no-tote is
+    passed, in which case they are appended raw. The scie-tote entries
+    are stored uncompressed unless --tote-compression deflate is passed.
+    With --auto-compress, each uncompressed tar is compressed with both
+    zstd and xz and the smallest result is stored in its place. With
+    --compress-manifest, the lift manifest is stored zstd compressed,
+    which no longer allows reading it with `tail -1`. When
+    --require-jump-version is given, packing fails unless the scie tip
+    has exactly that version. With --dev (or SCIE_DEV=1), files without
+    a hash are not hashed and are not verified at boot; so dev scies
+    should never be distributed. With --verify, each scie is loaded back
+    after packing and its payload checked against its lift manifest. A
+    lift manifest of `-` is read from stdin and its files are found
+    relative to the current directory or else the directory given by
+    --resolve-base. Scies are written to the current directory unless
+    --output is given. With a single lift manifest, --output names the
+    scie unless it is an existing directory; otherwise it names the
+    directory to write each scie to.
 
-help: Display this help message.
+    With --output-hash-file, a JSON list recording the path, size,
+    sha256 hash and source lift manifest of each scie built is also
+    written to the given path.
 
-inspect: Pretty-print this scie's lift manifest to stdout.
+    When the last file is not a zip, --no-tote also requires
+    --compress-manifest or --manifest-compression; so the scie can still
+    find its lift manifest.
 
-install (-s|--symlink) [dest dir]*
+    Directories are stored as zips unless --store-dirs-as tar is passed,
+    in which case they are stored as tars that preserve their exact file
+    modes and symlinks.
+
+    Archive entries are stored in sorted order and, when
+    SOURCE_DATE_EPOCH is set, are stamped with that time and no owner;
+    so that packing the same inputs produces the same scie.
+
+    The hashes of files are cached by path, modification time and size
+    unless --no-fingerprint-cache is passed.
+
+    With --manifest-compression gzip, the lift manifest is stored gzip
+    compressed instead; --compress-manifest is the same as
+    --manifest-compression zstd.
+
+completions [bash|zsh|fish]
+
+    Print a shell completion script that completes the names of the
+    commands contained in this scie when given as the 1st argument.
+
+env [command]?
+
+    Print the env vars the selected command would be run with, without
+    running it. When a command name is given as the 1st argument, that
+    command is selected; otherwise the command is selected as it would be
+    without SCIE=env set. The command's bindings are not run. Each env var
+    set is printed as `NAME=VALUE` and each env var removed, including
+    those removed by a regex, as `unset NAME`.
+
+gc
+    (--older-than [duration])
+    (--base-gc-keep [count])
+
+    Remove the directories in the nce cache base this scie uses, which it
+    may share with other scies, whose most recently modified file is
+    older than the given duration. A duration is a whole number followed
+    by one of s, m, h, d or w; e.g.: 12h or 7d. With --base-gc-keep, only
+    the given count of most recently accessed directories are kept, by
+    the access times of their files. When both options are given, the
+    directories either selects are removed. Directories a scie is
+    extracting into are left in place.
+
+help [command]?
+
+    Display this help message. When a command name is given, either as
+    the 1st argument or via SCIE_HELP_CMD, just display that command's
+    description and its exe and args as written in the lift manifest.
+
+inspect (--file [name or key])? (--diff [other scie])? (--self-hash)
+
+    Pretty-print this scie's lift manifest to stdout. When --file is
+    given, just print the matching file entry. When --diff is given,
+    print the files, commands and env entries that were added (+),
+    removed (-) or changed (~) relative to the other scie instead. When
+    --self-hash is given, a trailing `# sha256: <hex>` line with the hash
+    of this whole scie is printed after the lift manifest.
+
+install (-s|--symlink) (-n|--dry-run) (--verify-only) [dest dir]*
 
     Install all the commands in this scie to each dest dir given. If no
-    dest dirs are given, installs them in the current directory.
+    dest dirs are given, installs them in the current directory. The
+    commands are reflinked where supported or else hard linked by
+    default, falling back to copies. Set SCIE_INSTALL_MODE to one of
+    hardlink, symlink or copy to change the default; copy never reflinks
+    or hard links and an explicit --symlink always wins. With --dry-run,
+    just print the operations that would be tried for each command. With
+    --verify-only, nothing is installed; instead each command is
+    reported as OK, MISSING or STALE depending on whether it already
+    links to or is a copy of this scie, and the exit code is non-zero
+    unless all are OK.
+
+list (--with-exe) (--with-description)
+
+    List the names of the commands contained in this scie. With
+    --with-exe, each name is followed by a tab and the command's exe as
+    written in the lift manifest. With --with-description, a tab and the
+    command's description follow.
 
-list: List the names of the commands contained in this scie.
+run [command] (-- [arg]*)
 
-split [directory]?
+    Run the named command, forwarding any args given after `--` to it.
+    This selects the command just like SCIE_BOOT=[command] would.
+
+split (--flat) (--gzip-tote) (--stdout [name] (--raw)) [directory]?
 
     Split this scie into its component files in the given directory or
-    else the current directory if no argument is given.
+    else the current directory if no argument is given. If the scie has
+    a scie-tote and --gzip-tote is passed, the scie-tote files are also
+    re-packed into a single `tote.tar.gz` in the directory. With --flat,
+    files are split directly into the directory by their basenames and
+    it is an error for two files to share a basename. With --stdout,
+    just the bytes of the named file are written to stdout and no files
+    are created. Archives, directories and compressed blobs are refused
+    unless --raw is also passed, in which case their stored bytes are
+    written.
 ";
 
 pub enum BootAction {
-    Execute((Process, bool)),
+    Completions((PathBuf, Vec<ScieBoot>)),
+    Env(Process),
+    /// The process to execute and the number of leading argv entries the scie consumed.
+    Execute((Process, usize)),
+    /// The nce cache base to collect garbage in.
+    Gc(PathBuf),
     Help((String, i32)),
-    Inspect((Jump, Lift)),
+    Inspect((Jump, Lift, PathBuf)),
     Install((PathBuf, Vec<ScieBoot>)),
     List(Vec<ScieBoot>),
     Pack((Jump, PathBuf)),
@@ -97,6 +233,40 @@ struct CurrentExe {
     invoked_as: PathBuf,
 }
 
+#[cfg(unix)]
+fn write_access(path: &Path) -> Result<(), &'static str> {
+    use nix::errno::Errno;
+    use nix::unistd::{access, AccessFlags};
+
+    match access(path, AccessFlags::W_OK) {
+        Err(Errno::EROFS) => Err("is on a read-only filesystem"),
+        Err(Errno::EACCES) => Err("is not writable by the current user"),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn write_access(path: &Path) -> Result<(), &'static str> {
+    match path.metadata() {
+        Ok(metadata) if metadata.permissions().readonly() => Err("is read-only"),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the file at `path` can be modified in place before any attempt is made to do so.
+///
+/// Files on read-only filesystems or that are otherwise not writable are reported with an error
+/// suggesting a writable copy be used instead.
+pub fn check_writable(path: &Path) -> Result<(), String> {
+    write_access(path).map_err(|reason| {
+        format!(
+            "The file at {path} {reason}; so it cannot be modified in place. Copy it to a writable \
+            location first and try again with the copy.",
+            path = path.display()
+        )
+    })
+}
+
 fn find_current_exe() -> Result<CurrentExe, String> {
     let exe =
         current_exe().map_err(|e| format!("Failed to find path of the current executable: {e}"))?;
@@ -108,41 +278,455 @@ fn find_current_exe() -> Result<CurrentExe, String> {
     Ok(CurrentExe { exe, invoked_as })
 }
 
-#[time("debug", "jump::{}")]
-pub fn prepare_boot() -> Result<BootAction, String> {
-    let current_exe = find_current_exe()?;
-    let file = std::fs::File::open(&current_exe.exe).map_err(|e| {
+fn dotenv_files(lift: &Lift) -> Vec<PathBuf> {
+    if let Some(files) = env::var_os("SCIE_DOTENV_FILES") {
+        env::split_paths(&files).collect()
+    } else if !lift.dotenv_files.is_empty() {
+        lift.dotenv_files.iter().map(PathBuf::from).collect()
+    } else {
+        vec![PathBuf::from(".env")]
+    }
+}
+
+// N.B.: Env vars that are already set are never overwritten; so the earliest file to set a given
+// env var wins.
+fn load_dotenv_files(files: &[PathBuf]) {
+    for file in files {
+        let loaded = match EnvFileFormat::from_path(file) {
+            Some(format) if file.is_file() => context::load_env_file(file, format).map(|env| {
+                for (name, value) in env {
+                    if env::var_os(&name).is_none() {
+                        env::set_var(name, value);
+                    }
+                }
+                file.clone()
+            }),
+            _ => dotenv::from_filename(file).map_err(|e| e.to_string()),
+        };
+        match loaded {
+            Ok(dotenv_file) => {
+                debug!("Loaded env file from {path}", path = dotenv_file.display())
+            }
+            Err(e) => debug!("Did not load env file {path}: {e}", path = file.display()),
+        }
+    }
+}
+
+fn maybe_load_dotenv(lift: &Lift, cmd: Option<&Cmd>) {
+    let load_dotenv = cmd
+        .and_then(|cmd| cmd.load_dotenv)
+        .unwrap_or(lift.load_dotenv || !lift.dotenv_files.is_empty());
+    if load_dotenv {
+        let _timer = timer!(Level::Debug; "jump::load_dotenv");
+        load_dotenv_files(&dotenv_files(lift));
+    }
+}
+
+/// Creates the installer for the files in `payload`, honoring the `SCIE_*` env vars that control
+/// installation.
+fn installer<'a>(payload: &'a [u8], lift: &Lift) -> Result<Installer<'a>, String> {
+    let max_extract_size = env::var("SCIE_MAX_EXTRACT_BYTES")
+        .ok()
+        .map(|value| {
+            value.parse::<u64>().map_err(|e| {
+                format!("Failed to parse SCIE_MAX_EXTRACT_BYTES of {value} as a byte count: {e}")
+            })
+        })
+        .transpose()?;
+    match env::var_os("SCIE_LOOSE_FILES") {
+        Some(directory) => Installer::from_directory(PathBuf::from(directory)),
+        None => Installer::new(payload),
+    }
+    .audit_log(env::var_os("SCIE_AUDIT_LOG").map(PathBuf::from))
+    .max_extract_size(max_extract_size)
+    .dev(lift.dev)
+    .zstd_dictionary(lift)
+}
+
+/// Sets the process umask to any `SCIE_CACHE_UMASK` so that everything the scie creates, from the
+/// files it extracts to its locks and binding outputs, has those mode bits removed.
+///
+/// Returns the mode bits removed; so they can also be removed from the modes set explicitly.
+fn apply_cache_umask() -> Result<u32, String> {
+    let Ok(value) = env::var("SCIE_CACHE_UMASK") else {
+        return Ok(0);
+    };
+    let umask = u32::from_str_radix(&value, 8)
+        .ok()
+        .filter(|umask| *umask <= 0o777)
+        .ok_or_else(|| format!("Failed to parse SCIE_CACHE_UMASK of {value} as an octal umask."))?;
+    set_umask(umask);
+    Ok(umask)
+}
+
+#[cfg(unix)]
+fn set_umask(umask: u32) {
+    use nix::sys::stat::Mode;
+    nix::sys::stat::umask(Mode::from_bits_truncate(umask as nix::libc::mode_t));
+}
+
+#[cfg(not(unix))]
+fn set_umask(umask: u32) {
+    debug!(
+        "Ignoring SCIE_CACHE_UMASK of {umask:03o} since file modes are not supported on this \
+        platform."
+    );
+}
+
+fn command_help(lift: &Lift, name: &str) -> (String, i32) {
+    let key = if name == lift.name && lift.boot.commands.contains_key("") {
+        ""
+    } else {
+        name
+    };
+    if let Some(cmd) = lift.boot.commands.get(key) {
+        let mut message = format!("{name}\n");
+        if let Some(description) = cmd.description.as_ref() {
+            message.push_str(&format!("    {description}\n"));
+        }
+        message.push_str(&format!("exe: {exe}\n", exe = cmd.exe));
+        if !cmd.args.is_empty() {
+            message.push_str(&format!("args: {args}\n", args = cmd.args.join(" ")));
+        }
+        if !cmd.args_append.is_empty() {
+            message.push_str(&format!(
+                "args_append: {args}\n",
+                args = cmd.args_append.join(" ")
+            ));
+        }
+        (message, 0)
+    } else {
+        let names = lift
+            .boots()
+            .into_iter()
+            .map(|boot| format!("    {name}", name = boot.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        (
+            format!(
+                "There is no command named {name:?} in this scie. The available commands are:\n\
+                {names}\n"
+            ),
+            exit_code::SELECTION_ERROR,
+        )
+    }
+}
+
+// Loads the scie's lift manifest, merging in any commands from its commands file.
+fn load_scie(scie: &Path, data: &[u8]) -> Result<(Jump, Lift), String> {
+    let (jump, mut lift) = lift::load_scie(scie, data)?;
+    if let Some(commands_file) = lift.commands_file.clone() {
+        let payload = &data[jump.size..data.len() - lift.size];
+        let commands = installer::scie_file(payload, &lift, &commands_file, "commands file")?;
+        lift::merge_commands(&mut lift, commands)?;
+    }
+    Ok((jump, lift))
+}
+
+/// Loads the scie at `scie_path` back and checks it is self-consistent.
+///
+/// The lift manifest must parse and validate, the files stored directly in the scie must exactly
+/// fill its payload and each must match its recorded hash. The files stored in a scie-tote are
+/// covered by the hash of the scie-tote itself.
+pub fn verify_scie(scie_path: &Path) -> Result<(), String> {
+    let data = std::fs::read(scie_path).map_err(|e| {
+        format!(
+            "Failed to read the scie at {path} to verify it: {e}",
+            path = scie_path.display()
+        )
+    })?;
+    let (jump, lift) = load_scie(scie_path, &data)?;
+    config(jump.clone(), lift.clone())
+        .validate()
+        .map_err(|errors| {
+            format!(
+                "The lift manifest of the scie at {path} is invalid:\n{errors}",
+                path = scie_path.display(),
+                errors = errors
+                    .iter()
+                    .map(|error| format!("+ {error}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        })?;
+    let payload = &data[jump.size..data.len() - lift.size];
+    let files_size: usize = lift
+        .files
+        .iter()
+        .filter(|file| Source::Scie == file.source)
+        .map(|file| file.size)
+        .sum();
+    if files_size != payload.len() {
+        return Err(format!(
+            "The scie at {path} has a {payload_size} byte payload but its lift manifest lists \
+            {files_size} bytes of files stored in the scie.",
+            path = scie_path.display(),
+            payload_size = payload.len()
+        ));
+    }
+    let mut location = 0;
+    for file in lift.files.iter().filter(|file| Source::Scie == file.source) {
+        let bytes = &payload[location..location + file.size];
+        location += file.size;
+        if file.size == 0 || (lift.dev && file.hash.is_empty()) {
+            continue;
+        }
+        let hash = fingerprint::digest(bytes);
+        if file.hash != hash {
+            return Err(format!(
+                "The file {name} in the scie at {path} has hash {hash} but its lift manifest \
+                records {expected}.",
+                name = file.name,
+                path = scie_path.display(),
+                expected = file.hash
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Selects and reifies the named command of the scie at `scie_path` without installing anything.
+///
+/// If no command name is given, the default command is selected. Placeholders for files resolve to
+/// the paths the files would be installed at and no boot bindings or command stages are run.
+pub fn dry_select_command(scie_path: &Path, command: Option<&str>) -> Result<Process, String> {
+    prepare_command(scie_path, command, None, Decompressors::new(), true)
+}
+
+/// Selects and reifies the named command of the scie at `scie_path`, installing the files it needs.
+///
+/// If no command name is given, the default command is selected. If no `env` is given, the command
+/// is reified against the current process environment, after loading any dotenv files just as a
+/// boot would.
+pub fn resolve_command(
+    scie_path: &Path,
+    command: Option<&str>,
+    env: Option<HashMap<String, String>>,
+) -> Result<Process, String> {
+    prepare_command(scie_path, command, env, Decompressors::new(), false)
+}
+
+/// Like [`resolve_command`], but tar archives whose "decompressor" names one of the given
+/// `decompressors` are decompressed with it before they are unpacked.
+pub fn resolve_command_with_decompressors(
+    scie_path: &Path,
+    command: Option<&str>,
+    env: Option<HashMap<String, String>>,
+    decompressors: Decompressors,
+) -> Result<Process, String> {
+    prepare_command(scie_path, command, env, decompressors, false)
+}
+
+fn prepare_command(
+    scie_path: &Path,
+    command: Option<&str>,
+    env: Option<HashMap<String, String>>,
+    decompressors: Decompressors,
+    dry_run: bool,
+) -> Result<Process, String> {
+    let data = std::fs::read(scie_path).map_err(|e| {
         format!(
-            "Failed to open current exe at {exe} for reading: {e}",
-            exe = current_exe.exe.display(),
+            "Failed to read the scie at {path}: {e}",
+            path = scie_path.display()
         )
     })?;
+    let (jump, lift) = load_scie(scie_path, &data)?;
+    let payload = &data[jump.size..data.len() - lift.size];
+    let name = command.unwrap_or("");
+    if !dry_run && env.is_none() {
+        maybe_load_dotenv(&lift, lift.boot.commands.get(name));
+    }
+    let installer = installer(payload, &lift)?
+        .decompressors(decompressors)
+        .dry_run(dry_run);
+    let ambient_env = env.map(|env| {
+        env.into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect()
+    });
+    let selected_command =
+        context::select_named_command(scie_path, &jump, &lift, &installer, name, ambient_env)?
+            .ok_or_else(|| {
+                format!(
+                    "The scie at {path} has no command named {name:?}.",
+                    path = scie_path.display()
+                )
+            })?;
+    if !dry_run {
+        installer.install(&selected_command.files)?;
+        if let Some(exe_member) = selected_command.exe_member.as_ref() {
+            exe_member.check()?;
+        }
+    }
+    Ok(selected_command.process)
+}
+
+/// Parses the argv of a `SCIE=run <command> [-- args...]` boot into the command name and the number
+/// of leading argv entries that are not forwarded to the command.
+fn parse_run_args(args: &[String]) -> Result<(&str, usize), String> {
+    match args {
+        [_, name] => Ok((name.as_str(), 2)),
+        [_, name, separator, ..] if separator == "--" => Ok((name.as_str(), 3)),
+        [_, name, rest @ ..] => Err(format!(
+            "The args to forward to {name} must follow a `--`; e.g.: SCIE=run {name} -- {args}",
+            args = rest.join(" ")
+        )),
+        _ => Err(
+            "SCIE=run requires the name of the command to run; e.g.: SCIE=run <command> -- \
+            [args...]"
+                .to_string(),
+        ),
+    }
+}
+
+fn append_extra_args(process: &mut Process, extra_args: Vec<String>) {
+    // N.B.: The extra args follow the command's own args and precede the user's args.
+    process
+        .args
+        .extend(extra_args.into_iter().map(OsString::from));
+}
+
+/// Parses the argv of a `SCIE=env [command]?` boot into the name of the command to select, if any.
+fn parse_env_args(args: &[String]) -> Result<Option<&str>, String> {
+    match args {
+        [_] | [] => Ok(None),
+        [_, name] => Ok(Some(name.as_str())),
+        [_, name, rest @ ..] => Err(format!(
+            "SCIE=env accepts at most one command name but was given {name} {args}",
+            args = rest.join(" ")
+        )),
+    }
+}
+
+/// The number of leading argv entries that are not passed through to the selected command.
+fn argv_skip(run: Option<(&str, usize)>, argv1_consumed: bool) -> usize {
+    match run {
+        Some((_, argv_skip)) => argv_skip,
+        None if argv1_consumed => 2,
+        None => 1,
+    }
+}
+
+/// Splits a string into args like a shell would, minus any expansions.
+///
+/// Args are separated by whitespace. Single quotes preserve everything they enclose and double
+/// quotes do too, save for backslash escaped `"` and `\\`. Outside of quotes, a backslash escapes
+/// the character that follows it.
+fn split_args(value: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(char) => arg.push(char),
+                        None => return Err("Found an unterminated single quote.".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(char @ ('"' | '\\')) => arg.push(char),
+                            Some(char) => {
+                                arg.push('\\');
+                                arg.push(char);
+                            }
+                            None => return Err("Found an unterminated double quote.".to_string()),
+                        },
+                        Some(char) => arg.push(char),
+                        None => return Err("Found an unterminated double quote.".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(char) => arg.get_or_insert_with(String::new).push(char),
+                None => {
+                    return Err("Found a trailing backslash with nothing to escape.".to_string())
+                }
+            },
+            char if char.is_whitespace() => args.extend(arg.take()),
+            char => arg.get_or_insert_with(String::new).push(char),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Prepares the action this scie should take.
+///
+/// Failures are returned with the [`exit_code`] they should be reported with.
+#[time("debug", "jump::{}")]
+pub fn prepare_boot() -> Result<BootAction, (String, i32)> {
+    let config_error = |e| (e, exit_code::CONFIG_ERROR);
+    let extraction_error = |e| (e, exit_code::EXTRACTION_ERROR);
+
+    let umask = apply_cache_umask().map_err(config_error)?;
+    let current_exe = find_current_exe().map_err(config_error)?;
+    let file = std::fs::File::open(&current_exe.exe)
+        .map_err(|e| {
+            format!(
+                "Failed to open current exe at {exe} for reading: {e}",
+                exe = current_exe.exe.display(),
+            )
+        })
+        .map_err(config_error)?;
     let data = unsafe {
         memmap::Mmap::map(&file)
-            .map_err(|e| format!("Failed to mmap {exe}: {e}", exe = current_exe.exe.display()))?
+            .map_err(|e| format!("Failed to mmap {exe}: {e}", exe = current_exe.exe.display()))
+            .map_err(config_error)?
     };
 
-    if let Some(jump) = jump::load(&data, &current_exe.exe)? {
+    if let Some(jump) = jump::load(&data, &current_exe.exe).map_err(config_error)? {
         return Ok(BootAction::Pack((jump, current_exe.exe)));
     }
 
-    let (jump, lift) = lift::load_scie(&current_exe.exe, &data)?;
+    let (jump, lift) = load_scie(&current_exe.exe, &data).map_err(config_error)?;
+    let payload = &data[jump.size..data.len() - lift.size];
     trace!(
         "Loaded lift manifest from {current_exe}:\n{lift:#?}",
         current_exe = current_exe.exe.display()
     );
 
+    let args = env::args().collect::<Vec<_>>();
+    let mut run = None;
+    let mut print_env = false;
     if let Some(value) = env::var_os("SCIE") {
         if "boot-pack" == value {
             return Ok(BootAction::Pack((jump, current_exe.exe)));
+        } else if "completions" == value {
+            return Ok(BootAction::Completions((current_exe.exe, lift.boots())));
+        } else if "env" == value {
+            run = parse_env_args(&args)
+                .map_err(|e| (e, exit_code::SELECTION_ERROR))?
+                .map(|name| (name, 2));
+            print_env = true;
+        } else if "gc" == value {
+            let base = context::base(&current_exe, &lift).map_err(config_error)?;
+            return Ok(BootAction::Gc(base));
         } else if "help" == value {
+            let command = env::var("SCIE_HELP_CMD")
+                .ok()
+                .or_else(|| env::args().nth(1));
+            if let Some(command) = command {
+                return Ok(BootAction::Help(command_help(&lift, &command)));
+            }
             return Ok(BootAction::Help((format!("{HELP}\n"), 0)));
         } else if "inspect" == value {
-            return Ok(BootAction::Inspect((jump, lift)));
+            return Ok(BootAction::Inspect((jump, lift, current_exe.exe)));
         } else if "install" == value {
             return Ok(BootAction::Install((current_exe.exe, lift.boots())));
         } else if "list" == value {
             return Ok(BootAction::List(lift.boots()));
+        } else if "run" == value {
+            run = Some(parse_run_args(&args).map_err(|e| (e, exit_code::SELECTION_ERROR))?);
         } else if "split" == value {
             return Ok(BootAction::Split((jump, lift, current_exe.exe)));
         } else if !PathBuf::from(&value).exists() {
@@ -153,33 +737,474 @@ pub fn prepare_boot() -> Result<BootAction, String> {
                 {HELP}\
                 "
             );
-            return Ok(BootAction::Help((help_message, 1)));
+            return Ok(BootAction::Help((help_message, exit_code::SELECTION_ERROR)));
         }
     }
 
-    if lift.load_dotenv {
-        let _timer = timer!(Level::Debug; "jump::load_dotenv");
-        if let Ok(dotenv_file) = dotenv::dotenv() {
-            debug!("Loaded env file from {path}", path = dotenv_file.display());
+    if let Some(min_jump_version) = lift.min_jump_version.as_ref() {
+        if let Err(e) = jump::check_min_version(jump::VERSION, min_jump_version) {
+            return Ok(BootAction::Help((
+                format!("{e}\n"),
+                exit_code::CONFIG_ERROR,
+            )));
         }
     }
-    let payload = &data[jump.size..data.len() - lift.size];
-    let installer = Installer::new(payload);
-    let result = context::select_command(&current_exe, &jump, &lift, &installer);
+
+    let run_name = run.map(|(name, _)| name);
+    maybe_load_dotenv(
+        &lift,
+        context::peek_command(
+            &current_exe,
+            &lift,
+            run_name,
+            &env::vars_os().collect(),
+            env::args().nth(1).as_deref(),
+        ),
+    );
+    let extra_args = env::var("SCIE_EXTRA_ARGS")
+        .ok()
+        .map(|value| {
+            split_args(&value)
+                .map_err(|e| format!("Failed to parse SCIE_EXTRA_ARGS of {value:?}: {e}"))
+        })
+        .transpose()
+        .map_err(config_error)?
+        .unwrap_or_default();
+    // N.B.: The extra args are for this scie alone; so we keep a scie it runs from applying them
+    // again.
+    env::remove_var("SCIE_EXTRA_ARGS");
+    // N.B.: SCIE=env only reports on the selected command; so its bindings and stages do not run.
+    let installer = installer(payload, &lift)
+        .map_err(config_error)?
+        .umask(umask)
+        .dry_run(print_env);
+    let result = context::select_command(&current_exe, &jump, &lift, &installer, run_name);
     if let Ok(Some(selected_command)) = result {
-        installer.install(&selected_command.files)?;
-        let process = selected_command.process;
+        if print_env {
+            env::set_var("SCIE", current_exe.exe.as_os_str());
+            return Ok(BootAction::Env(selected_command.process));
+        }
+        installer
+            .install(&selected_command.files)
+            .map_err(extraction_error)?;
+        if let Some(exe_member) = selected_command.exe_member.as_ref() {
+            exe_member.check().map_err(extraction_error)?;
+        }
+        let mut process = selected_command.process;
+        append_extra_args(&mut process, extra_args);
         trace!("Prepared {process:#?}");
         env::set_var("SCIE", current_exe.exe.as_os_str());
-        Ok(BootAction::Execute((
-            process,
-            selected_command.argv1_consumed,
-        )))
+        let argv_skip = argv_skip(run, selected_command.argv1_consumed);
+        Ok(BootAction::Execute((process, argv_skip)))
     } else {
+        let (error_message, exit_code) = match result {
+            Err((message, exit_code)) => (Some(message), exit_code),
+            _ => (
+                run_name.map(|name| format!("There is no command named {name:?} to run.")),
+                exit_code::SELECTION_ERROR,
+            ),
+        };
         Ok(BootAction::Select(SelectBoot {
             boots: lift.boots(),
             description: lift.description,
-            error_message: result.err(),
+            error_message,
+            exit_code,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Write;
+
+    use super::{
+        append_extra_args, argv_skip, check_writable, command_help, load_dotenv_files, load_scie,
+        parse_env_args, parse_run_args, split_args, verify_scie,
+    };
+    use crate::config::{Boot, Cmd};
+    use crate::installer::Installer;
+    use crate::{
+        context, fingerprint, testing, EnvVars, Lift, Process, COMPRESSED_LIFT_MAGIC, EOF_MAGIC,
+        GZIP_COMPRESSED_LIFT_MAGIC,
+    };
+
+    #[test]
+    fn dotenv_files_precedence() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let local = tempdir.path().join(".env.local");
+        std::fs::write(&local, "__DOTENV_A__=local\n").unwrap();
+        let base = tempdir.path().join(".env");
+        std::fs::write(&base, "__DOTENV_A__=base\n__DOTENV_B__=base\n").unwrap();
+
+        assert!(std::env::var_os("__DOTENV_A__").is_none());
+        assert!(std::env::var_os("__DOTENV_B__").is_none());
+        load_dotenv_files(&[local, tempdir.path().join(".env.dne"), base]);
+        assert_eq!("local", std::env::var("__DOTENV_A__").unwrap());
+        assert_eq!("base", std::env::var("__DOTENV_B__").unwrap());
+        std::env::remove_var("__DOTENV_A__");
+        std::env::remove_var("__DOTENV_B__");
+    }
+
+    #[test]
+    fn help_for_command() {
+        let cmd = |exe: &str, description: Option<&str>| Cmd {
+            exe: exe.to_string(),
+            args: vec!["{scie.bindings.configure}".to_string(), "-v".to_string()],
+            description: description.map(str::to_string),
+            ..Default::default()
+        };
+        let lift = Lift {
+            name: "app".to_string(),
+            boot: Boot {
+                commands: vec![
+                    ("".to_string(), cmd("{python}", None)),
+                    (
+                        "migrate".to_string(),
+                        cmd("{migrate}", Some("Migrate the database.")),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+                bindings: BTreeMap::new(),
+            },
+            ..testing::lift()
+        };
+
+        let (message, exit_code) = command_help(&lift, "migrate");
+        assert_eq!(0, exit_code);
+        assert_eq!(
+            "migrate\n    Migrate the database.\nexe: {migrate}\nargs: {scie.bindings.configure} -v\n",
+            message
+        );
+
+        let (message, exit_code) = command_help(&lift, "app");
+        assert_eq!(0, exit_code);
+        assert!(message.contains("exe: {python}"), "{message}");
+
+        let (message, exit_code) = command_help(&lift, "dne");
+        assert_eq!(crate::exit_code::SELECTION_ERROR, exit_code);
+        assert!(message.contains("    app\n    migrate\n"), "{message}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn payload_less() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut data = vec![0; 56];
+        data.extend(64_u32.to_le_bytes());
+        data.extend(EOF_MAGIC.to_le_bytes());
+        let manifest = serde_json::json!({
+            "scie": {
+                "lift": {
+                    "name": "manifest-only",
+                    "base": tempdir.path(),
+                    "files": [
+                        {
+                            "name": "data.txt",
+                            "size": 12,
+                            "hash": fingerprint::digest(b"payload-less"),
+                            "type": "blob",
+                            "source": "fetch"
+                        }
+                    ],
+                    "boot": {
+                        "commands": {
+                            "": {
+                                "exe": "cat",
+                                "args": ["{data.txt}"]
+                            }
+                        },
+                        "bindings": {
+                            "fetch": {
+                                "exe": "sh",
+                                "args": ["-c", "printf payload-less"]
+                            }
+                        }
+                    }
+                },
+                "jump": {
+                    "size": 64,
+                    "version": "0.9.0"
+                }
+            }
+        });
+        data.push(b'\n');
+        data.extend(serde_json::to_vec(&manifest).unwrap());
+
+        let scie = tempdir.path().join("scie");
+        std::fs::write(&scie, &data).unwrap();
+        let (jump, lift) = load_scie(&scie, &data).unwrap();
+        assert_eq!(64, jump.size);
+        let payload = &data[jump.size..data.len() - lift.size];
+        assert!(payload.is_empty());
+
+        let installer = Installer::new(payload);
+        let selected_command =
+            context::select_named_command(&scie, &jump, &lift, &installer, "", None)
+                .unwrap()
+                .unwrap();
+        installer.install(&selected_command.files).unwrap();
+        assert_eq!(
+            "payload-less",
+            std::fs::read_to_string(&selected_command.process.args[0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn compressed_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut data = vec![0; 56];
+        data.extend(64_u32.to_le_bytes());
+        data.extend(EOF_MAGIC.to_le_bytes());
+        let manifest = serde_json::json!({
+            "scie": {
+                "lift": {
+                    "name": "compressed",
+                    "files": [
+                        {
+                            "name": "data.txt",
+                            "size": 4,
+                            "hash": fingerprint::digest(b"data"),
+                            "type": "blob",
+                            "source": "fetch"
+                        }
+                    ],
+                    "boot": {
+                        "commands": {
+                            "": {
+                                "exe": "cat",
+                                "args": ["{data.txt}"]
+                            }
+                        },
+                        "bindings": {
+                            "fetch": {
+                                "exe": "sh",
+                                "args": ["-c", "printf data"]
+                            }
+                        }
+                    }
+                },
+                "jump": {
+                    "size": 64,
+                    "version": "0.9.0"
+                }
+            }
+        });
+        let manifest = serde_json::to_vec(&manifest).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gzip.write_all(&manifest).unwrap();
+        for (compressed, magic) in [
+            (
+                zstd::stream::encode_all(manifest.as_slice(), 3).unwrap(),
+                COMPRESSED_LIFT_MAGIC,
+            ),
+            (gzip.finish().unwrap(), GZIP_COMPRESSED_LIFT_MAGIC),
+        ] {
+            let mut data = data.clone();
+            data.extend(&compressed);
+            data.extend((compressed.len() as u32).to_le_bytes());
+            data.extend(magic.to_le_bytes());
+
+            let scie = tempdir.path().join("scie");
+            std::fs::write(&scie, &data).unwrap();
+            let (jump, lift) = load_scie(&scie, &data).unwrap();
+            assert_eq!(64, jump.size);
+            assert_eq!("compressed", lift.name);
+            assert_eq!(
+                vec!["data.txt"],
+                lift.files
+                    .iter()
+                    .map(|file| file.name.as_str())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(compressed.len() + 8, lift.size);
+            assert!(data[jump.size..data.len() - lift.size].is_empty());
+        }
+    }
+
+    #[test]
+    fn compressed_manifest_bomb() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut data = vec![0; 56];
+        data.extend(64_u32.to_le_bytes());
+        data.extend(EOF_MAGIC.to_le_bytes());
+        let compressed = zstd::stream::encode_all(vec![b' '; 1024 * 1024].as_slice(), 3).unwrap();
+        data.extend(&compressed);
+        data.extend((compressed.len() as u32).to_le_bytes());
+        data.extend(COMPRESSED_LIFT_MAGIC.to_le_bytes());
+
+        let scie = tempdir.path().join("scie");
+        std::fs::write(&scie, &data).unwrap();
+        let err = load_scie(&scie, &data).unwrap_err();
+        assert!(
+            err.contains("decompresses to more than the maximum"),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        std::fs::write(&scie, "scie").unwrap();
+        check_writable(&scie).unwrap();
+
+        std::fs::set_permissions(&scie, std::fs::Permissions::from_mode(0o444)).unwrap();
+        // N.B.: Root can write to any file on a writable filesystem.
+        if !nix::unistd::geteuid().is_root() {
+            let err = check_writable(&scie).unwrap_err();
+            assert!(err.contains("Copy it to a writable location"), "{err}");
+        }
+    }
+
+    #[test]
+    fn run_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let argv = args(&["scie", "build", "--", "--release"]);
+        let (name, argv_skip) = parse_run_args(&argv).unwrap();
+        assert_eq!("build", name);
+        assert_eq!(vec!["--release".to_string()], argv[argv_skip..].to_vec());
+
+        let argv = args(&["scie", "build"]);
+        let (name, argv_skip) = parse_run_args(&argv).unwrap();
+        assert_eq!("build", name);
+        assert!(argv[argv_skip..].is_empty());
+
+        assert!(parse_run_args(&args(&["scie"])).is_err());
+        let err = parse_run_args(&args(&["scie", "build", "--release"])).unwrap_err();
+        assert!(err.contains("SCIE=run build -- --release"), "{err}");
+    }
+
+    #[test]
+    fn env_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(None, parse_env_args(&args(&["scie"])).unwrap());
+        assert_eq!(
+            Some("build"),
+            parse_env_args(&args(&["scie", "build"])).unwrap()
+        );
+        let err = parse_env_args(&args(&["scie", "build", "--release"])).unwrap_err();
+        assert!(err.contains("build --release"), "{err}");
+    }
+
+    #[test]
+    fn extra_args() {
+        assert_eq!(Vec::<String>::new(), split_args("").unwrap());
+        assert_eq!(Vec::<String>::new(), split_args(" \t ").unwrap());
+        assert_eq!(
+            vec![
+                "--flag",
+                "two words",
+                "it's",
+                "a \"quote\"",
+                "C:\\dir",
+                "",
+                "x y"
+            ],
+            split_args(r#" --flag 'two words' "it's" "a \"quote\"" "C:\dir" '' x\ y "#).unwrap()
+        );
+        assert_eq!(vec!["ab"], split_args(r#"a'b'"#).unwrap());
+        assert!(split_args("'unterminated").is_err());
+        assert!(split_args("\"unterminated").is_err());
+        assert!(split_args("trailing\\").is_err());
+    }
+
+    #[test]
+    fn extra_args_order() {
+        let mut process = Process {
+            env: EnvVars { vars: vec![] },
+            exe: "exe".into(),
+            args: vec!["own".into()],
+            args_append: vec!["appended".into()],
+            stdout: None,
+            stderr: None,
+            inherit_fds: vec![],
+        };
+        append_extra_args(&mut process, split_args("extra 'extra two'").unwrap());
+        assert_eq!(
+            vec!["own", "extra", "extra two", "user", "appended"]
+                .into_iter()
+                .map(std::ffi::OsString::from)
+                .collect::<Vec<_>>(),
+            process.argv(vec!["user".into()])
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        let zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]))
+            .finish()
+            .unwrap()
+            .into_inner();
+        let mut data = b"#!scie-jump".to_vec();
+        data.extend(&zip);
+        data.extend(
+            format!(
+                r#"{{"scie": {{"lift": {{"name": "test", "files": [{{"name": "app.zip", "size": {size}, "hash": "{hash}", "type": "zip"}}], "boot": {{"commands": {{"": {{"exe": "{{app.zip}}"}}}}}}}}, "jump": {{"size": 11, "version": "0.9.0"}}}}}}"#,
+                size = zip.len(),
+                hash = fingerprint::digest(&zip)
+            )
+            .as_bytes(),
+        );
+        std::fs::write(&scie, &data).unwrap();
+        verify_scie(&scie).unwrap();
+
+        std::fs::write(&scie, &data[..data.len() - 1]).unwrap();
+        assert!(verify_scie(&scie).is_err());
+
+        let mut short_tip = data.clone();
+        short_tip.remove(0);
+        std::fs::write(&scie, &short_tip).unwrap();
+        let err = verify_scie(&scie).unwrap_err();
+        assert!(err.contains("byte payload"), "{err}");
+
+        let invalid = String::from_utf8(data).unwrap().replace(
+            r#""exe": "{app.zip}""#,
+            r#""exe": "{app.zip}", "args": ["{missing}"]"#,
+        );
+        std::fs::write(&scie, invalid).unwrap();
+        let err = verify_scie(&scie).unwrap_err();
+        assert!(err.contains("is invalid:\n+ The default command"), "{err}");
+    }
+
+    #[test]
+    fn args_append_order() {
+        let process = Process {
+            env: EnvVars { vars: vec![] },
+            exe: "exe".into(),
+            args: vec!["own".into()],
+            args_append: vec!["appended".into(), "appended two".into()],
+            stdout: None,
+            stderr: None,
+            inherit_fds: vec![],
+        };
+        let argv = |skip| {
+            process
+                .argv(
+                    ["scie", "tool", "user"]
+                        .map(std::ffi::OsString::from)
+                        .into_iter()
+                        .skip(skip),
+                )
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["own", "user", "appended", "appended two"],
+            argv(argv_skip(None, true))
+        );
+        assert_eq!(
+            vec!["own", "tool", "user", "appended", "appended two"],
+            argv(argv_skip(None, false))
+        );
+    }
+}
@@ -1,18 +1,52 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use bstr::ByteSlice;
 use logging_timer::time;
 
-use crate::config::{ArchiveType, Boot, Config, FileType, Jump, Other};
+use crate::config::{ArchiveType, Boot, Cmd, Compression, Config, FileType, Jump, Other};
+use crate::fingerprint::FingerprintCache;
 use crate::{archive, fingerprint};
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub enum Source {
     Scie,
     LoadBinding(String),
+    // A `<scheme>://<path>` URL the scie-jump loads the file from itself.
+    Url((String, String)),
+}
+
+/// The URL schemes the scie-jump can load files from without the help of a binding command.
+///
+/// + `file://<path>`: The file is read from the given local path.
+/// + `scie://<path>`: The file of the same name is read from the payload of the scie at the given
+///   local path.
+pub(crate) const URL_SCHEMES: [&str; 2] = ["file", "scie"];
+
+fn parse_source(source: Option<String>) -> Result<Source, String> {
+    let Some(source) = source else {
+        return Ok(Source::Scie);
+    };
+    match source.split_once("://") {
+        Some((scheme, path)) if URL_SCHEMES.contains(&scheme) => {
+            Ok(Source::Url((scheme.to_string(), path.to_string())))
+        }
+        Some((scheme, _)) => Err(format!(
+            "The file source {source} uses the unsupported scheme {scheme}://. Supported schemes \
+            are: {schemes}",
+            schemes = URL_SCHEMES
+                .iter()
+                .map(|scheme| format!("{scheme}://"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        None => Ok(Source::LoadBinding(source)),
+    }
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
@@ -23,10 +57,37 @@ pub struct File {
     pub hash: String,
     pub file_type: FileType,
     pub executable: Option<bool>,
+    pub mode: Option<u32>,
+    pub compression: Option<Compression>,
     pub eager_extract: bool,
+    pub extract_members: Vec<String>,
+    pub use_zstd_dictionary: bool,
+    pub decompressor: Option<String>,
     pub source: Source,
 }
 
+// N.B.: This is a blob stored in the scie whose size and hash are not yet known; i.e.: a file
+// declared by name alone in a lift manifest before the boot-pack fills in its details.
+impl Default for File {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            key: None,
+            size: 0,
+            hash: String::new(),
+            file_type: FileType::Blob,
+            executable: None,
+            mode: None,
+            compression: None,
+            eager_extract: false,
+            extract_members: vec![],
+            use_zstd_dictionary: false,
+            decompressor: None,
+            source: Source::Scie,
+        }
+    }
+}
+
 impl From<File> for crate::config::File {
     fn from(value: File) -> Self {
         Self {
@@ -36,13 +97,23 @@ impl From<File> for crate::config::File {
                 0 => None,
                 size => Some(size),
             },
-            hash: Some(value.hash),
+            hash: if value.hash.is_empty() {
+                None
+            } else {
+                Some(value.hash)
+            },
             file_type: Some(value.file_type),
             executable: value.executable,
+            mode: value.mode,
+            compression: value.compression,
             eager_extract: value.eager_extract,
+            extract_members: value.extract_members,
+            use_zstd_dictionary: value.use_zstd_dictionary,
+            decompressor: value.decompressor,
             source: match value.source {
                 Source::Scie => None,
                 Source::LoadBinding(binding_name) => Some(binding_name),
+                Source::Url((scheme, path)) => Some(format!("{scheme}://{path}")),
             },
         }
     }
@@ -54,6 +125,16 @@ pub struct Lift {
     pub description: Option<String>,
     pub base: Option<PathBuf>,
     pub(crate) load_dotenv: bool,
+    pub(crate) dotenv_files: Vec<String>,
+    pub(crate) extract_by_hash: bool,
+    pub(crate) zstd_dictionary: Option<String>,
+    pub(crate) min_jump_version: Option<String>,
+    pub(crate) base_binding: Option<String>,
+    pub(crate) commands_file: Option<String>,
+    pub(crate) env_file: Option<String>,
+    pub(crate) relative_base: bool,
+    pub(crate) dev: bool,
+    pub(crate) shared_bindings: bool,
     pub size: usize,
     pub hash: String,
     pub boot: Boot,
@@ -65,6 +146,8 @@ pub struct ScieBoot {
     pub name: String,
     pub description: Option<String>,
     pub default: bool,
+    // N.B.: This is the exe as written in the lift manifest; i.e.: with its placeholders intact.
+    pub exe: String,
 }
 
 impl Lift {
@@ -84,10 +167,32 @@ impl Lift {
                     name,
                     description,
                     default,
+                    exe: cmd.exe.clone(),
                 }
             })
             .collect::<Vec<_>>()
     }
+
+    /// Returns the file with the given name or else, failing that, the file with the given key.
+    pub fn file(&self, name_or_key: &str) -> Option<&File> {
+        find_file(&self.files, name_or_key)
+    }
+
+    /// Returns an iterator over the files in lift manifest order.
+    pub fn files_iter(&self) -> impl Iterator<Item = &File> {
+        self.files.iter()
+    }
+}
+
+fn find_file<'a>(files: &'a [File], name_or_key: &str) -> Option<&'a File> {
+    files
+        .iter()
+        .find(|file| file.name == name_or_key)
+        .or_else(|| {
+            files
+                .iter()
+                .find(|file| file.key.as_deref() == Some(name_or_key))
+        })
 }
 
 impl From<Lift> for crate::config::Lift {
@@ -97,6 +202,31 @@ impl From<Lift> for crate::config::Lift {
             description: value.description,
             base: value.base,
             load_dotenv: if value.load_dotenv { Some(true) } else { None },
+            dotenv_files: value.dotenv_files,
+            extract_by_hash: if value.extract_by_hash {
+                Some(true)
+            } else {
+                None
+            },
+            zstd_dictionary: value.zstd_dictionary,
+            min_jump_version: value.min_jump_version,
+            base_binding: value.base_binding,
+            commands_file: value.commands_file,
+            env_file: value.env_file,
+            relative_base: if value.relative_base {
+                Some(true)
+            } else {
+                None
+            },
+            dev: if value.dev { Some(true) } else { None },
+            shared_bindings: if value.shared_bindings {
+                Some(true)
+            } else {
+                None
+            },
+            // N.B.: The interpreter and app shorthand is expanded into the default command on load.
+            interpreter: None,
+            app: None,
             boot: value.boot,
             files: value
                 .files
@@ -165,21 +295,71 @@ fn assemble(
     resolve_base: &Path,
     config_files: Vec<crate::config::File>,
     reconstitute: bool,
+    options: LoadOptions,
 ) -> Result<Vec<File>, String> {
+    let LoadOptions {
+        dev,
+        dir_archive_type,
+        fingerprint_cache,
+        source_date_epoch,
+    } = options;
     let mut files = vec![];
     for file in config_files {
         let mut path = resolve_base.join(&file.name);
 
-        let file_type = if let Some(file_type) = file.file_type {
+        let mut file_type = if let Some(file_type) = file.file_type {
             file_type
         } else if reconstitute {
             determine_file_type(&path)?
         } else {
             return Err(format!("A file type is required. Found: {file:?}"));
         };
+        if file.compression.is_some() && file_type != FileType::Blob {
+            return Err(format!(
+                "Only blob files can have a compression, {name} is of type {file_type:?}.",
+                name = file.name
+            ));
+        }
+
+        if !file.extract_members.is_empty()
+            && !matches!(file_type, FileType::Archive(_) | FileType::Directory)
+        {
+            return Err(format!(
+                "Only archive files can have extract_members, {name} is of type {file_type:?}.",
+                name = file.name
+            ));
+        }
+
+        if file.use_zstd_dictionary
+            && file_type != FileType::Archive(ArchiveType::CompressedTar(Compression::Zstd))
+        {
+            return Err(format!(
+                "Only tar.zst archives can use the zstd dictionary, {name} is of type \
+                {file_type:?}.",
+                name = file.name
+            ));
+        }
 
-        if reconstitute && file_type == FileType::Directory {
-            path = archive::create(resolve_base, &file.name)?;
+        if file.decompressor.is_some() && file_type != FileType::Archive(ArchiveType::Tar) {
+            return Err(format!(
+                "Only tar archives can have a decompressor, {name} is of type {file_type:?}.",
+                name = file.name
+            ));
+        }
+
+        let created = reconstitute && file_type == FileType::Directory;
+        if created {
+            path = archive::create(
+                resolve_base,
+                &file.name,
+                dir_archive_type,
+                source_date_epoch,
+            )?;
+            // N.B.: A directory stored as a zip retains its directory type for backwards
+            // compatibility, but a tar is just a tar archive as far as the installer is concerned.
+            if ArchiveType::Tar == dir_archive_type {
+                file_type = FileType::Archive(ArchiveType::Tar);
+            }
         }
 
         let (size, hash) = match file {
@@ -193,7 +373,28 @@ fn assemble(
                 hash: Some(hash),
                 ..
             } => (0, hash), // A scie-tote entry.
-            _ if reconstitute => fingerprint::digest_file(&path)?,
+            crate::config::File {
+                size: Some(size), ..
+            } if dev => (size, String::new()),
+            _ if dev && reconstitute => {
+                let size = path
+                    .metadata()
+                    .map_err(|e| {
+                        format!(
+                            "Failed to determine the size of {path}: {e}",
+                            path = path.display()
+                        )
+                    })?
+                    .len() as usize;
+                (size, String::new())
+            }
+            _ if dev => (0, String::new()), // An unhashed scie-tote entry.
+            // N.B.: An archive just created from a directory is always new; so it gains nothing
+            // from the fingerprint cache.
+            _ if reconstitute => match fingerprint_cache {
+                Some(fingerprint_cache) if !created => fingerprint_cache.digest_file(&path)?,
+                _ => fingerprint::digest_file(&path)?,
+            },
             file => {
                 return Err(format!(
                     "Both file size and hash are required. Found: {file:?}"
@@ -216,26 +417,225 @@ fn assemble(
             hash,
             file_type,
             executable,
+            mode: file.mode,
+            compression: file.compression,
             eager_extract: file.eager_extract,
-            source: match file.source {
-                None => Source::Scie,
-                Some(binding_name) => Source::LoadBinding(binding_name),
-            },
+            extract_members: file.extract_members,
+            use_zstd_dictionary: file.use_zstd_dictionary,
+            decompressor: file.decompressor,
+            source: parse_source(file.source)?,
         });
     }
     Ok(files)
 }
 
+/// Merges the commands defined in the JSON object `data` into the lift's boot commands.
+///
+/// The lift's commands file is dropped once merged; so the lift serializes with its commands
+/// defined just once.
+pub(crate) fn merge_commands(lift: &mut Lift, data: &[u8]) -> Result<(), String> {
+    let commands: BTreeMap<String, Cmd> = serde_json::from_slice(data)
+        .map_err(|e| format!("Failed to decode the commands file: {e}"))?;
+    for (name, cmd) in commands {
+        if lift.boot.commands.contains_key(&name) {
+            return Err(format!(
+                "The command {name:?} is defined in both the lift manifest and the commands file."
+            ));
+        }
+        lift.boot.commands.insert(name, cmd);
+    }
+    lift.commands_file = None;
+    Ok(())
+}
+
+/// Expands the `interpreter` and `app` shorthand, if present, into a default command that runs the
+/// app file with the interpreter file.
+///
+/// The interpreter can also name an executable inside an archive file; e.g.: `python/bin/python3`.
+fn expand_interpreter(
+    boot: &mut Boot,
+    files: &[File],
+    interpreter: Option<String>,
+    app: Option<String>,
+) -> Result<(), String> {
+    let (interpreter, app) =
+        match (interpreter, app) {
+            (None, None) => return Ok(()),
+            (Some(interpreter), Some(app)) => (interpreter, app),
+            _ => return Err(
+                "A lift manifest must specify both an \"interpreter\" and an \"app\" or neither."
+                    .to_string(),
+            ),
+        };
+    if boot.commands.contains_key("") {
+        return Err(format!(
+            "The lift manifest \"interpreter\" {interpreter} and \"app\" {app} define the \
+            default command, but a default command is already defined."
+        ));
+    }
+    let (interpreter_file, member) = match interpreter.split_once('/') {
+        Some((file, member)) => (file, Some(member)),
+        None => (interpreter.as_str(), None),
+    };
+    for name in [interpreter_file, app.as_str()] {
+        if find_file(files, name).is_none() {
+            return Err(format!(
+                "The lift manifest \"interpreter\" {interpreter} and \"app\" {app} refer to a \
+                file named {name} that is not in the lift manifest."
+            ));
+        }
+    }
+    let exe = match member {
+        Some(member) => format!("{{{interpreter_file}}}/{member}"),
+        None => format!("{{{interpreter_file}}}"),
+    };
+    boot.commands.insert(
+        "".to_string(),
+        Cmd {
+            exe,
+            args: vec![format!("{{{app}}}")],
+            ..Default::default()
+        },
+    );
+    Ok(())
+}
+
+// N.B.: Files are referenced by name or key; so any collision means one file shadows another. When
+// `allow_collisions`, collisions are only warned about.
+fn check_file_keys(files: &[File], allow_collisions: bool) -> Result<(), String> {
+    let collision = |message: String| {
+        if allow_collisions {
+            warn!("{message}");
+            Ok(())
+        } else {
+            Err(format!(
+                "{message} Set SCIE_ALLOW_DUP_NAMES=1 to only warn about this."
+            ))
+        }
+    };
+    let mut names = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if let Some((other_index, _)) = names.insert(file.name.as_str(), (index, file)) {
+            collision(format!(
+                "The files at indexes {other_index} and {index} in the lift manifest are both \
+                named {name}.",
+                name = file.name
+            ))?;
+        }
+    }
+    let mut keys = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if let Some(key) = file.key.as_deref() {
+            // N.B.: We compare indexes since identical duplicate entries are still distinct files.
+            if let Some((_, other)) = names
+                .get(key)
+                .filter(|(other_index, _)| *other_index != index)
+            {
+                collision(format!(
+                    "The file {name} has key {key} which collides with the name of file \
+                    {other_name}.",
+                    name = file.name,
+                    other_name = other.name
+                ))?;
+            }
+            if let Some(other) = keys.insert(key, file) {
+                collision(format!(
+                    "The files {other_name} and {name} both have the key {key}.",
+                    other_name = other.name,
+                    name = file.name
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// N.B.: We decompress no more than a lift manifest can hold; so a small compressed lift manifest
+// cannot expand to exhaust memory.
+fn decompress_lift(
+    scie_path: &Path,
+    compression: Compression,
+    compressed: &[u8],
+) -> Result<Vec<u8>, String> {
+    let decompress_error = |e: std::io::Error| {
+        format!(
+            "Failed to decompress the lift manifest of the scie at {scie_path}: {e}",
+            scie_path = scie_path.display()
+        )
+    };
+    let decoder: Box<dyn Read + '_> = match compression {
+        Compression::Gzip => Box::new(flate2::read::GzDecoder::new(compressed)),
+        Compression::Zstd => {
+            Box::new(zstd::stream::Decoder::new(compressed).map_err(decompress_error)?)
+        }
+        compression => {
+            return Err(format!(
+                "The lift manifest of the scie at {scie_path} has unsupported compression \
+                {compression:?}.",
+                scie_path = scie_path.display()
+            ))
+        }
+    };
+    let mut manifest = vec![];
+    decoder
+        .take(Config::MAXIMUM_CONFIG_SIZE as u64 + 1)
+        .read_to_end(&mut manifest)
+        .map_err(decompress_error)?;
+    if manifest.len() > Config::MAXIMUM_CONFIG_SIZE {
+        return Err(format!(
+            "The lift manifest of the scie at {scie_path} decompresses to more than the maximum \
+            of {max} bytes.",
+            scie_path = scie_path.display(),
+            max = Config::MAXIMUM_CONFIG_SIZE
+        ));
+    }
+    Ok(manifest)
+}
+
 #[time("debug", "lift::{}")]
 pub(crate) fn load_scie(scie_path: &Path, scie_data: &[u8]) -> Result<(Jump, Lift), String> {
-    let end_of_zip = crate::zip::end_of_zip(scie_data, Config::MAXIMUM_CONFIG_SIZE)?;
-    let result = load(scie_path, &scie_data[end_of_zip..], false).map_err(|e| {
+    let (manifest, trailer_size) = if let Some((compression, compressed)) =
+        crate::jump::compressed_lift(scie_data)?
+    {
+        let manifest = decompress_lift(scie_path, compression, compressed)?;
+        (Cow::Owned(manifest), Some(compressed.len() + 8))
+    } else {
+        // N.B.: A scie whose files are all loaded by bindings has no payload; so its lift manifest
+        // follows the scie-jump tip directly.
+        let start_of_lift = match crate::zip::end_of_zip(scie_data, Config::MAXIMUM_CONFIG_SIZE) {
+            Ok(end_of_zip) => end_of_zip,
+            Err(e) => crate::jump::end_of_jump(scie_data, Config::MAXIMUM_CONFIG_SIZE).ok_or(e)?,
+        };
+        (Cow::Borrowed(&scie_data[start_of_lift..]), None)
+    };
+    let mut result = load(scie_path, &manifest, false, LoadOptions::default()).map_err(|e| {
         format!(
             "The scie at {scie_path} has missing information in its lift manifest: {e}",
             scie_path = scie_path.display()
         )
     })?;
+    // The payload ends where the compressed lift manifest trailer starts.
+    if let Some(trailer_size) = trailer_size {
+        result.1.size = trailer_size;
+    }
     match result {
+        (Some(jump), lift)
+            if jump
+                .size
+                .checked_add(lift.size)
+                .filter(|size| *size <= scie_data.len())
+                .is_none() =>
+        {
+            Err(format!(
+                "The scie at {path} has a lift manifest that specifies a scie-jump size of \
+                {jump_size} bytes, but the scie is only {scie_size} bytes including its \
+                {lift_size} byte lift manifest.",
+                path = scie_path.display(),
+                jump_size = jump.size,
+                scie_size = scie_data.len(),
+                lift_size = lift.size
+            ))
+        }
         (Some(jump), lift) => Ok((jump, lift)),
         _ => Err(format!(
             "The scie at {path} has a lift manifest with no scie-jump information.",
@@ -244,23 +644,64 @@ pub(crate) fn load_scie(scie_path: &Path, scie_data: &[u8]) -> Result<(Jump, Lif
     }
 }
 
+/// Options for loading a lift manifest for packing.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions<'a> {
+    /// Files without a hash are not hashed and their hashes are not verified when the scie is
+    /// booted.
+    pub dev: bool,
+    /// Directory files are stored as archives of this type, which must be either a zip or a tar.
+    pub dir_archive_type: ArchiveType,
+    /// Files are digested through this cache when given.
+    pub fingerprint_cache: Option<&'a FingerprintCache>,
+    /// Archives created from directory files are stamped with this time when given.
+    pub source_date_epoch: Option<u64>,
+}
+
+impl Default for LoadOptions<'_> {
+    fn default() -> Self {
+        Self {
+            dev: false,
+            dir_archive_type: ArchiveType::Zip,
+            fingerprint_cache: None,
+            source_date_epoch: None,
+        }
+    }
+}
+
 #[time("debug", "lift::{}")]
-pub fn load_lift(manifest_path: &Path) -> Result<(Option<Jump>, Lift), String> {
+/// Loads the lift manifest at `manifest_path` for packing.
+pub fn load_lift(
+    manifest_path: &Path,
+    options: LoadOptions,
+) -> Result<(Option<Jump>, Lift), String> {
     let data = std::fs::read(manifest_path).map_err(|e| {
         format!(
             "Failed to open lift manifest at {manifest}: {e}",
             manifest = manifest_path.display()
         )
     })?;
-    load(manifest_path, &data, true)
+    load(manifest_path, &data, true, options)
+}
+
+#[time("debug", "lift::{}")]
+/// Loads the lift manifest `data` for packing, resolving its files relative to `resolve_base`.
+///
+/// This supports lift manifests that have no file of their own; e.g.: those read from stdin.
+pub fn load_lift_data(
+    data: &[u8],
+    resolve_base: &Path,
+    options: LoadOptions,
+) -> Result<(Option<Jump>, Lift), String> {
+    load_from(resolve_base, data, true, options)
 }
 
 fn load(
     manifest_path: &Path,
     data: &[u8],
     reconstitute: bool,
+    options: LoadOptions,
 ) -> Result<(Option<Jump>, Lift), String> {
-    let config = Config::parse(data)?;
     let manifest_absolute_path = manifest_path.canonicalize().map_err(|e| {
         format!(
             "Failed to resolve an absolute path for the lift manifest {manifest}: {e}",
@@ -270,8 +711,34 @@ fn load(
     let resolve_base = manifest_absolute_path
         .parent()
         .unwrap_or_else(|| Path::new(""));
+    load_from(resolve_base, data, reconstitute, options)
+}
+
+fn load_from(
+    resolve_base: &Path,
+    data: &[u8],
+    reconstitute: bool,
+    options: LoadOptions,
+) -> Result<(Option<Jump>, Lift), String> {
+    let config = Config::parse(data)?;
     let lift = config.scie.lift;
-    let files = assemble(resolve_base, lift.files, reconstitute)?;
+    let dev = options.dev || lift.dev.unwrap_or(false);
+    let files = assemble(
+        resolve_base,
+        lift.files,
+        reconstitute,
+        LoadOptions { dev, ..options },
+    )?;
+    // N.B.: Collisions are a packing error; so a scie packed with SCIE_ALLOW_DUP_NAMES=1 boots
+    // regardless of whether its end user sets it too.
+    if reconstitute {
+        let allow_collisions = std::env::var_os("SCIE_ALLOW_DUP_NAMES")
+            .map(|value| value == "1")
+            .unwrap_or(false);
+        check_file_keys(&files, allow_collisions)?;
+    }
+    let mut boot = lift.boot;
+    expand_interpreter(&mut boot, &files, lift.interpreter, lift.app)?;
     Ok((
         config.scie.jump,
         Lift {
@@ -279,7 +746,17 @@ fn load(
             description: lift.description,
             base: lift.base,
             load_dotenv: lift.load_dotenv.unwrap_or(false),
-            boot: lift.boot,
+            dotenv_files: lift.dotenv_files,
+            extract_by_hash: lift.extract_by_hash.unwrap_or(false),
+            zstd_dictionary: lift.zstd_dictionary,
+            min_jump_version: lift.min_jump_version,
+            base_binding: lift.base_binding,
+            commands_file: lift.commands_file,
+            env_file: lift.env_file,
+            relative_base: lift.relative_base.unwrap_or(false),
+            dev,
+            shared_bindings: lift.shared_bindings.unwrap_or(false),
+            boot,
             size: data.len(),
             hash: fingerprint::digest(data),
             files,
@@ -287,3 +764,368 @@ fn load(
         },
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_file_keys, determine_file_type, load_lift, load_scie, merge_commands, parse_source,
+        LoadOptions,
+    };
+    use crate::config::{ArchiveType, Compression, FileType};
+    use crate::{fingerprint, testing, File, Lift, Source};
+
+    fn file(name: &str, key: Option<&str>) -> File {
+        File {
+            name: name.to_string(),
+            key: key.map(str::to_string),
+            size: 42,
+            hash: "abc".to_string(),
+            ..File::default()
+        }
+    }
+
+    #[test]
+    fn file_keys() {
+        assert!(check_file_keys(
+            &[file("python", Some("py")), file("app.pex", Some("app"))],
+            false
+        )
+        .is_ok());
+        assert!(check_file_keys(&[file("python", Some("python"))], false).is_ok());
+        assert!(check_file_keys(
+            &[
+                file("python", Some("python")),
+                file("python", Some("python"))
+            ],
+            false
+        )
+        .is_err());
+
+        let err = check_file_keys(
+            &[file("python", None), file("cpython", Some("python"))],
+            false,
+        )
+        .unwrap_err();
+        assert!(err.contains("cpython"), "{err}");
+        assert!(err.contains("python"), "{err}");
+
+        let err = check_file_keys(
+            &[
+                file("python3.9", Some("py")),
+                file("python3.10", Some("py")),
+            ],
+            false,
+        )
+        .unwrap_err();
+        assert!(err.contains("python3.9"), "{err}");
+        assert!(err.contains("python3.10"), "{err}");
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let mut other = file("python", None);
+        other.hash = "def".to_string();
+        let files = [file("python", None), file("app.pex", None), other];
+        let err = check_file_keys(&files, false).unwrap_err();
+        assert!(
+            err.contains("indexes 0 and 2 in the lift manifest are both named python"),
+            "{err}"
+        );
+        assert!(err.contains("SCIE_ALLOW_DUP_NAMES=1"), "{err}");
+        assert!(check_file_keys(&files, true).is_ok());
+    }
+
+    #[test]
+    fn duplicate_names_packed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        let mut data = b"#!scie-jump".to_vec();
+        data.extend(
+            zip::ZipWriter::new(std::io::Cursor::new(vec![]))
+                .finish()
+                .unwrap()
+                .into_inner(),
+        );
+        data.extend(
+            br#"{"scie": {"lift": {"name": "test", "files": [{"name": "python", "type": "blob", "size": 0, "hash": "abc"}, {"name": "python", "type": "blob", "size": 0, "hash": "def"}], "boot": {"commands": {"": {"exe": "{python}"}}}}, "jump": {"size": 11, "version": "0.9.0"}}}"#,
+        );
+        std::fs::write(&scie, &data).unwrap();
+        let (_, lift) = load_scie(&scie, &data).unwrap();
+        assert_eq!(2, lift.files.len());
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(Source::Scie, parse_source(None).unwrap());
+        assert_eq!(
+            Source::LoadBinding("fetch".to_string()),
+            parse_source(Some("fetch".to_string())).unwrap()
+        );
+        assert_eq!(
+            Source::Url(("file".to_string(), "/opt/cache/python.tar.zst".to_string())),
+            parse_source(Some("file:///opt/cache/python.tar.zst".to_string())).unwrap()
+        );
+        assert_eq!(
+            Source::Url(("scie".to_string(), "/opt/app".to_string())),
+            parse_source(Some("scie:///opt/app".to_string())).unwrap()
+        );
+        let err = parse_source(Some("https://example.com/python.tar.zst".to_string())).unwrap_err();
+        assert!(err.contains("unsupported scheme https://"), "{err}");
+    }
+
+    #[test]
+    fn file_lookup() {
+        let lift = Lift {
+            files: vec![file("python", Some("py")), file("app.pex", None)],
+            ..testing::lift()
+        };
+        assert_eq!(Some(&lift.files[0]), lift.file("python"));
+        assert_eq!(Some(&lift.files[0]), lift.file("py"));
+        assert_eq!(Some(&lift.files[1]), lift.file("app.pex"));
+        assert_eq!(None, lift.file("app"));
+        assert_eq!(
+            vec!["python", "app.pex"],
+            lift.files_iter()
+                .map(|file| file.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn short_archive_extensions() {
+        let tempdir = tempfile::tempdir().unwrap();
+        for (name, compression) in [
+            ("app.tgz", Compression::Gzip),
+            ("app.tbz2", Compression::Bzip2),
+            ("app.tlz", Compression::Xz),
+            ("app.txz", Compression::Xz),
+            ("app.tzst", Compression::Zstd),
+            ("app.tar.xz", Compression::Xz),
+        ] {
+            let path = tempdir.path().join(name);
+            std::fs::write(&path, b"").unwrap();
+            assert_eq!(
+                FileType::Archive(ArchiveType::CompressedTar(compression)),
+                determine_file_type(&path).unwrap(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn jump_size_out_of_bounds() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        let scie_data = |jump_size: usize| {
+            let mut data = b"#!scie-jump".to_vec();
+            data.extend(
+                zip::ZipWriter::new(std::io::Cursor::new(vec![]))
+                    .finish()
+                    .unwrap()
+                    .into_inner(),
+            );
+            data.extend(
+                format!(
+                    r#"{{"scie": {{"lift": {{"name": "test", "files": [], "boot": {{"commands": {{}}}}}}, "jump": {{"size": {jump_size}, "version": "0.9.0"}}}}}}"#
+                )
+                .as_bytes(),
+            );
+            std::fs::write(&scie, &data).unwrap();
+            data
+        };
+
+        let data = scie_data(11);
+        let (jump, _) = load_scie(&scie, &data).unwrap();
+        assert_eq!(11, jump.size);
+
+        let data = scie_data(1 << 30);
+        let err = load_scie(&scie, &data).unwrap_err();
+        assert!(err.contains("scie-jump size of 1073741824 bytes"), "{err}");
+
+        let data = scie_data(usize::MAX);
+        assert!(load_scie(&scie, &data).is_err());
+    }
+
+    #[test]
+    fn external_commands() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        let mut data = b"#!scie-jump".to_vec();
+        data.extend(
+            zip::ZipWriter::new(std::io::Cursor::new(vec![]))
+                .finish()
+                .unwrap()
+                .into_inner(),
+        );
+        data.extend(
+            br#"{"scie": {"lift": {"name": "test", "commands_file": "commands.json", "files": [], "boot": {"commands": {"": {"exe": "default"}}}}, "jump": {"size": 11, "version": "0.9.0"}}}"#,
+        );
+        std::fs::write(&scie, &data).unwrap();
+        let (_, mut lift) = load_scie(&scie, &data).unwrap();
+        assert_eq!(Some("commands.json"), lift.commands_file.as_deref());
+
+        merge_commands(
+            &mut lift,
+            br#"{"ext": {"exe": "external", "args": ["-v"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(2, lift.boot.commands.len());
+        assert_eq!("default", lift.boot.commands[""].exe);
+        assert_eq!("external", lift.boot.commands["ext"].exe);
+        assert_eq!(vec!["-v".to_string()], lift.boot.commands["ext"].args);
+        assert_eq!(None, lift.commands_file);
+
+        let err = merge_commands(&mut lift, br#"{"": {"exe": "shadow"}}"#).unwrap_err();
+        assert!(err.contains("defined in both"), "{err}");
+        assert!(merge_commands(&mut lift, b"[]").is_err());
+    }
+
+    #[test]
+    fn dev_lift() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("big.bin"), b"big").unwrap();
+        let manifest = tempdir.path().join("lift.json");
+        std::fs::write(
+            &manifest,
+            r#"{"scie": {"lift": {"name": "test", "files": [{"name": "big.bin"}], "boot": {"commands": {"": {"exe": "{big.bin}"}}}}}}"#,
+        )
+        .unwrap();
+
+        let (_, lift) = load_lift(
+            &manifest,
+            LoadOptions {
+                dev: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(lift.dev);
+        assert_eq!(3, lift.files[0].size);
+        assert_eq!("", lift.files[0].hash);
+        let config = crate::config(
+            crate::Jump {
+                size: 11,
+                version: "0.9.0".to_string(),
+            },
+            lift,
+        );
+        assert_eq!(None, config.scie.lift.files[0].hash);
+        assert_eq!(Some(true), config.scie.lift.dev);
+
+        let (_, lift) = load_lift(&manifest, LoadOptions::default()).unwrap();
+        assert!(!lift.dev);
+        assert_eq!(crate::fingerprint::digest(b"big"), lift.files[0].hash);
+    }
+
+    #[test]
+    fn interpreter() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let manifest = tempdir.path().join("lift.json");
+        let write_manifest = |interpreter: Option<&str>, app: Option<&str>| {
+            let mut lift = serde_json::json!({
+                "name": "app",
+                "files": [
+                    {
+                        "name": "cpython.tar.gz",
+                        "key": "python",
+                        "size": 6,
+                        "hash": fingerprint::digest(b"python"),
+                        "type": "tar.gz"
+                    },
+                    {
+                        "name": "app.pyz",
+                        "size": 3,
+                        "hash": fingerprint::digest(b"app"),
+                        "type": "blob"
+                    }
+                ]
+            });
+            if let Some(interpreter) = interpreter {
+                lift["interpreter"] = interpreter.into();
+            }
+            if let Some(app) = app {
+                lift["app"] = app.into();
+            }
+            std::fs::write(
+                &manifest,
+                serde_json::to_vec(&serde_json::json!({ "scie": { "lift": lift } })).unwrap(),
+            )
+            .unwrap();
+        };
+
+        write_manifest(Some("python/bin/python3"), Some("app.pyz"));
+        let (_, lift) = load_lift(&manifest, LoadOptions::default()).unwrap();
+        let cmd = lift.boot.commands.get("").unwrap();
+        assert_eq!("{python}/bin/python3", cmd.exe);
+        assert_eq!(vec!["{app.pyz}".to_string()], cmd.args);
+
+        write_manifest(Some("python"), None);
+        assert!(load_lift(&manifest, LoadOptions::default()).is_err());
+
+        write_manifest(Some("python/bin/python3"), Some("app.pex"));
+        let err = load_lift(&manifest, LoadOptions::default()).unwrap_err();
+        assert!(err.contains("app.pex"), "{err}");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn directory_as_tar() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let app = tempdir.path().join("app");
+        std::fs::create_dir_all(app.join("bin")).unwrap();
+        std::fs::write(app.join("bin").join("app"), "#!/bin/sh\necho app").unwrap();
+        std::fs::set_permissions(
+            app.join("bin").join("app"),
+            std::fs::Permissions::from_mode(0o750),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("bin/app", app.join("run")).unwrap();
+        let manifest = tempdir.path().join("lift.json");
+        std::fs::write(
+            &manifest,
+            r#"{"scie": {"lift": {"name": "test", "files": [{"name": "app"}], "boot": {"commands": {"": {"exe": "{app}/run"}}}}}}"#,
+        )
+        .unwrap();
+
+        let (_, lift) = load_lift(
+            &manifest,
+            LoadOptions {
+                dir_archive_type: ArchiveType::Tar,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(FileType::Archive(ArchiveType::Tar), lift.files[0].file_type);
+        let tar = tempdir.path().join("app.tar");
+        assert_eq!(
+            fingerprint::digest_file(&tar).unwrap().1,
+            lift.files[0].hash
+        );
+
+        let unpacked = tempdir.path().join("unpacked");
+        tar::Archive::new(std::fs::File::open(&tar).unwrap())
+            .unpack(&unpacked)
+            .unwrap();
+        assert_eq!(
+            "#!/bin/sh\necho app",
+            std::fs::read_to_string(unpacked.join("bin").join("app")).unwrap()
+        );
+        assert_eq!(
+            0o750,
+            unpacked
+                .join("bin")
+                .join("app")
+                .metadata()
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        );
+        assert_eq!(
+            std::path::PathBuf::from("bin/app"),
+            std::fs::read_link(unpacked.join("run")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+//! A decoder for the LZW format written by the classic Unix `compress` utility; i.e.: `.Z` files.
+
+use std::io::{BufReader, Read};
+
+const MAGIC: [u8; 2] = [0x1f, 0x9d];
+const BLOCK_MODE: u8 = 0x80;
+const MAX_BITS_MASK: u8 = 0x1f;
+
+const INIT_BITS: u32 = 9;
+const MAX_BITS: u32 = 16;
+
+// In block mode, this code resets the code table.
+const CLEAR: u32 = 256;
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) struct Decoder<R> {
+    reader: BufReader<R>,
+    max_bits: u32,
+    block_mode: bool,
+    n_bits: u32,
+    bit_buffer: u32,
+    bit_count: u32,
+    // N.B.: Codes are written in groups of 8 and `compress` discards the rest of the current group
+    // whenever the code width changes; so we track how many codes into the group we are.
+    group_codes: u32,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    free_entry: u32,
+    old_code: Option<u32>,
+    first_char: u8,
+    output: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub(crate) fn new(reader: R) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut header = [0; 3];
+        reader.read_exact(&mut header)?;
+        if header[..2] != MAGIC {
+            return Err(invalid_data(format!(
+                "Expected LZW compressed data to start with {MAGIC:02x?} but found {magic:02x?}.",
+                magic = &header[..2]
+            )));
+        }
+        let max_bits = u32::from(header[2] & MAX_BITS_MASK);
+        if !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            return Err(invalid_data(format!(
+                "LZW compressed data must use between {INIT_BITS} and {MAX_BITS} bit codes but \
+                this data uses {max_bits}."
+            )));
+        }
+        let block_mode = header[2] & BLOCK_MODE != 0;
+        Ok(Self {
+            reader,
+            max_bits,
+            block_mode,
+            n_bits: INIT_BITS,
+            bit_buffer: 0,
+            bit_count: 0,
+            group_codes: 0,
+            prefix: vec![0; 1 << max_bits],
+            suffix: vec![0; 1 << max_bits],
+            free_entry: Self::first_entry(block_mode),
+            old_code: None,
+            first_char: 0,
+            output: vec![],
+            position: 0,
+            done: false,
+        })
+    }
+
+    fn first_entry(block_mode: bool) -> u32 {
+        if block_mode {
+            CLEAR + 1
+        } else {
+            CLEAR
+        }
+    }
+
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let mut byte = [0; 1];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // N.B.: Trailing bits too few to form a whole code are padding and end the stream.
+    fn read_code(&mut self) -> std::io::Result<Option<u32>> {
+        while self.bit_count < self.n_bits {
+            match self.read_byte()? {
+                Some(byte) => {
+                    self.bit_buffer |= u32::from(byte) << self.bit_count;
+                    self.bit_count += 8;
+                }
+                None => return Ok(None),
+            }
+        }
+        let code = self.bit_buffer & ((1 << self.n_bits) - 1);
+        self.bit_buffer >>= self.n_bits;
+        self.bit_count -= self.n_bits;
+        self.group_codes = (self.group_codes + 1) % 8;
+        Ok(Some(code))
+    }
+
+    fn skip_group(&mut self) -> std::io::Result<()> {
+        while self.group_codes != 0 {
+            if self.read_code()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Decodes the next code into the output buffer, which may be left empty.
+    fn decode(&mut self) -> std::io::Result<()> {
+        if self.n_bits < self.max_bits && self.free_entry > (1 << self.n_bits) - 1 {
+            self.skip_group()?;
+            self.n_bits += 1;
+        }
+        let Some(code) = self.read_code()? else {
+            self.done = true;
+            return Ok(());
+        };
+        if self.block_mode && code == CLEAR {
+            self.skip_group()?;
+            self.n_bits = INIT_BITS;
+            self.free_entry = Self::first_entry(self.block_mode);
+            self.old_code = None;
+            return Ok(());
+        }
+        let Some(old_code) = self.old_code else {
+            if code > 255 {
+                return Err(invalid_data(format!(
+                    "LZW compressed data must start with a literal byte code but found {code}."
+                )));
+            }
+            self.first_char = code as u8;
+            self.output.push(self.first_char);
+            self.old_code = Some(code);
+            return Ok(());
+        };
+
+        let start = self.output.len();
+        let mut entry = code;
+        if entry >= self.free_entry {
+            // N.B.: This is the one code the encoder can emit before the decoder has seen it; it
+            // is the previous string plus its own first character.
+            if entry > self.free_entry {
+                return Err(invalid_data(format!(
+                    "Encountered the LZW code {code} before the code table reached it."
+                )));
+            }
+            self.output.push(self.first_char);
+            entry = old_code;
+        }
+        while entry > 255 {
+            self.output.push(self.suffix[entry as usize]);
+            entry = u32::from(self.prefix[entry as usize]);
+        }
+        self.first_char = entry as u8;
+        self.output.push(self.first_char);
+        self.output[start..].reverse();
+
+        if self.free_entry < (1 << self.max_bits) {
+            self.prefix[self.free_entry as usize] = old_code as u16;
+            self.suffix[self.free_entry as usize] = self.first_char;
+            self.free_entry += 1;
+        }
+        self.old_code = Some(code);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.output.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            self.output.clear();
+            self.position = 0;
+            self.decode()?;
+        }
+        let size = buf.len().min(self.output.len() - self.position);
+        buf[..size].copy_from_slice(&self.output[self.position..self.position + size]);
+        self.position += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::Decoder;
+
+    fn decode(data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decoded = vec![];
+        Decoder::new(data)?.read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn compress_fixture() {
+        // N.B.: This fixture was compressed with 16 bit codes; so it exercises several code width
+        // changes.
+        let decoded = decode(include_bytes!("../tests/fixtures/lorem.tar.Z")).unwrap();
+        let mut archive = tar::Archive::new(decoded.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let mut entry = entries.next().unwrap().unwrap();
+        assert_eq!("lorem.txt", entry.path().unwrap().to_str().unwrap());
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        assert_eq!(250, contents.lines().count());
+        assert!(contents.starts_with("0: "), "{contents}");
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn clear() {
+        // The 9 bit codes for: a, b, CLEAR, padding to the end of the group of 8 and then c, a.
+        let codes: [u32; 10] = [97, 98, 256, 0, 0, 0, 0, 0, 99, 97];
+        let mut data = vec![0x1f, 0x9d, 0x90];
+        let (mut buffer, mut count) = (0u32, 0);
+        for code in codes {
+            buffer |= code << count;
+            count += 9;
+            while count >= 8 {
+                data.push(buffer as u8);
+                buffer >>= 8;
+                count -= 8;
+            }
+        }
+        data.push(buffer as u8);
+        assert_eq!(b"abca".to_vec(), decode(&data).unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decode(b"\x1f\x8b\x08").is_err());
+        assert!(decode(b"\x1f\x9d\x88").is_err());
+        // The first code must be a literal byte.
+        assert!(decode(b"\x1f\x9d\x90\xff\x01").is_err());
+    }
+}
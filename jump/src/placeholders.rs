@@ -1,10 +1,18 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
+#[cfg_attr(test, derive(Eq, PartialEq))]
+#[derive(Debug)]
+pub(crate) struct ScieBindingCmd<'a> {
+    pub(crate) binding: &'a str,
+    pub(crate) args: Vec<&'a str>,
+}
+
 #[cfg_attr(test, derive(Eq, PartialEq))]
 #[derive(Debug)]
 pub(crate) struct ScieBindingEnv<'a> {
     pub(crate) binding: &'a str,
+    pub(crate) args: Vec<&'a str>,
     pub(crate) env: &'a str,
 }
 
@@ -17,12 +25,16 @@ pub(crate) enum Placeholder<'a> {
     Scie,
     ScieBase,
     ScieBindings,
-    ScieBindingCmd(&'a str),
+    ScieBindingCmd(ScieBindingCmd<'a>),
     ScieBindingEnv(ScieBindingEnv<'a>),
+    ScieDir,
+    ScieFilesCount,
     ScieLift,
+    SciePid,
     SciePlatform,
     SciePlatformArch,
     SciePlatformOs,
+    ScieSizeTotal,
 }
 
 #[cfg_attr(test, derive(Eq, PartialEq))]
@@ -39,6 +51,111 @@ pub(crate) struct Parsed<'a> {
     pub items: Vec<Item<'a>>,
 }
 
+// A binding can be passed arguments like so: `name(arg1,arg2)`. The argument list must precede any
+// `:ENV` selecting an env var the binding exported.
+fn parse_binding(binding: &str) -> Result<(&str, Vec<&str>, Option<&str>), String> {
+    let colon = binding.find(':');
+    let Some(open) = binding
+        .find('(')
+        .filter(|open| colon.map(|colon| *open < colon).unwrap_or(true))
+    else {
+        return Ok(match binding.split_once(':') {
+            Some((name, env)) => (name, vec![], Some(env)),
+            None => (binding, vec![], None),
+        });
+    };
+    let close = binding[open..]
+        .find(')')
+        .map(|close| open + close)
+        .ok_or_else(|| {
+            format!("The binding placeholder {binding} has an unterminated argument list.")
+        })?;
+    let name = &binding[..open];
+    let args = match &binding[open + 1..close] {
+        "" => vec![],
+        args => args.split(',').collect(),
+    };
+    let env = match &binding[close + 1..] {
+        "" => None,
+        rest => Some(rest.strip_prefix(':').ok_or_else(|| {
+            format!(
+                "The binding placeholder {binding} has unexpected text after its argument list: \
+                {rest}"
+            )
+        })?),
+    };
+    Ok((name, args, env))
+}
+
+// The `scie.` placeholders that take no trailing name.
+const SCIE_PLACEHOLDERS: [&str; 11] = [
+    "scie",
+    "scie.base",
+    "scie.bindings",
+    "scie.dir",
+    "scie.files.count",
+    "scie.lift",
+    "scie.pid",
+    "scie.platform",
+    "scie.platform.arch",
+    "scie.platform.os",
+    "scie.size.total",
+];
+
+// The `scie.` placeholders that take a trailing name; e.g.: `scie.env.PATH`.
+const SCIE_PREFIXES: [&str; 4] = [
+    "scie.bindings.",
+    "scie.env.",
+    "scie.files.",
+    "scie.files:hash.",
+];
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Explains that the given file name is likely a misspelled `scie.` placeholder; so users are not
+/// left puzzling over a missing file.
+///
+/// Returns `None` if the name does not start with `scie.`.
+pub(crate) fn unknown_scie_placeholder(name: &str) -> Option<String> {
+    if !name.starts_with("scie.") {
+        return None;
+    }
+    let mut candidates = SCIE_PLACEHOLDERS
+        .iter()
+        .map(|placeholder| (edit_distance(name, placeholder), placeholder.to_string()))
+        .collect::<Vec<_>>();
+    if let Some((head, rest)) = name
+        .match_indices('.')
+        .nth(1)
+        .map(|(index, _)| name.split_at(index + 1))
+    {
+        candidates.extend(
+            SCIE_PREFIXES
+                .iter()
+                .map(|prefix| (edit_distance(head, prefix), format!("{prefix}{rest}"))),
+        );
+    }
+    let suggestion = candidates
+        .into_iter()
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, suggestion)| format!(", did you mean '{{{suggestion}}}'?"))
+        .unwrap_or_else(|| format!(" and no file named {name} is stored in this scie."));
+    Some(format!("Unknown scie placeholder '{{{name}}}'{suggestion}"))
+}
+
 pub(crate) fn parse(text: &str) -> Result<Parsed, String> {
     let mut items = vec![];
 
@@ -89,17 +206,21 @@ pub(crate) fn parse(text: &str) -> Result<Parsed, String> {
                     ["scie", "bindings"] => {
                         items.push(Item::Placeholder(Placeholder::ScieBindings))
                     }
-                    ["scie", "bindings", binding] => {
-                        match binding.splitn(2, ':').collect::<Vec<_>>()[..] {
-                            [name, env] => items.push(Item::Placeholder(
-                                Placeholder::ScieBindingEnv(ScieBindingEnv { binding: name, env }),
-                            )),
-                            _ => {
-                                items.push(Item::Placeholder(Placeholder::ScieBindingCmd(binding)))
-                            }
-                        }
-                    }
+                    ["scie", "bindings", binding] => match parse_binding(binding)? {
+                        (binding, args, Some(env)) => items.push(Item::Placeholder(
+                            Placeholder::ScieBindingEnv(ScieBindingEnv { binding, args, env }),
+                        )),
+                        (binding, args, None) => items.push(Item::Placeholder(
+                            Placeholder::ScieBindingCmd(ScieBindingCmd { binding, args }),
+                        )),
+                    },
+                    ["scie", "dir"] => items.push(Item::Placeholder(Placeholder::ScieDir)),
                     ["scie", "env", env] => items.push(Item::Placeholder(Placeholder::Env(env))),
+                    // N.B.: This must precede the general file match below; so a file named
+                    // `count` can only be referred to via `{count}`.
+                    ["scie", "files", "count"] => {
+                        items.push(Item::Placeholder(Placeholder::ScieFilesCount))
+                    }
                     ["scie", "files", name] => {
                         items.push(Item::Placeholder(Placeholder::FileName(name)))
                     }
@@ -107,6 +228,7 @@ pub(crate) fn parse(text: &str) -> Result<Parsed, String> {
                         items.push(Item::Placeholder(Placeholder::FileHash(name)))
                     }
                     ["scie", "lift"] => items.push(Item::Placeholder(Placeholder::ScieLift)),
+                    ["scie", "pid"] => items.push(Item::Placeholder(Placeholder::SciePid)),
                     ["scie", "platform"] => {
                         items.push(Item::Placeholder(Placeholder::SciePlatform))
                     }
@@ -116,6 +238,9 @@ pub(crate) fn parse(text: &str) -> Result<Parsed, String> {
                     ["scie", "platform", "os"] => {
                         items.push(Item::Placeholder(Placeholder::SciePlatformOs))
                     }
+                    ["scie", "size", "total"] => {
+                        items.push(Item::Placeholder(Placeholder::ScieSizeTotal))
+                    }
                     _ => items.push(Item::Placeholder(Placeholder::FileName(symbol))),
                 }
                 previous_char = Some('}');
@@ -134,8 +259,8 @@ pub(crate) fn parse(text: &str) -> Result<Parsed, String> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse, Item, Placeholder};
-    use crate::placeholders::ScieBindingEnv;
+    use super::{parse, unknown_scie_placeholder, Item, Placeholder};
+    use crate::placeholders::{ScieBindingCmd, ScieBindingEnv};
 
     #[test]
     fn no_placeholders() {
@@ -186,6 +311,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scie_dir() {
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieDir)],
+            parse("{scie.dir}").unwrap().items
+        );
+        assert_eq!(
+            vec![
+                Item::Placeholder(Placeholder::ScieDir),
+                Item::Text("/config.toml")
+            ],
+            parse("{scie.dir}/config.toml").unwrap().items
+        );
+    }
+
     #[test]
     fn scie_bindings() {
         assert_eq!(
@@ -212,12 +352,20 @@ mod tests {
     #[test]
     fn scie_bindings_cmd() {
         assert_eq!(
-            vec![Item::Placeholder(Placeholder::ScieBindingCmd("do"))],
+            vec![Item::Placeholder(Placeholder::ScieBindingCmd(
+                ScieBindingCmd {
+                    binding: "do",
+                    args: vec![]
+                }
+            ))],
             parse("{scie.bindings.do}").unwrap().items
         );
         assert_eq!(
             vec![
-                Item::Placeholder(Placeholder::ScieBindingCmd("dotted.cmd.name")),
+                Item::Placeholder(Placeholder::ScieBindingCmd(ScieBindingCmd {
+                    binding: "dotted.cmd.name",
+                    args: vec![]
+                })),
                 Item::Text("/venv/pex"),
             ],
             parse("{scie.bindings.dotted.cmd.name}/venv/pex")
@@ -232,6 +380,7 @@ mod tests {
             vec![Item::Placeholder(Placeholder::ScieBindingEnv(
                 ScieBindingEnv {
                     binding: "do",
+                    args: vec![],
                     env: "FOO"
                 }
             ))],
@@ -241,6 +390,7 @@ mod tests {
             vec![
                 Item::Placeholder(Placeholder::ScieBindingEnv(ScieBindingEnv {
                     binding: "dotted.cmd.name",
+                    args: vec![],
                     env: "BAR"
                 })),
                 Item::Text("/venv/pex"),
@@ -251,6 +401,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scie_bindings_args() {
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieBindingCmd(
+                ScieBindingCmd {
+                    binding: "fetch",
+                    args: vec!["linux", "x86_64"]
+                }
+            ))],
+            parse("{scie.bindings.fetch(linux,x86_64)}").unwrap().items
+        );
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieBindingCmd(
+                ScieBindingCmd {
+                    binding: "fetch",
+                    args: vec![]
+                }
+            ))],
+            parse("{scie.bindings.fetch()}").unwrap().items
+        );
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieBindingEnv(
+                ScieBindingEnv {
+                    binding: "fetch",
+                    args: vec!["3.11"],
+                    env: "PYTHON"
+                }
+            ))],
+            parse("{scie.bindings.fetch(3.11):PYTHON}").unwrap().items
+        );
+        assert!(parse("{scie.bindings.fetch(3.11}").is_err());
+        assert!(parse("{scie.bindings.fetch(3.11)PYTHON}").is_err());
+    }
+
     #[test]
     fn scie_env() {
         assert_eq!(
@@ -314,6 +498,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stats() {
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieFilesCount)],
+            parse("{scie.files.count}").unwrap().items
+        );
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::FileName("files.count"))],
+            parse("{files.count}").unwrap().items
+        );
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::FileName("count.txt"))],
+            parse("{scie.files.count.txt}").unwrap().items
+        );
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::ScieSizeTotal)],
+            parse("{scie.size.total}").unwrap().items
+        );
+    }
+
+    #[test]
+    fn pid() {
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::SciePid)],
+            parse("{scie.pid}").unwrap().items,
+        );
+        assert_eq!(
+            vec![
+                Item::Text("/tmp/"),
+                Item::Placeholder(Placeholder::SciePid),
+                Item::Text(".lock")
+            ],
+            parse("/tmp/{scie.pid}.lock").unwrap().items,
+        );
+    }
+
     #[test]
     fn platform() {
         assert_eq!(
@@ -351,4 +571,36 @@ mod tests {
                 .items
         );
     }
+
+    #[test]
+    fn unknown_scie() {
+        assert_eq!(
+            vec![Item::Placeholder(Placeholder::FileName("scie.platfrm"))],
+            parse("{scie.platfrm}").unwrap().items,
+        );
+        assert_eq!(
+            Some(
+                "Unknown scie placeholder '{scie.platfrm}', did you mean '{scie.platform}'?"
+                    .to_string()
+            ),
+            unknown_scie_placeholder("scie.platfrm")
+        );
+        assert_eq!(
+            Some(
+                "Unknown scie placeholder '{scie.bindngs.configure}', did you mean \
+                '{scie.bindings.configure}'?"
+                    .to_string()
+            ),
+            unknown_scie_placeholder("scie.bindngs.configure")
+        );
+        assert_eq!(
+            Some(
+                "Unknown scie placeholder '{scie.jar}' and no file named scie.jar is stored in this \
+                scie."
+                    .to_string()
+            ),
+            unknown_scie_placeholder("scie.jar")
+        );
+        assert_eq!(None, unknown_scie_placeholder("node.tar.gz"));
+    }
 }
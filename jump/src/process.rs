@@ -1,10 +1,18 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::Path;
 use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use logging_timer::time;
 use sha2::{Digest, Sha256};
@@ -12,10 +20,220 @@ use sha2::{Digest, Sha256};
 use crate::comparable_regex::ComparableRegex;
 use crate::config::EnvVar as ConfigEnvVar;
 
+#[cfg(windows)]
+const PATHSEP: &str = ";";
+
+#[cfg(unix)]
+const PATHSEP: &str = ":";
+
+// The number of children we are currently waiting on.
+#[cfg(windows)]
+static WAITING: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(windows)]
+unsafe extern "system" fn ctrl_handler(ctrl_type: u32) -> windows_sys::Win32::Foundation::BOOL {
+    use std::sync::atomic::Ordering;
+
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    // N.B.: Our children share our console and so receive these events too. We leave it to them
+    // to decide whether to exit and then report their exit code as our own.
+    let handled =
+        matches!(ctrl_type, CTRL_C_EVENT | CTRL_BREAK_EVENT) && WAITING.load(Ordering::SeqCst) > 0;
+    i32::from(handled)
+}
+
+// Assigns the child to a job that kills it when the job handle is closed; which the OS does for
+// us should we be killed before the child exits.
+#[cfg(windows)]
+fn kill_on_close_job(child: &Child) -> std::io::Result<windows_sys::Win32::Foundation::HANDLE> {
+    use std::os::windows::io::AsRawHandle;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_SILENT_BREAKAWAY_OK,
+    };
+
+    let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+    if job == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+    // N.B.: The processes the child spawns are left out of the job; so daemons it launches outlive
+    // it just as they would on Unix.
+    info.BasicLimitInformation.LimitFlags =
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE | JOB_OBJECT_LIMIT_SILENT_BREAKAWAY_OK;
+    let assigned = unsafe {
+        SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const JOBOBJECT_EXTENDED_LIMIT_INFORMATION as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        ) != 0
+            && AssignProcessToJobObject(job, child.as_raw_handle() as HANDLE) != 0
+    };
+    if !assigned {
+        let err = std::io::Error::last_os_error();
+        unsafe { CloseHandle(job) };
+        return Err(err);
+    }
+    Ok(job)
+}
+
+// N.B.: Windows has no exec; so we stand in for the child until it exits. Console control events
+// are left for the child to handle and the child is killed should we be killed first.
+#[cfg(windows)]
+fn supervise<T, F>(child: &mut Child, await_exit: F) -> std::io::Result<T>
+where
+    F: FnOnce(&mut Child) -> std::io::Result<T>,
+{
+    use std::sync::atomic::Ordering;
+    use std::sync::Once;
+
+    use log::warn;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    static CTRL_HANDLER: Once = Once::new();
+    CTRL_HANDLER.call_once(|| {
+        if unsafe { SetConsoleCtrlHandler(Some(ctrl_handler), 1) } == 0 {
+            warn!(
+                "Failed to install a console control handler; so Ctrl-C may leave the child \
+                running: {err}",
+                err = std::io::Error::last_os_error()
+            );
+        }
+    });
+    let job = kill_on_close_job(child)
+        .map_err(|e| {
+            warn!(
+                "Failed to tie the lifetime of child process {pid} to this one: {e}",
+                pid = child.id()
+            )
+        })
+        .ok();
+    WAITING.fetch_add(1, Ordering::SeqCst);
+    let result = await_exit(child);
+    WAITING.fetch_sub(1, Ordering::SeqCst);
+    if let Some(job) = job {
+        unsafe { CloseHandle(job) };
+    }
+    result
+}
+
+#[cfg(unix)]
+fn supervise<T, F>(child: &mut Child, await_exit: F) -> std::io::Result<T>
+where
+    F: FnOnce(&mut Child) -> std::io::Result<T>,
+{
+    await_exit(child)
+}
+
+fn wait(child: &mut Child) -> std::io::Result<ExitStatus> {
+    supervise(child, Child::wait)
+}
+
+// The longest we sleep between checks on a child that must exit by a deadline.
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Waits for the child to exit, killing it should it still be running at the `deadline`; in which
+/// case `None` is returned.
+pub(crate) fn wait_deadline(
+    child: &mut Child,
+    deadline: Instant,
+) -> std::io::Result<Option<ExitStatus>> {
+    supervise(child, |child| {
+        let mut interval = Duration::from_millis(1);
+        loop {
+            if let Some(exit_status) = child.try_wait()? {
+                return Ok(Some(exit_status));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                child.kill()?;
+                child.wait()?;
+                return Ok(None);
+            }
+            std::thread::sleep(interval.min(deadline - now));
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        }
+    })
+}
+
+/// Kills a child should it still be running at a deadline.
+///
+/// Unlike `wait_deadline`, the deadline is enforced from a background thread; so it holds even
+/// while the child's output is still being consumed.
+pub(crate) struct Watchdog {
+    child: Arc<Mutex<Child>>,
+    deadline: Option<Instant>,
+    timed_out: Arc<AtomicBool>,
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn new(child: Child, deadline: Option<Instant>) -> Self {
+        let child = Arc::new(Mutex::new(child));
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let (cancel, thread) = if let Some(deadline) = deadline {
+            let (cancel, cancelled) = std::sync::mpsc::channel::<()>();
+            let watched = Arc::clone(&child);
+            let timed_out = Arc::clone(&timed_out);
+            let thread = std::thread::spawn(move || {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                    if let Ok(mut child) = watched.lock() {
+                        if let Ok(None) = child.try_wait() {
+                            timed_out.store(true, Ordering::SeqCst);
+                            let _ = child.kill();
+                        }
+                    }
+                }
+            });
+            (Some(cancel), Some(thread))
+        } else {
+            (None, None)
+        };
+        Self {
+            child,
+            deadline,
+            timed_out,
+            cancel,
+            thread,
+        }
+    }
+
+    /// Waits for the child to exit, returning `None` if it had to be killed at the deadline.
+    pub(crate) fn wait(mut self) -> std::io::Result<Option<ExitStatus>> {
+        // N.B.: Dropping the sender wakes the watchdog thread, which then exits.
+        self.cancel.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let mut child = self
+            .child
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Poisoned child lock."))?;
+        if self.timed_out.load(Ordering::SeqCst) {
+            child.wait()?;
+            return Ok(None);
+        }
+        match self.deadline {
+            Some(deadline) => wait_deadline(&mut child, deadline),
+            None => wait(&mut child).map(Some),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum EnvVar {
     Default((OsString, OsString)),
     Replace((OsString, OsString)),
+    Append((OsString, OsString)),
+    Prepend((OsString, OsString)),
     Remove(OsString),
     RemoveMatching(ComparableRegex),
 }
@@ -35,6 +253,18 @@ impl TryFrom<(&ConfigEnvVar, Option<String>)> for EnvVar {
                 ComparableRegex::try_from(name.as_str())?,
             )),
             (ConfigEnvVar::Replace(name), None) => Ok(Self::Remove(name.to_owned().into())),
+            (ConfigEnvVar::Append(name), Some(value)) => {
+                Ok(Self::Append((name.to_owned().into(), value.into())))
+            }
+            (ConfigEnvVar::Prepend(name), Some(value)) => {
+                Ok(Self::Prepend((name.to_owned().into(), value.into())))
+            }
+            (ConfigEnvVar::Append(name), None) => Err(format!(
+                "The env var +{name} must have a value to append to {name}."
+            )),
+            (ConfigEnvVar::Prepend(name), None) => Err(format!(
+                "The env var {name}+ must have a value to prepend to {name}."
+            )),
         }
     }
 }
@@ -45,12 +275,24 @@ pub struct EnvVars {
 }
 
 impl EnvVars {
-    // Translates this `EnvVars` into a sequence of env var set and env var remove instructions
-    // that, when carried out in order, will place the environment in the requested state.
-    fn to_env_vars(&self) -> Vec<(OsString, Option<OsString>)> {
+    /// Translates this `EnvVars` into a sequence of env var set and env var remove instructions
+    /// that, when carried out in order, will place the environment in the requested state.
+    pub fn to_env_vars(&self) -> Vec<(OsString, Option<OsString>)> {
+        self.to_env_vars_in(&env::vars_os().collect())
+    }
+
+    /// Like `to_env_vars`, but for when the environment starts out as the given `ambient_env`
+    /// instead of the process environment.
+    pub fn to_env_vars_in(
+        &self,
+        ambient_env: &HashMap<OsString, OsString>,
+    ) -> Vec<(OsString, Option<OsString>)> {
         let mut defaults = vec![];
         let mut replacements = vec![];
-        let mut removals: HashSet<OsString> = HashSet::new();
+        let mut extensions = vec![];
+        // N.B.: Removals are kept sorted so the same `EnvVars` always yields the same instructions
+        // regardless of the ordering of the ambient environment.
+        let mut removals: BTreeSet<OsString> = BTreeSet::new();
         for env_var in &self.vars {
             match env_var {
                 EnvVar::Default((name, val)) => {
@@ -59,13 +301,19 @@ impl EnvVars {
                 EnvVar::Replace((name, val)) => {
                     replacements.push((name.to_owned(), val.to_owned()));
                 }
+                EnvVar::Append((name, val)) => {
+                    extensions.push((name.to_owned(), val.to_owned(), true));
+                }
+                EnvVar::Prepend((name, val)) => {
+                    extensions.push((name.to_owned(), val.to_owned(), false));
+                }
                 EnvVar::Remove(name) => {
                     removals.insert(name.to_owned());
                 }
                 EnvVar::RemoveMatching(regex) => {
-                    for (name, _) in env::vars() {
-                        if regex.is_match(name.as_str()) {
-                            removals.insert(name.into());
+                    for name in ambient_env.keys() {
+                        if name.to_str().map(|name| regex.is_match(name)) == Some(true) {
+                            removals.insert(name.to_owned());
                         }
                     }
                 }
@@ -79,13 +327,39 @@ impl EnvVars {
             let value = if removals.contains(&name) {
                 default
             } else {
-                env::var_os(&name).unwrap_or(default)
+                ambient_env.get(&name).cloned().unwrap_or(default)
             };
             env_vars.push((name, Some(value)))
         }
         for (name, value) in replacements {
             env_vars.push((name, Some(value)))
         }
+        // Appends and prepends extend the value the env var would otherwise have.
+        for (name, value, append) in extensions {
+            let current = env_vars
+                .iter()
+                .rev()
+                .find(|(env_var_name, _)| env_var_name == &name)
+                .map(|(_, current)| current.clone())
+                .unwrap_or_else(|| ambient_env.get(&name).cloned());
+            let extended = match current {
+                Some(current) if !current.is_empty() => {
+                    let mut extended = OsString::new();
+                    if append {
+                        extended.push(current);
+                        extended.push(PATHSEP);
+                        extended.push(value);
+                    } else {
+                        extended.push(value);
+                        extended.push(PATHSEP);
+                        extended.push(current);
+                    }
+                    extended
+                }
+                _ => value,
+            };
+            env_vars.push((name, Some(extended)))
+        }
         env_vars
     }
 
@@ -99,14 +373,49 @@ impl EnvVars {
     }
 }
 
-pub fn execute(exe: OsString, args: Vec<OsString>, argv_skip: usize) -> Result<ExitStatus, String> {
-    Command::new(&exe)
-        .args(&args)
-        .args(env::args().skip(argv_skip))
-        .spawn()
-        .map_err(|e| format!("Failed to spawn {exe:?} {args:?}: {e}"))?
-        .wait()
-        .map_err(|e| format!("Spawned {exe:?} {args:?} but failed to gather its exit status: {e}"))
+/// Opens the given path for appending process output to, creating it and any missing parent
+/// directories as needed.
+pub fn open_output(path: &OsStr) -> Result<File, String> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            format!(
+                "Failed to create parent directory of output file {path}: {e}",
+                path = path.display()
+            )
+        })?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            format!(
+                "Failed to open output file {path}: {e}",
+                path = path.display()
+            )
+        })
+}
+
+pub fn execute(process: Process, argv_skip: usize) -> Result<ExitStatus, String> {
+    let mut command = Command::new(&process.exe);
+    command.args(process.argv(env::args_os().skip(argv_skip)));
+    process.redirect(&mut command)?;
+    process.inherit_fds()?;
+    let mut child = command.spawn().map_err(|e| {
+        format!(
+            "Failed to spawn {exe:?} {args:?}: {e}",
+            exe = process.exe,
+            args = process.args
+        )
+    })?;
+    wait(&mut child).map_err(|e| {
+        format!(
+            "Spawned {exe:?} {args:?} but failed to gather its exit status: {e}",
+            exe = process.exe,
+            args = process.args
+        )
+    })
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -114,6 +423,10 @@ pub struct Process {
     pub env: EnvVars,
     pub exe: OsString,
     pub args: Vec<OsString>,
+    pub args_append: Vec<OsString>,
+    pub stdout: Option<OsString>,
+    pub stderr: Option<OsString>,
+    pub inherit_fds: Vec<i32>,
 }
 
 fn as_bytes(os_string: &OsString) -> Result<Vec<u8>, String> {
@@ -125,6 +438,17 @@ fn as_bytes(os_string: &OsString) -> Result<Vec<u8>, String> {
 }
 
 impl Process {
+    /// The args to execute the process with given the args passed through from the user; these
+    /// follow the process's own args and precede its appended args.
+    pub fn argv(&self, passthrough_args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+        self.args
+            .iter()
+            .cloned()
+            .chain(passthrough_args)
+            .chain(self.args_append.iter().cloned())
+            .collect()
+    }
+
     #[time("debug", "Process::{}")]
     pub(crate) fn fingerprint(&self) -> Result<String, String> {
         let mut hasher = Sha256::new_with_prefix(as_bytes(&self.exe)?);
@@ -137,12 +461,69 @@ impl Process {
                 hasher.update(as_bytes(&val)?);
             }
         }
+        // N.B.: We only mix in output redirections when present to keep fingerprints stable for
+        // processes that don't use them.
+        if let Some(stdout) = &self.stdout {
+            hasher.update(b"stdout");
+            hasher.update(as_bytes(stdout)?);
+        }
+        if let Some(stderr) = &self.stderr {
+            hasher.update(b"stderr");
+            hasher.update(as_bytes(stderr)?);
+        }
+        for arg in &self.args_append {
+            hasher.update(b"args_append");
+            hasher.update(as_bytes(arg)?);
+        }
+        for fd in &self.inherit_fds {
+            hasher.update(b"inherit_fd");
+            hasher.update(fd.to_le_bytes());
+        }
         Ok(format!("{digest:x}", digest = hasher.finalize()))
     }
 
-    fn as_command(&self) -> Command {
+    /// Ensures the file descriptors the process should inherit will be inherited by clearing their
+    /// close-on-exec flags.
+    #[cfg(unix)]
+    pub fn inherit_fds(&self) -> Result<(), String> {
+        use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+        for fd in &self.inherit_fds {
+            let flags = fcntl(*fd, FcntlArg::F_GETFD)
+                .map_err(|e| format!("Failed to read the flags of fd {fd} to inherit: {e}"))?;
+            let mut flags = FdFlag::from_bits_truncate(flags);
+            flags.remove(FdFlag::FD_CLOEXEC);
+            fcntl(*fd, FcntlArg::F_SETFD(flags))
+                .map_err(|e| format!("Failed to mark fd {fd} as inheritable: {e}"))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn inherit_fds(&self) -> Result<(), String> {
+        if self.inherit_fds.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Inheriting file descriptors is only supported on unix. Cannot inherit {fds:?}.",
+                fds = self.inherit_fds
+            ))
+        }
+    }
+
+    fn redirect(&self, command: &mut Command) -> Result<(), String> {
+        if let Some(stdout) = &self.stdout {
+            command.stdout(Stdio::from(open_output(stdout)?));
+        }
+        if let Some(stderr) = &self.stderr {
+            command.stderr(Stdio::from(open_output(stderr)?));
+        }
+        Ok(())
+    }
+
+    fn as_command(&self) -> Result<Command, String> {
         let mut command = Command::new(&self.exe);
-        command.args(&self.args);
+        command.args(self.argv(std::iter::empty()));
         for (name, value) in self.env.to_env_vars() {
             match value {
                 Some(val) => {
@@ -153,36 +534,93 @@ impl Process {
                 }
             }
         }
-        command
+        self.redirect(&mut command)?;
+        self.inherit_fds()?;
+        Ok(command)
     }
 
     pub fn execute(
         &self,
         extra_env: impl IntoIterator<Item = (OsString, OsString)>,
     ) -> Result<ExitStatus, String> {
-        self.as_command()
-            .envs(extra_env)
-            .spawn()
-            .map_err(|e| {
-                format!(
-                    "Failed to spawn {exe:?} {args:?}: {e}",
-                    exe = self.exe,
-                    args = self.args
-                )
-            })?
-            .wait()
-            .map_err(|e| {
-                format!(
-                    "Spawned process with {exe:?} {args:?} but failed to gather its exit \
-                    status: {e}",
-                    exe = self.exe,
-                    args = self.args
-                )
+        let mut child = self.as_command()?.envs(extra_env).spawn().map_err(|e| {
+            format!(
+                "Failed to spawn {exe:?} {args:?}: {e}",
+                exe = self.exe,
+                args = self.args
+            )
+        })?;
+        wait(&mut child).map_err(|e| {
+            format!(
+                "Spawned process with {exe:?} {args:?} but failed to gather its exit \
+                status: {e}",
+                exe = self.exe,
+                args = self.args
+            )
+        })
+    }
+
+    /// Executes the process, capturing its stderr if `capture_stderr` is set and the process does
+    /// not already redirect it.
+    ///
+    /// If the process is still running after the `timeout`, it is killed and `None` is returned.
+    pub(crate) fn execute_timeout(
+        &self,
+        extra_env: impl IntoIterator<Item = (OsString, OsString)>,
+        capture_stderr: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<(ExitStatus, Vec<u8>)>, String> {
+        let mut command = self.as_command()?;
+        command.envs(extra_env);
+        if capture_stderr && self.stderr.is_none() {
+            command.stderr(Stdio::piped());
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut child = command.spawn().map_err(|e| {
+            format!(
+                "Failed to spawn {exe:?} {args:?}: {e}",
+                exe = self.exe,
+                args = self.args
+            )
+        })?;
+        // N.B.: We drain stderr as the process runs lest it block writing to a full pipe.
+        let stderr_reader = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut buffer = vec![];
+                stderr.read_to_end(&mut buffer).map(|_| buffer)
             })
+        });
+        let exit_status = match deadline {
+            Some(deadline) => wait_deadline(&mut child, deadline),
+            None => wait(&mut child).map(Some),
+        }
+        .map_err(|e| {
+            format!(
+                "Spawned process with {exe:?} {args:?} but failed to gather its exit \
+                status: {e}",
+                exe = self.exe,
+                args = self.args
+            )
+        })?;
+        // N.B.: A process that timed out may have left children holding its stderr open; so we
+        // don't wait on its stderr.
+        let Some(exit_status) = exit_status else {
+            return Ok(None);
+        };
+        let stderr = match stderr_reader {
+            Some(stderr_reader) => stderr_reader
+                .join()
+                .map_err(|_| format!("Failed to read the stderr of {exe:?}.", exe = self.exe))?
+                .map_err(|e| {
+                    format!("Failed to read the stderr of {exe:?}: {e}", exe = self.exe)
+                })?,
+            None => vec![],
+        };
+        Ok(Some((exit_status, stderr)))
     }
 
     pub fn spawn_stdout(&self, args: &[&str]) -> Result<Child, String> {
-        self.as_command()
+        self.as_command()?
             .args(args)
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
@@ -196,3 +634,162 @@ impl Process {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{EnvVar, EnvVars, Process, PATHSEP};
+    use crate::config::EnvVar as ConfigEnvVar;
+
+    #[cfg(unix)]
+    #[test]
+    fn redirect() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let stdout = tempdir.path().join("stdout.txt");
+        let stderr = tempdir.path().join("logs").join("stderr.txt");
+        let process = Process {
+            env: EnvVars { vars: vec![] },
+            exe: "sh".into(),
+            args: vec!["-c".into(), "echo out; echo err >&2".into()],
+            args_append: vec![],
+            stdout: Some(stdout.clone().into()),
+            stderr: Some(stderr.clone().into()),
+            inherit_fds: vec![],
+        };
+        assert!(process.execute(std::iter::empty()).unwrap().success());
+        assert_eq!("out\n", std::fs::read_to_string(&stdout).unwrap());
+        assert_eq!("err\n", std::fs::read_to_string(&stderr).unwrap());
+
+        assert!(process.execute(std::iter::empty()).unwrap().success());
+        assert_eq!("out\nout\n", std::fs::read_to_string(&stdout).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inherit_fds() {
+        use nix::fcntl::OFlag;
+        use nix::unistd::{close, pipe2, write};
+
+        let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC).unwrap();
+        write(write_fd, b"inherited").unwrap();
+        close(write_fd).unwrap();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let stdout = tempdir.path().join("stdout.txt");
+        let process = Process {
+            env: EnvVars { vars: vec![] },
+            exe: "sh".into(),
+            args: vec!["-c".into(), format!("cat <&{read_fd}").into()],
+            args_append: vec![],
+            stdout: Some(stdout.clone().into()),
+            stderr: None,
+            inherit_fds: vec![read_fd],
+        };
+        assert!(process.execute(std::iter::empty()).unwrap().success());
+        assert_eq!("inherited", std::fs::read_to_string(&stdout).unwrap());
+        close(read_fd).unwrap();
+    }
+
+    #[test]
+    fn append_prepend() {
+        let env_vars = EnvVars {
+            vars: vec![
+                EnvVar::Append(("__APPEND__".into(), "/appended".into())),
+                EnvVar::Prepend(("__PREPEND__".into(), "/prepended".into())),
+            ],
+        };
+
+        assert_eq!(
+            vec![
+                ("__APPEND__".into(), Some("/appended".into())),
+                ("__PREPEND__".into(), Some("/prepended".into())),
+            ],
+            env_vars.to_env_vars_in(&HashMap::new())
+        );
+
+        let ambient_env = vec![
+            ("__APPEND__".into(), "/ambient".into()),
+            ("__PREPEND__".into(), "/ambient".into()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            vec![
+                (
+                    "__APPEND__".into(),
+                    Some(format!("/ambient{PATHSEP}/appended").into())
+                ),
+                (
+                    "__PREPEND__".into(),
+                    Some(format!("/prepended{PATHSEP}/ambient").into())
+                ),
+            ],
+            env_vars.to_env_vars_in(&ambient_env)
+        );
+
+        let env_vars = EnvVars {
+            vars: vec![
+                EnvVar::Replace(("__EXTENDED__".into(), "/replaced".into())),
+                EnvVar::Append(("__EXTENDED__".into(), "/appended".into())),
+                EnvVar::Prepend(("__EXTENDED__".into(), "/prepended".into())),
+            ],
+        };
+        assert_eq!(
+            Some(&(
+                "__EXTENDED__".into(),
+                Some(format!("/prepended{PATHSEP}/replaced{PATHSEP}/appended").into())
+            )),
+            env_vars.to_env_vars_in(&HashMap::new()).last()
+        );
+    }
+
+    #[test]
+    fn prepend_removal() {
+        let err = EnvVar::try_from((&ConfigEnvVar::Prepend("PATH".to_string()), None)).unwrap_err();
+        assert_eq!(
+            "The env var PATH+ must have a value to prepend to PATH.",
+            err
+        );
+
+        let env_vars = EnvVars {
+            vars: vec![
+                EnvVar::try_from((&ConfigEnvVar::Default("BASH_.+".to_string()), None)).unwrap(),
+            ],
+        };
+        let ambient_env = vec![
+            ("BASH_SOURCE".into(), "source".into()),
+            ("BASH_".into(), "bare".into()),
+            ("PATH".into(), "/bin".into()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            vec![("BASH_SOURCE".into(), None)],
+            env_vars.to_env_vars_in(&ambient_env)
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let env_vars = EnvVars {
+            vars: vec![
+                EnvVar::Remove("__ZZZ__".into()),
+                EnvVar::Default(("__DEFAULT__".into(), "default".into())),
+                EnvVar::Remove("__AAA__".into()),
+                EnvVar::Replace(("__REPLACE__".into(), "replace".into())),
+                EnvVar::Remove("__MMM__".into()),
+            ],
+        };
+        let expected: Vec<(std::ffi::OsString, Option<std::ffi::OsString>)> = vec![
+            ("__AAA__".into(), None),
+            ("__MMM__".into(), None),
+            ("__ZZZ__".into(), None),
+            ("__DEFAULT__".into(), Some("default".into())),
+            ("__REPLACE__".into(), Some("replace".into())),
+        ];
+        for _ in 0..3 {
+            assert_eq!(expected, env_vars.to_env_vars_in(&HashMap::new()));
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+//! Fixtures shared by the unit tests of this crate.
+
+use crate::config::Boot;
+use crate::{fingerprint, File, Lift};
+
+/// A lift with no files or commands that tests fill in with struct update syntax.
+pub(crate) fn lift() -> Lift {
+    Lift {
+        name: "test".to_string(),
+        description: None,
+        base: None,
+        load_dotenv: false,
+        dotenv_files: vec![],
+        extract_by_hash: false,
+        zstd_dictionary: None,
+        min_jump_version: None,
+        base_binding: None,
+        commands_file: None,
+        env_file: None,
+        relative_base: false,
+        dev: false,
+        shared_bindings: false,
+        size: 137,
+        hash: "abc".to_string(),
+        boot: Boot::default(),
+        files: vec![],
+        other: None,
+    }
+}
+
+/// A blob stored in the scie with the given `contents`.
+pub(crate) fn blob(name: &str, contents: &[u8]) -> File {
+    File {
+        name: name.to_string(),
+        size: contents.len(),
+        hash: fingerprint::digest(contents),
+        ..File::default()
+    }
+}
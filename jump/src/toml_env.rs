@@ -0,0 +1,229 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+//! Parses the flat TOML tables used as env files.
+//!
+//! Env files only hold top-level keys with scalar values; so instead of growing the scie-jump
+//! binary with a full TOML parser, just that subset of TOML is handled here and the rest rejected.
+
+use std::collections::BTreeMap;
+
+/// Parses the top-level keys of the TOML document in `contents`.
+///
+/// Scalar values are returned in their string form and arrays and inline tables as `None`.
+pub(crate) fn parse(contents: &str) -> Result<BTreeMap<String, Option<String>>, String> {
+    let mut table = BTreeMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = parse_line(line).map_err(|e| format!("Line {}: {e}", index + 1))?;
+        if table.insert(key.clone(), value).is_some() {
+            return Err(format!(
+                "Line {}: The key {key} is defined twice.",
+                index + 1
+            ));
+        }
+    }
+    Ok(table)
+}
+
+fn parse_line(line: &str) -> Result<(String, Option<String>), String> {
+    if line.starts_with('[') {
+        return Err(format!(
+            "Only top-level keys are supported but found the table {line}"
+        ));
+    }
+    let (key, rest) = parse_key(line)?;
+    let rest = rest.trim_start();
+    if rest.starts_with('.') {
+        return Err(format!(
+            "Only top-level keys are supported but found the dotted key {line}"
+        ));
+    }
+    let value = rest
+        .strip_prefix('=')
+        .ok_or_else(|| format!("Expected an `=` after the key {key}."))?;
+    let value = parse_value(value.trim_start())?;
+    Ok((key, value))
+}
+
+fn parse_key(line: &str) -> Result<(String, &str), String> {
+    if let Some(rest) = line.strip_prefix('"') {
+        parse_basic_string(rest)
+    } else if let Some(rest) = line.strip_prefix('\'') {
+        parse_literal_string(rest)
+    } else {
+        let end = line
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(line.len());
+        if end == 0 {
+            return Err(format!("Expected a key but found {line}"));
+        }
+        Ok((line[..end].to_string(), &line[end..]))
+    }
+}
+
+fn parse_value(value: &str) -> Result<Option<String>, String> {
+    if value.starts_with("\"\"\"") || value.starts_with("'''") {
+        return Err("Multi-line strings are not supported.".to_string());
+    }
+    let (parsed, rest) = if let Some(rest) = value.strip_prefix('"') {
+        parse_basic_string(rest)?
+    } else if let Some(rest) = value.strip_prefix('\'') {
+        parse_literal_string(rest)?
+    } else if value.starts_with('[') || value.starts_with('{') {
+        // Arrays and inline tables are not scalars.
+        return Ok(None);
+    } else {
+        let end = value.find('#').unwrap_or(value.len());
+        (parse_bare_value(value[..end].trim_end())?, &value[end..])
+    };
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("Unexpected text after the value: {rest}"));
+    }
+    Ok(Some(parsed))
+}
+
+fn parse_basic_string(rest: &str) -> Result<(String, &str), String> {
+    let mut string = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &rest[index + 1..])),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('b') => '\u{8}',
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('f') => '\u{c}',
+                    Some('r') => '\r',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some(unicode @ ('u' | 'U')) => {
+                        let digits = if unicode == 'u' { 4 } else { 8 };
+                        let hex = (0..digits)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<String>();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == digits)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid unicode escape \\{unicode}{hex}"))?
+                    }
+                    Some(c) => return Err(format!("Invalid escape \\{c}")),
+                    None => break,
+                };
+                string.push(escaped);
+            }
+            c => string.push(c),
+        }
+    }
+    Err(format!("Unterminated string \"{rest}"))
+}
+
+fn parse_literal_string(rest: &str) -> Result<(String, &str), String> {
+    let end = rest
+        .find('\'')
+        .ok_or_else(|| format!("Unterminated string '{rest}"))?;
+    Ok((rest[..end].to_string(), &rest[end + 1..]))
+}
+
+fn parse_bare_value(value: &str) -> Result<String, String> {
+    if value == "true" || value == "false" {
+        return Ok(value.to_string());
+    }
+    let digits = value.replace('_', "");
+    let (sign, unsigned) = match digits.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", digits.strip_prefix('+').unwrap_or(&digits)),
+    };
+    let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| unsigned.strip_prefix(prefix).map(|rest| (rest, radix)));
+    if let Some((rest, radix)) = radix {
+        if sign.is_empty() {
+            if let Ok(integer) = i64::from_str_radix(rest, radix) {
+                return Ok(integer.to_string());
+            }
+        }
+    } else if let Ok(integer) = format!("{sign}{unsigned}").parse::<i64>() {
+        return Ok(integer.to_string());
+    } else if let Some(float) = unsigned
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| digits.parse::<f64>().ok())
+        .flatten()
+    {
+        return Ok(float.to_string());
+    } else if value.starts_with(|c: char| c.is_ascii_digit())
+        && value.contains(|c| c == '-' || c == ':')
+    {
+        // A date, time or date-time, which we pass through as written.
+        return Ok(value.to_string());
+    }
+    Err(format!(
+        "Expected a string, number, boolean or date-time value but found {value}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::parse;
+
+    #[test]
+    fn scalars() {
+        let table = parse(
+            r#"
+            # Configures the app.
+            JAVA_HOME = "/opt/java" # The JDK.
+            'LITERAL' = 'C:\Users'
+            "QUOTED KEY" = "tab\tquote\"\u00e9"
+            PORT = 8_080
+            OFFSET = -42
+            MASK = 0o755
+            RATIO = 0.5
+            VERBOSE = true
+            RELEASED = 1979-05-27T07:32:00Z
+            PATH = ["/usr/bin"]
+            "#,
+        )
+        .unwrap();
+        let expected = vec![
+            ("JAVA_HOME", Some("/opt/java")),
+            ("LITERAL", Some("C:\\Users")),
+            ("QUOTED KEY", Some("tab\tquote\"\u{e9}")),
+            ("PORT", Some("8080")),
+            ("OFFSET", Some("-42")),
+            ("MASK", Some("493")),
+            ("RATIO", Some("0.5")),
+            ("VERBOSE", Some("true")),
+            ("RELEASED", Some("1979-05-27T07:32:00Z")),
+            ("PATH", None),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.map(str::to_string)))
+        .collect::<BTreeMap<_, _>>();
+        assert_eq!(expected, table);
+    }
+
+    #[test]
+    fn unsupported() {
+        let err = parse("[app]\nPORT = 8080\n").unwrap_err();
+        assert!(err.starts_with("Line 1: Only top-level keys"), "{err}");
+        let err = parse("app.port = 8080\n").unwrap_err();
+        assert!(err.contains("dotted key"), "{err}");
+        let err = parse("A = 1\nA = 2\n").unwrap_err();
+        assert_eq!("Line 2: The key A is defined twice.", err);
+        assert!(parse("A = \"\"\"multi\nline\"\"\"").is_err());
+        assert!(parse("A = \"unterminated").is_err());
+        assert!(parse("A = bare").is_err());
+        // N.B.: TOML only allows signs on decimal integers.
+        assert!(parse("A = -0x10").is_err());
+        assert!(parse("A = 1 2").is_err());
+        assert!(parse("A").is_err());
+    }
+}
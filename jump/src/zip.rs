@@ -2,41 +2,78 @@
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
 use std::cmp::min;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 
-use itertools::Itertools;
+use byteorder::{LittleEndian, ReadBytesExt};
 
 // See "4.3.6 Overall .ZIP file format:" and "4.3.16  End of central directory record:"
 // in https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT for Zip file format facts
 // leveraged here.
 
-const EOCD_SIGNATURE: (&u8, &u8, &u8, &u8) = (&0x06, &0x05, &0x4b, &0x50);
+const EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
 const EOCD_MIN_SIZE: usize = 22;
 const EOCD_MAX_SIZE: usize = EOCD_MIN_SIZE + u16::MAX as usize;
 
-pub(crate) fn end_of_zip(data: &[u8], maximum_trailer_size: usize) -> Result<usize, String> {
+// See "4.3.14  Zip64 end of central directory record:" and "4.3.15 Zip64 end of central directory
+// locator:" in the same document.
+
+const ZIP64_EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x06];
+const ZIP64_EOCD_MIN_SIZE: usize = 56;
+const ZIP64_EOCD_LOCATOR_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x07];
+const ZIP64_EOCD_LOCATOR_SIZE: usize = 20;
+
+/// Finds the start of the last end of central directory record signature that begins within
+/// `max_scan` bytes of the latest position such a record could start.
+///
+/// The scan starts with a window just large enough to hold a minimal record and doubles the
+/// window size on each miss; so the common case of a record at or near the end of `data` only
+/// examines a handful of bytes.
+fn find_eocd(data: &[u8], max_scan: usize) -> Option<usize> {
+    let max_signature_position = data.len().checked_sub(EOCD_MIN_SIZE)? + EOCD_SIGNATURE.len();
+    let scan_floor = max_signature_position.saturating_sub(max_scan);
+    let mut window = EOCD_MIN_SIZE;
+    let mut high = max_signature_position;
+    loop {
+        let low = high.saturating_sub(window).max(scan_floor);
+        // N.B.: We overlap the prior window by all but one byte of the signature to catch
+        // signatures straddling the window boundary.
+        let end = min(high + EOCD_SIGNATURE.len() - 1, max_signature_position);
+        if let Some(position) = data[low..end]
+            .windows(EOCD_SIGNATURE.len())
+            .rposition(|chunk| chunk == EOCD_SIGNATURE)
+        {
+            return Some(low + position);
+        }
+        if low == scan_floor {
+            return None;
+        }
+        high = low;
+        window *= 2;
+    }
+}
+
+// The facts about a zip recorded in its end of central directory record, with offsets relative to
+// the start of the data the record was found in.
+struct EndOfCentralDirectory {
+    start: usize,
+    end: usize,
+    cd_size: usize,
+    cd_offset: usize,
+}
+
+fn read_eocd(data: &[u8], maximum_trailer_size: usize) -> Result<EndOfCentralDirectory, String> {
     #[allow(clippy::too_many_arguments)]
     let eocd_struct = structure!("<4sHHHHIIH");
     debug_assert!(EOCD_MIN_SIZE == eocd_struct.size());
 
     let max_scan = EOCD_MAX_SIZE + maximum_trailer_size;
-    let max_signature_position = data.len() - EOCD_MIN_SIZE + 4;
-
-    let offset_from_eof = EOCD_MIN_SIZE
-        + data[..max_signature_position]
-            .iter()
-            .rev()
-            .take(max_scan)
-            .tuple_windows::<(_, _, _, _)>()
-            .position(|chunk| EOCD_SIGNATURE == chunk)
-            .ok_or_else(|| {
-                format!(
-                "Failed to find application zip end of central directory record within the last \
-                {max_scan} bytes of the file. Invalid NCE."
-            )
-            })?;
-    let eocd_start = data.len() - offset_from_eof;
+    let eocd_start = find_eocd(data, max_scan).ok_or_else(|| {
+        format!(
+            "Failed to find application zip end of central directory record within the last \
+            {max_scan} bytes of the file. Invalid NCE."
+        )
+    })?;
     let eocd_end = eocd_start + EOCD_MIN_SIZE;
     let (
         _signature,
@@ -44,8 +81,8 @@ pub(crate) fn end_of_zip(data: &[u8], maximum_trailer_size: usize) -> Result<usi
         _cd_disk_no,
         _disk_cd_record_count,
         _total_cd_record_count,
-        _cd_size,
-        _cd_offset,
+        cd_size,
+        cd_offset,
         zip_comment_size,
     ) = eocd_struct
         .unpack(&data[eocd_start..eocd_end])
@@ -54,10 +91,71 @@ pub(crate) fn end_of_zip(data: &[u8], maximum_trailer_size: usize) -> Result<usi
                 "Invalid end of central directory record found starting at byte {eocd_start}: {e}"
             )
         })?;
-    Ok(eocd_end + (zip_comment_size as usize))
+    Ok(EndOfCentralDirectory {
+        start: eocd_start,
+        end: eocd_end + (zip_comment_size as usize),
+        cd_size: cd_size as usize,
+        cd_offset: cd_offset as usize,
+    })
 }
 
-pub fn check_is_zip(path: &Path) -> Result<(), String> {
+// The facts about a zip64 recorded in its zip64 end of central directory record, with its start
+// relative to the start of the data the record was found in.
+struct Zip64EndOfCentralDirectory {
+    start: usize,
+    cd_size: u64,
+    cd_offset: u64,
+}
+
+// N.B.: Only a zip64 end of central directory record with no extensible data that immediately
+// precedes its locator is supported; this is what zip writers produce.
+fn read_zip64_eocd(
+    data: &[u8],
+    eocd: &EndOfCentralDirectory,
+) -> Result<Option<Zip64EndOfCentralDirectory>, String> {
+    let Some(locator_start) = eocd.start.checked_sub(ZIP64_EOCD_LOCATOR_SIZE) else {
+        return Ok(None);
+    };
+    if data[locator_start..locator_start + ZIP64_EOCD_LOCATOR_SIGNATURE.len()]
+        != ZIP64_EOCD_LOCATOR_SIGNATURE
+    {
+        return Ok(None);
+    }
+    let start = locator_start
+        .checked_sub(ZIP64_EOCD_MIN_SIZE)
+        .filter(|start| data[*start..*start + ZIP64_EOCD_SIGNATURE.len()] == ZIP64_EOCD_SIGNATURE)
+        .ok_or_else(|| {
+            format!(
+                "Failed to find the zip64 end of central directory record immediately preceding \
+                its locator at byte {locator_start}."
+            )
+        })?;
+    // The central directory size and offset are the last two fields of the record.
+    let mut fields = Cursor::new(&data[start + 40..locator_start]);
+    let cd_size = fields.read_u64::<LittleEndian>();
+    let cd_offset = fields.read_u64::<LittleEndian>();
+    match (cd_size, cd_offset) {
+        (Ok(cd_size), Ok(cd_offset)) => Ok(Some(Zip64EndOfCentralDirectory {
+            start,
+            cd_size,
+            cd_offset,
+        })),
+        (Err(e), _) | (_, Err(e)) => Err(format!(
+            "Invalid zip64 end of central directory record found starting at byte {start}: {e}"
+        )),
+    }
+}
+
+pub(crate) fn end_of_zip(data: &[u8], maximum_trailer_size: usize) -> Result<usize, String> {
+    read_eocd(data, maximum_trailer_size).map(|eocd| eocd.end)
+}
+
+/// Returns the byte range, `[start, end)`, the zip ending the file at `path` occupies.
+///
+/// The zip need not start at the beginning of the file; e.g.: the zip of a scie follows the
+/// scie-jump tip. Only zips whose central directory immediately precedes their end of central
+/// directory record, or their zip64 end of central directory record, are supported.
+pub fn zip_payload_bounds(path: &Path) -> Result<(u64, u64), String> {
     let mut file = std::fs::File::open(path).map_err(|e| {
         format!(
             "Failed to open zip {zip} for reading: {e}",
@@ -73,7 +171,10 @@ pub fn check_is_zip(path: &Path) -> Result<(), String> {
             )
         })?
         .len();
-    let seek = min(EOCD_MAX_SIZE, file_size as usize);
+    let seek = min(
+        EOCD_MAX_SIZE + ZIP64_EOCD_LOCATOR_SIZE + ZIP64_EOCD_MIN_SIZE,
+        file_size as usize,
+    );
     file.seek(SeekFrom::End(-(seek as i64))).map_err(|e| {
         format!(
             "Failed to reset stream pointer for {file_size} byte file {path} to position \
@@ -89,5 +190,195 @@ pub fn check_is_zip(path: &Path) -> Result<(), String> {
             path = path.display()
         )
     })?;
-    end_of_zip(&buffer, 0).map(|_| ())
+    let eocd = read_eocd(&buffer, 0)?;
+    let buffer_start = file_size - seek as u64;
+    let (record_start, cd_size, cd_offset) = match read_zip64_eocd(&buffer, &eocd)? {
+        Some(zip64_eocd) => (zip64_eocd.start, zip64_eocd.cd_size, zip64_eocd.cd_offset),
+        None => (eocd.start, eocd.cd_size as u64, eocd.cd_offset as u64),
+    };
+    let record_start = buffer_start + record_start as u64;
+    let start = record_start
+        .checked_sub(cd_size)
+        .and_then(|cd_start| cd_start.checked_sub(cd_offset))
+        .ok_or_else(|| {
+            format!(
+                "The zip end of central directory record at byte {record_start} of {path} says \
+                the central directory has {cd_size} bytes at offset {cd_offset}, which is before \
+                the start of the file.",
+                path = path.display()
+            )
+        })?;
+    Ok((start, buffer_start + eocd.end as u64))
+}
+
+pub fn check_is_zip(path: &Path) -> Result<(), String> {
+    zip_payload_bounds(path).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use super::{
+        check_is_zip, end_of_zip, find_eocd, zip_payload_bounds, EOCD_MAX_SIZE, EOCD_MIN_SIZE,
+        EOCD_SIGNATURE, ZIP64_EOCD_LOCATOR_SIGNATURE, ZIP64_EOCD_SIGNATURE,
+    };
+
+    fn create_zip(comment: &str) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("file", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(b"contents").unwrap();
+        zip.set_comment(comment);
+        zip.finish().unwrap().into_inner()
+    }
+
+    // Rewrites a zip with no comment to carry its central directory size and offset in a zip64
+    // end of central directory record, as a zip64 writer would for a large zip.
+    fn create_zip64() -> Vec<u8> {
+        let zip = create_zip("");
+        let (data, eocd) = zip.split_at(zip.len() - EOCD_MIN_SIZE);
+        let field = |range: std::ops::Range<usize>| {
+            let mut bytes = [0; 8];
+            bytes[..range.len()].copy_from_slice(&eocd[range]);
+            u64::from_le_bytes(bytes)
+        };
+        let mut zip64 = data.to_vec();
+        let record_offset = zip64.len() as u64;
+        zip64.extend(ZIP64_EOCD_SIGNATURE);
+        zip64.extend(44_u64.to_le_bytes());
+        zip64.extend(45_u16.to_le_bytes());
+        zip64.extend(45_u16.to_le_bytes());
+        zip64.extend(0_u32.to_le_bytes());
+        zip64.extend(0_u32.to_le_bytes());
+        zip64.extend(field(8..10).to_le_bytes());
+        zip64.extend(field(10..12).to_le_bytes());
+        zip64.extend(field(12..16).to_le_bytes());
+        zip64.extend(field(16..20).to_le_bytes());
+        zip64.extend(ZIP64_EOCD_LOCATOR_SIGNATURE);
+        zip64.extend(0_u32.to_le_bytes());
+        zip64.extend(record_offset.to_le_bytes());
+        zip64.extend(1_u32.to_le_bytes());
+        zip64.extend(&eocd[..12]);
+        zip64.extend(u32::MAX.to_le_bytes());
+        zip64.extend(u32::MAX.to_le_bytes());
+        zip64.extend(&eocd[20..]);
+        zip64
+    }
+
+    // The original exhaustive reverse scan.
+    fn find_eocd_exhaustive(data: &[u8], max_scan: usize) -> Option<usize> {
+        let max_signature_position = data.len() - EOCD_MIN_SIZE + EOCD_SIGNATURE.len();
+        let scan_floor = max_signature_position.saturating_sub(max_scan);
+        (scan_floor..=(max_signature_position - EOCD_SIGNATURE.len()))
+            .rev()
+            .find(|start| data[*start..(*start + EOCD_SIGNATURE.len())] == EOCD_SIGNATURE)
+    }
+
+    #[test]
+    fn end_of_zip_plain() {
+        let zip = create_zip("");
+        assert_eq!(zip.len(), end_of_zip(&zip, 0).unwrap());
+        assert_eq!(
+            find_eocd_exhaustive(&zip, EOCD_MAX_SIZE),
+            find_eocd(&zip, EOCD_MAX_SIZE)
+        );
+    }
+
+    #[test]
+    fn end_of_zip_comment() {
+        for comment_size in [1, 3, 4, 5, 21, 22, 23, 1000, u16::MAX as usize] {
+            let zip = create_zip("c".repeat(comment_size).as_str());
+            assert_eq!(zip.len(), end_of_zip(&zip, 0).unwrap());
+            assert_eq!(
+                find_eocd_exhaustive(&zip, EOCD_MAX_SIZE),
+                find_eocd(&zip, EOCD_MAX_SIZE)
+            );
+        }
+    }
+
+    #[test]
+    fn end_of_zip_trailer() {
+        let mut zip = create_zip("comment");
+        let zip_len = zip.len();
+        zip.extend("{\"scie\": {}}\n".repeat(10_000).as_bytes());
+        let trailer_size = zip.len() - zip_len;
+
+        assert_eq!(zip_len, end_of_zip(&zip, trailer_size).unwrap());
+        assert_eq!(
+            find_eocd_exhaustive(&zip, EOCD_MAX_SIZE + trailer_size),
+            find_eocd(&zip, EOCD_MAX_SIZE + trailer_size)
+        );
+
+        // The scan is bounded by the maximum trailer size.
+        assert!(end_of_zip(&zip, 0).is_err());
+        assert_eq!(
+            find_eocd_exhaustive(&zip, EOCD_MAX_SIZE),
+            find_eocd(&zip, EOCD_MAX_SIZE)
+        );
+    }
+
+    #[test]
+    fn end_of_zip_multiple_signatures() {
+        // A signature embedded in the trailer shadows the real one, just as in the original scan.
+        let mut data = create_zip("");
+        let zip_len = data.len();
+        data.extend(EOCD_SIGNATURE);
+        data.extend([0; EOCD_MIN_SIZE]);
+        assert_eq!(Some(zip_len), find_eocd(&data, EOCD_MAX_SIZE));
+        assert_eq!(
+            find_eocd_exhaustive(&data, EOCD_MAX_SIZE),
+            find_eocd(&data, EOCD_MAX_SIZE)
+        );
+    }
+
+    #[test]
+    fn end_of_zip_too_short() {
+        assert!(end_of_zip(b"PK", 0).is_err());
+        assert!(find_eocd(&[0; EOCD_MIN_SIZE], EOCD_MAX_SIZE).is_none());
+    }
+
+    #[test]
+    fn payload_bounds() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("scie");
+        let zip = create_zip("comment");
+
+        std::fs::write(&path, &zip).unwrap();
+        assert_eq!((0, zip.len() as u64), zip_payload_bounds(&path).unwrap());
+
+        let mut data = b"#!scie-jump".to_vec();
+        data.extend(&zip);
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!((11, data.len() as u64), zip_payload_bounds(&path).unwrap());
+        check_is_zip(&path).unwrap();
+
+        std::fs::write(&path, b"#!scie-jump").unwrap();
+        assert!(zip_payload_bounds(&path).is_err());
+        assert!(check_is_zip(&path).is_err());
+    }
+
+    #[test]
+    fn payload_bounds_zip64() {
+        let zip64 = create_zip64();
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip64.as_slice())).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("file")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!("contents", contents);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("scie");
+        std::fs::write(&path, &zip64).unwrap();
+        assert_eq!((0, zip64.len() as u64), zip_payload_bounds(&path).unwrap());
+        check_is_zip(&path).unwrap();
+
+        let mut data = b"#!scie-jump".to_vec();
+        data.extend(&zip64);
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!((11, data.len() as u64), zip_payload_bounds(&path).unwrap());
+    }
 }
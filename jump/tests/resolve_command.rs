@@ -0,0 +1,68 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+// A minimal scie with a fake 11 byte scie-jump tip, a single zip file and a single line lift
+// manifest.
+fn create_scie(dir: &Path) -> (PathBuf, String) {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("hello.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"Hello World!").unwrap();
+    let archive = zip.finish().unwrap().into_inner();
+    let hash = jump::fingerprint::digest(archive.as_slice());
+
+    let mut data = b"#!scie-jump".to_vec();
+    data.extend(archive.as_slice());
+    data.extend(
+        format!(
+            r#"
+{{"scie":{{"lift":{{"name":"hello","files":[{{"name":"app.zip","key":"app","size":{size},"hash":"{hash}","type":"zip"}}],"boot":{{"commands":{{"":{{"exe":"{{app}}/hello.txt","args":["{{scie.env.GREETING}}"]}},"other":{{"exe":"{{app}}/other.txt"}}}}}}}},"jump":{{"size":11,"version":"0.9.0"}}}}}}
+"#,
+            size = archive.len()
+        )
+        .as_bytes(),
+    );
+    let scie = dir.join("hello");
+    std::fs::write(&scie, data).unwrap();
+    (scie, hash)
+}
+
+#[test]
+fn resolve_default_command() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let (scie, hash) = create_scie(tempdir.path());
+    let base = tempdir.path().join("nce");
+    let env = vec![
+        ("SCIE_BASE".to_string(), base.display().to_string()),
+        ("GREETING".to_string(), "Hi".to_string()),
+    ]
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    let exe = base.join(&hash).join("app.zip").join("hello.txt");
+    let dry_process = jump::dry_select_command(&scie, None).unwrap();
+    assert_eq!(
+        Some("hello.txt"),
+        Path::new(&dry_process.exe)
+            .file_name()
+            .and_then(|name| name.to_str())
+    );
+    assert!(!exe.exists());
+
+    let process = jump::resolve_command(&scie, None, Some(env.clone())).unwrap();
+    assert_eq!(exe, PathBuf::from(&process.exe));
+    assert_eq!(vec![std::ffi::OsString::from("Hi")], process.args);
+    assert_eq!("Hello World!", std::fs::read_to_string(&exe).unwrap());
+
+    let err = jump::resolve_command(&scie, Some("dne"), Some(env.clone())).unwrap_err();
+    assert!(err.contains("no command named \"dne\""), "{err}");
+
+    // The other command's exe is not a member of the app.zip.
+    assert!(jump::resolve_command(&scie, Some("other"), Some(env)).is_err());
+}
@@ -22,6 +22,7 @@ const PATHSEP: &str = ";";
 const PATHSEP: &str = ":";
 
 fn add_magic(path: &Path) -> ExitResult {
+    jump::check_writable(path).map_err(|e| Code::FAILURE.with_message(e))?;
     let mut binary = std::fs::OpenOptions::new()
         .append(true)
         .open(path)
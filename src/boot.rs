@@ -2,15 +2,20 @@
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
 use std::env;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
 use jump::config::Fmt;
-use jump::{Jump, Lift, ScieBoot, SelectBoot};
-use log::warn;
-use proc_exit::{Code, ExitResult};
+use jump::{fingerprint, Jump, Lift, Process, ScieBoot, SelectBoot};
+use log::{debug, warn};
+use proc_exit::{Code, Exit, ExitResult};
 
+mod completions;
+mod gc;
 mod pack;
 mod split;
+pub(crate) use completions::completions;
+pub(crate) use gc::gc;
 pub(crate) use pack::set as pack;
 pub(crate) use split::split;
 
@@ -24,12 +29,112 @@ pub(crate) fn help(message: String, exit_code: i32) -> ExitResult {
     }
 }
 
-pub(crate) fn inspect(jump: Jump, lift: Lift) -> ExitResult {
+fn env_lines(process: &Process) -> Vec<String> {
+    process
+        .env
+        .to_env_vars()
+        .into_iter()
+        .map(|(name, value)| {
+            let name = name.to_string_lossy();
+            if let Some(value) = value {
+                format!("{name}={value}", value = value.to_string_lossy())
+            } else {
+                format!("unset {name}")
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn print_env(process: Process) -> ExitResult {
+    for line in env_lines(&process) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn describe_exec(process: &Process, passthrough_args: &[OsString], cwd: &Path) -> String {
+    let mut description = format!(
+        "exe: {exe}\nargs: {args}\ncwd: {cwd}\n",
+        exe = process.exe.to_string_lossy(),
+        args = process
+            .argv(passthrough_args.iter().cloned())
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" "),
+        cwd = cwd.display()
+    );
+    for line in env_lines(process) {
+        description.push_str(&format!("env: {line}\n"));
+    }
+    description
+}
+
+/// Prints the process about to be executed and waits for the user to press enter; so they can
+/// attach a debugger or inspect the `nce` cache first.
+pub(crate) fn wait(process: &Process, argv_skip: usize) -> ExitResult {
+    let cwd = env::current_dir().map_err(|e| {
+        Code::FAILURE.with_message(format!("Failed to determine the current directory: {e}"))
+    })?;
+    let passthrough_args = env::args_os().skip(argv_skip).collect::<Vec<_>>();
+    eprint!("{}", describe_exec(process, &passthrough_args, &cwd));
+    eprintln!(
+        "Press enter to continue (pid {pid})...",
+        pid = std::process::id()
+    );
+    std::io::stdin()
+        .read_line(&mut String::new())
+        .map_err(|e| Code::FAILURE.with_message(format!("Failed to wait for enter: {e}")))?;
+    Ok(())
+}
+
+fn inspect_file(lift: Lift, name: &str) -> ExitResult {
+    let file = lift.file(name).cloned().ok_or_else(|| {
+        Code::FAILURE.with_message(format!(
+            "There is no file named {name} in the {lift_name} lift manifest.",
+            lift_name = lift.name
+        ))
+    })?;
+    let file = jump::config::File::from(file);
+    let fmt = Fmt::new().pretty(true).trailing_newline(true);
+    file.serialize(std::io::stdout(), fmt)
+        .map_err(|e| Code::FAILURE.with_message(format!("Failed to serialize file {name}: {e}")))
+}
+
+pub(crate) fn inspect(jump: Jump, lift: Lift, scie: PathBuf) -> ExitResult {
+    let mut self_hash = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--self-hash" {
+            self_hash = true;
+        } else if arg == "--file" {
+            let name = args.next().ok_or_else(|| {
+                Code::FAILURE.with_message("The --file option requires a file name or key.")
+            })?;
+            return inspect_file(lift, name.as_str());
+        } else if arg == "--diff" {
+            let other_scie = args.next().ok_or_else(|| {
+                Code::FAILURE.with_message("The --diff option requires a path to another scie.")
+            })?;
+            let diff = jump::diff_scie(Path::new(&other_scie), &lift)
+                .map_err(|e| Code::FAILURE.with_message(e))?;
+            print!("{diff}");
+            return Ok(());
+        }
+    }
     let config = jump::config(jump, lift);
     let fmt = Fmt::new().pretty(true).trailing_newline(true);
-    config
-        .serialize(std::io::stdout(), fmt)
-        .map_err(|e| Code::FAILURE.with_message(format!("Failed to serialize lift manifest: {e}")))
+    config.serialize(std::io::stdout(), fmt).map_err(|e| {
+        Code::FAILURE.with_message(format!("Failed to serialize lift manifest: {e}"))
+    })?;
+    if self_hash {
+        // N.B.: We emit the hash as a trailing comment line; so consumers can easily strip it to
+        // recover the lift manifest JSON.
+        let (_, hash) =
+            jump::fingerprint::digest_file(&scie).map_err(|e| Code::FAILURE.with_message(e))?;
+        println!("# sha256: {hash}");
+    }
+    Ok(())
 }
 
 pub(crate) fn select(select_boot: SelectBoot) -> ExitResult {
@@ -38,7 +143,7 @@ pub(crate) fn select(select_boot: SelectBoot) -> ExitResult {
     } else {
         "This Scie binary has no default boot command.\n"
     };
-    Err(Code::FAILURE.with_message(format!(
+    Err(Code::new(select_boot.exit_code).with_message(format!(
         "{description}\n\
             Please select from the following boot commands:\n\
             \n\
@@ -105,15 +210,213 @@ fn symlink_file(src: &Path, dst: &Path) -> ExitResult {
     })
 }
 
+#[cfg(target_os = "linux")]
+nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let src_file = std::fs::File::open(src)?;
+    let dst_file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)?;
+    let result = unsafe {
+        ficlone(
+            dst_file.as_raw_fd(),
+            src_file.as_raw_fd() as nix::sys::ioctl::ioctl_param_type,
+        )
+    }
+    .map_err(std::io::Error::from)
+    .and_then(|_| dst_file.set_permissions(src_file.metadata()?.permissions()));
+    if result.is_err() {
+        drop(dst_file);
+        let _ = std::fs::remove_file(dst);
+    }
+    result
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_src = CString::new(src.as_os_str().as_bytes())?;
+    let c_dst = CString::new(dst.as_os_str().as_bytes())?;
+    if unsafe { nix::libc::clonefile(c_src.as_ptr(), c_dst.as_ptr(), 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+const REFLINK_SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "macos"));
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dst: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Reflinks are not supported on this platform.",
+    ))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum InstallMode {
+    Hardlink,
+    Symlink,
+    Copy,
+}
+
+impl InstallMode {
+    fn from_env() -> Result<Self, Exit> {
+        match env::var("SCIE_INSTALL_MODE") {
+            Ok(mode) => match mode.as_str() {
+                "hardlink" => Ok(InstallMode::Hardlink),
+                "symlink" => Ok(InstallMode::Symlink),
+                "copy" => Ok(InstallMode::Copy),
+                _ => Err(Code::FAILURE.with_message(format!(
+                    "The SCIE_INSTALL_MODE must be one of hardlink, symlink or copy; given: {mode}"
+                ))),
+            },
+            Err(env::VarError::NotPresent) => Ok(InstallMode::Hardlink),
+            Err(e) => {
+                Err(Code::FAILURE
+                    .with_message(format!("Failed to read the SCIE_INSTALL_MODE: {e}")))
+            }
+        }
+    }
+
+    fn select(symlink: bool) -> Result<Self, Exit> {
+        if symlink {
+            Ok(InstallMode::Symlink)
+        } else {
+            InstallMode::from_env()
+        }
+    }
+
+    // The operations tried in order to install a command, each falling back to the next on failure.
+    fn operations(&self) -> &'static [&'static str] {
+        match self {
+            InstallMode::Hardlink if REFLINK_SUPPORTED => &["reflink", "hardlink", "copy"],
+            InstallMode::Hardlink => &["hardlink", "copy"],
+            InstallMode::Symlink => &["symlink"],
+            InstallMode::Copy => &["copy"],
+        }
+    }
+}
+
+fn dry_run_line(mode: InstallMode, src: &Path, dst: &Path, exists: bool) -> String {
+    let (operation, fallbacks) = mode
+        .operations()
+        .split_first()
+        .expect("Every install mode has at least one operation.");
+    format!(
+        "{operation} {src} -> {dst}{fallbacks}{exists}",
+        src = src.display(),
+        dst = dst.display(),
+        fallbacks = if fallbacks.is_empty() {
+            "".to_string()
+        } else {
+            format!(" (falling back to {})", fallbacks.join(" then "))
+        },
+        exists = if exists { " (exists)" } else { "" }
+    )
+}
+
+fn install_path(dest_dir: &Path, command: &ScieBoot) -> PathBuf {
+    dest_dir
+        .join(command.name.as_str())
+        .with_extension(env::consts::EXE_EXTENSION)
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum InstallStatus {
+    Ok,
+    Missing,
+    Stale,
+}
+
+impl InstallStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InstallStatus::Ok => "OK",
+            InstallStatus::Missing => "MISSING",
+            InstallStatus::Stale => "STALE",
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn same_file(src: &std::fs::Metadata, dst: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    src.dev() == dst.dev() && src.ino() == dst.ino()
+}
+
+#[cfg(target_family = "windows")]
+fn same_file(_src: &std::fs::Metadata, _dst: &std::fs::Metadata) -> bool {
+    // N.B.: Windows file indexes are not exposed in stable Rust; so hard links are verified by
+    // their content instead.
+    false
+}
+
+// Checks whether the installed command at `dest` is a symlink to, hard link to or copy of `scie`.
+fn install_status(scie: &Path, dest: &Path) -> Result<InstallStatus, String> {
+    if dest.symlink_metadata().is_err() {
+        return Ok(InstallStatus::Missing);
+    }
+    let canonicalize = |path: &Path| {
+        path.canonicalize().map_err(|e| {
+            format!(
+                "Failed to resolve the path of {path}: {e}",
+                path = path.display()
+            )
+        })
+    };
+    let Ok(dest) = canonicalize(dest) else {
+        // A dangling symlink.
+        return Ok(InstallStatus::Stale);
+    };
+    let scie = canonicalize(scie)?;
+    if scie == dest {
+        return Ok(InstallStatus::Ok);
+    }
+    let metadata = |path: &Path| {
+        path.metadata().map_err(|e| {
+            format!(
+                "Failed to read the metadata of {path}: {e}",
+                path = path.display()
+            )
+        })
+    };
+    let (scie_metadata, dest_metadata) = (metadata(&scie)?, metadata(&dest)?);
+    if same_file(&scie_metadata, &dest_metadata) {
+        return Ok(InstallStatus::Ok);
+    }
+    if scie_metadata.len() != dest_metadata.len() {
+        return Ok(InstallStatus::Stale);
+    }
+    if fingerprint::digest_file(&scie)? == fingerprint::digest_file(&dest)? {
+        Ok(InstallStatus::Ok)
+    } else {
+        Ok(InstallStatus::Stale)
+    }
+}
+
 pub(crate) fn install(scie: PathBuf, commands: Vec<ScieBoot>) -> ExitResult {
     let mut symlink = false;
+    let mut dry_run = false;
+    let mut verify_only = false;
     let mut dest_dirs = vec![];
     for arg in env::args().skip(1) {
         match arg.as_str() {
             "-s" | "--symlink" => symlink = true,
+            "-n" | "--dry-run" => dry_run = true,
+            "--verify-only" => verify_only = true,
             path => dest_dirs.push(PathBuf::from(path)),
         }
     }
+    let mode = InstallMode::select(symlink)?;
+    let symlink = mode == InstallMode::Symlink;
     if dest_dirs.is_empty() {
         dest_dirs.push(env::current_dir().map_err(|e| {
             Code::FAILURE.with_message(format!(
@@ -121,6 +424,47 @@ pub(crate) fn install(scie: PathBuf, commands: Vec<ScieBoot>) -> ExitResult {
             ))
         })?);
     }
+    if verify_only {
+        let mut not_ok = 0;
+        for dest_dir in &dest_dirs {
+            for command in &commands {
+                let dest = install_path(dest_dir, command);
+                if dest != scie {
+                    let status = install_status(&scie, &dest).map_err(|e| {
+                        Code::FAILURE.with_message(format!(
+                            "Failed to verify the install of {dest}: {e}",
+                            dest = dest.display()
+                        ))
+                    })?;
+                    if status != InstallStatus::Ok {
+                        not_ok += 1;
+                    }
+                    println!(
+                        "{status} {dest}",
+                        status = status.as_str(),
+                        dest = dest.display()
+                    );
+                }
+            }
+        }
+        if not_ok > 0 {
+            return Err(Code::FAILURE.with_message(format!(
+                "{not_ok} of the installed commands are missing or stale."
+            )));
+        }
+        return Ok(());
+    }
+    if dry_run {
+        for dest_dir in dest_dirs {
+            for command in &commands {
+                let dest = install_path(&dest_dir, command);
+                if dest != scie {
+                    println!("{}", dry_run_line(mode, &scie, &dest, dest.exists()));
+                }
+            }
+        }
+        return Ok(());
+    }
     for dest_dir in dest_dirs {
         std::fs::create_dir_all(&dest_dir).map_err(|e| {
             Code::FAILURE.with_message(format!(
@@ -128,15 +472,26 @@ pub(crate) fn install(scie: PathBuf, commands: Vec<ScieBoot>) -> ExitResult {
                 dest_dir = dest_dir.display()
             ))
         })?;
-        let mut hardlink = true;
+        let try_reflink = mode.operations().contains(&"reflink");
+        let mut hardlink = mode == InstallMode::Hardlink;
         for command in &commands {
-            let dest = dest_dir
-                .join(command.name.as_str())
-                .with_extension(env::consts::EXE_EXTENSION);
+            let dest = install_path(&dest_dir, command);
             if dest != scie {
                 if symlink {
                     symlink_file(&scie, &dest)?;
                 } else {
+                    if try_reflink {
+                        if let Err(e) = reflink(&scie, &dest) {
+                            debug!(
+                                "Failed to reflink {src} to {dst}, falling back to hard links: \
+                                {e}",
+                                src = scie.display(),
+                                dst = dest.display()
+                            );
+                        } else {
+                            continue;
+                        }
+                    }
                     if hardlink {
                         if let Err(e) = std::fs::hard_link(&scie, &dest) {
                             hardlink = false;
@@ -164,9 +519,163 @@ pub(crate) fn install(scie: PathBuf, commands: Vec<ScieBoot>) -> ExitResult {
     Ok(())
 }
 
+fn list_line(command: &ScieBoot, with_exe: bool, with_description: bool) -> String {
+    let mut line = command.name.clone();
+    if with_exe {
+        line.push('\t');
+        line.push_str(&command.exe);
+    }
+    if with_description {
+        line.push('\t');
+        line.push_str(command.description.as_deref().unwrap_or_default());
+    }
+    line
+}
+
 pub(crate) fn list(commands: Vec<ScieBoot>) -> ExitResult {
-    for command in commands {
-        println!("{}", command.name);
+    let mut with_exe = false;
+    let mut with_description = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--with-exe" => with_exe = true,
+            "--with-description" => with_description = true,
+            _ => warn!(
+                "The list command only accepts --with-exe and --with-description; ignoring: {arg}"
+            ),
+        }
+    }
+    for command in &commands {
+        println!("{}", list_line(command, with_exe, with_description));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    use jump::{EnvVar, EnvVars, Process, ScieBoot};
+
+    use super::{
+        describe_exec, dry_run_line, install_status, list_line, InstallMode, InstallStatus,
+        REFLINK_SUPPORTED,
+    };
+
+    #[test]
+    fn describe() {
+        let process = Process {
+            env: EnvVars {
+                vars: vec![
+                    EnvVar::Replace(("JAVA_HOME".into(), "/opt/java".into())),
+                    EnvVar::Remove("CLASSPATH".into()),
+                ],
+            },
+            exe: "/opt/java/bin/java".into(),
+            args: vec!["-jar".into(), "app.jar".into()],
+            args_append: vec![],
+            stdout: None,
+            stderr: None,
+            inherit_fds: vec![],
+        };
+        let description =
+            describe_exec(&process, &[OsString::from("--verbose")], Path::new("/work"));
+        assert!(
+            description
+                .starts_with("exe: /opt/java/bin/java\nargs: -jar app.jar --verbose\ncwd: /work\n"),
+            "{description}"
+        );
+        assert!(
+            description.contains("env: JAVA_HOME=/opt/java\n"),
+            "{description}"
+        );
+        assert!(
+            description.contains("env: unset CLASSPATH\n"),
+            "{description}"
+        );
+    }
+
+    #[test]
+    fn dry_run() {
+        let (scie, dest) = (Path::new("/opt/scie"), Path::new("/usr/bin/tool"));
+        assert_eq!(
+            "copy /opt/scie -> /usr/bin/tool",
+            dry_run_line(InstallMode::Copy, scie, dest, false)
+        );
+        assert_eq!(
+            "symlink /opt/scie -> /usr/bin/tool (exists)",
+            dry_run_line(InstallMode::Symlink, scie, dest, true)
+        );
+        let expected = if REFLINK_SUPPORTED {
+            "reflink /opt/scie -> /usr/bin/tool (falling back to hardlink then copy)"
+        } else {
+            "hardlink /opt/scie -> /usr/bin/tool (falling back to copy)"
+        };
+        assert_eq!(
+            expected,
+            dry_run_line(InstallMode::Hardlink, scie, dest, false)
+        );
+    }
+
+    #[test]
+    fn verify_install() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie = tempdir.path().join("scie");
+        std::fs::write(&scie, b"scie").unwrap();
+
+        let dest = tempdir.path().join("dest");
+        assert_eq!(
+            InstallStatus::Missing,
+            install_status(&scie, &dest).unwrap()
+        );
+
+        std::fs::hard_link(&scie, &dest).unwrap();
+        assert_eq!(InstallStatus::Ok, install_status(&scie, &dest).unwrap());
+        std::fs::remove_file(&dest).unwrap();
+
+        std::fs::copy(&scie, &dest).unwrap();
+        assert_eq!(InstallStatus::Ok, install_status(&scie, &dest).unwrap());
+
+        std::fs::write(&dest, b"old scie").unwrap();
+        assert_eq!(InstallStatus::Stale, install_status(&scie, &dest).unwrap());
+        std::fs::remove_file(&dest).unwrap();
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&scie, &dest).unwrap();
+            assert_eq!(InstallStatus::Ok, install_status(&scie, &dest).unwrap());
+            std::fs::remove_file(&dest).unwrap();
+
+            std::os::unix::fs::symlink(tempdir.path().join("gone"), &dest).unwrap();
+            assert_eq!(InstallStatus::Stale, install_status(&scie, &dest).unwrap());
+        }
+    }
+
+    #[test]
+    fn list_lines() {
+        let command = ScieBoot {
+            name: "tool".to_string(),
+            description: Some("Runs the tool.".to_string()),
+            default: false,
+            exe: "{python}/bin/python".to_string(),
+        };
+        assert_eq!("tool", list_line(&command, false, false));
+        assert_eq!(
+            "tool\t{python}/bin/python",
+            list_line(&command, true, false)
+        );
+        assert_eq!("tool\tRuns the tool.", list_line(&command, false, true));
+        assert_eq!(
+            "tool\t{python}/bin/python\tRuns the tool.",
+            list_line(&command, true, true)
+        );
+        let command = ScieBoot {
+            description: None,
+            ..command
+        };
+        assert_eq!(
+            "tool\t{python}/bin/python\t",
+            list_line(&command, true, true)
+        );
+    }
+}
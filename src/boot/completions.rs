@@ -0,0 +1,114 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+use std::env;
+use std::path::PathBuf;
+
+use jump::ScieBoot;
+use proc_exit::{Code, ExitResult};
+
+fn function_name(program: &str) -> String {
+    let sanitized = program
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("_scie_{sanitized}")
+}
+
+fn bash(program: &str, names: &[&str]) -> String {
+    format!(
+        r#"{function}() {{
+    if [ "${{COMP_CWORD}}" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{names}" -- "${{COMP_WORDS[1]}}"))
+    fi
+}}
+complete -F {function} {program}
+"#,
+        function = function_name(program),
+        names = names.join(" ")
+    )
+}
+
+fn zsh(program: &str, names: &[&str]) -> String {
+    format!(
+        r#"#compdef {program}
+{function}() {{
+    if (( CURRENT == 2 )); then
+        compadd -- {names}
+    fi
+}}
+compdef {function} {program}
+"#,
+        function = function_name(program),
+        names = names.join(" ")
+    )
+}
+
+fn fish(program: &str, names: &[&str]) -> String {
+    format!(
+        r#"complete -c {program} -f -n "test (count (commandline -opc)) -eq 1" -a "{names}"
+"#,
+        names = names.join(" ")
+    )
+}
+
+fn generate(shell: &str, program: &str, names: &[&str]) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash(program, names)),
+        "zsh" => Ok(zsh(program, names)),
+        "fish" => Ok(fish(program, names)),
+        _ => Err(format!(
+            "Completions are supported for bash, zsh and fish; given: {shell}"
+        )),
+    }
+}
+
+pub(crate) fn completions(scie: PathBuf, commands: Vec<ScieBoot>) -> ExitResult {
+    let shell = env::args().nth(1).ok_or_else(|| {
+        Code::FAILURE.with_message(
+            "You must specify the shell to generate completions for: one of bash, zsh or fish.",
+        )
+    })?;
+
+    #[cfg(windows)]
+    let program = scie.file_stem();
+
+    #[cfg(unix)]
+    let program = scie.file_name();
+
+    let program = program.and_then(|name| name.to_str()).ok_or_else(|| {
+        Code::FAILURE.with_message(format!(
+            "Failed to determine the program name of {scie}.",
+            scie = scie.display()
+        ))
+    })?;
+    let names = commands
+        .iter()
+        .filter(|command| !command.default)
+        .map(|command| command.name.as_str())
+        .collect::<Vec<_>>();
+    let script = generate(shell.as_str(), program, names.as_slice())
+        .map_err(|e| Code::FAILURE.with_message(e))?;
+    print!("{script}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+
+    #[test]
+    fn bash() {
+        let script = generate("bash", "my-scie", &["foo", "bar", "baz"]).unwrap();
+        assert!(script.contains("compgen -W \"foo bar baz\""));
+        for name in ["foo", "bar", "baz"] {
+            assert!(script.contains(name));
+        }
+        assert!(script.contains("complete -F _scie_my_scie my-scie"));
+    }
+
+    #[test]
+    fn unknown_shell() {
+        assert!(generate("csh", "my-scie", &["foo"]).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2022 Science project contributors.
+// Licensed under the Apache License, Version 2.0 (see LICENSE).
+
+use std::env;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use proc_exit::{Code, ExitResult};
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (count, unit) = value.split_at(split);
+    let count = count.parse::<u64>().map_err(|e| {
+        format!("Failed to parse the duration {value:?}; it must start with a whole number: {e}")
+    })?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "The duration {value:?} must end in one of s, m, h, d or w; given: {unit:?}"
+            ))
+        }
+    };
+    count
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("The duration {value:?} is too long."))
+}
+
+// N.B.: Symlinks are not followed; so a cache entry linking elsewhere is judged by the link alone.
+fn newest_mtime(path: &Path) -> std::io::Result<SystemTime> {
+    let metadata = path.symlink_metadata()?;
+    let mut newest = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            newest = newest.max(newest_mtime(&entry?.path())?);
+        }
+    }
+    Ok(newest)
+}
+
+fn expired(dir: &Path, cutoff: SystemTime) -> Result<bool, String> {
+    let newest = newest_mtime(dir).map_err(|e| {
+        format!(
+            "Failed to determine the most recent modification time in {dir}: {e}",
+            dir = dir.display()
+        )
+    })?;
+    Ok(newest < cutoff)
+}
+
+// The install locks `atomic_path` takes for the dir and everything under it.
+fn lock_files(path: &Path, lock_files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.symlink_metadata()?.is_dir() {
+            lock_files(&path, lock_files)?;
+        } else if path.extension().map(|ext| ext == "lck").unwrap_or(false) {
+            lock_files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Removes `dir` unless a scie holds one of its install locks; i.e.: unless a scie is populating
+/// it right now. Returns `false` if the dir was left in place.
+fn remove_unlocked(dir: &Path) -> Result<bool, String> {
+    let mut paths = vec![];
+    let lock_file = dir.with_extension("lck");
+    if lock_file.is_file() {
+        paths.push(lock_file);
+    }
+    lock_files(dir, &mut paths).map_err(|e| {
+        format!(
+            "Failed to find the lock files in {dir}: {e}",
+            dir = dir.display()
+        )
+    })?;
+    let mut locks = paths
+        .iter()
+        .map(|path| {
+            OpenOptions::new()
+                .write(true)
+                .open(path)
+                .map(fd_lock::RwLock::new)
+                .map_err(|e| {
+                    format!(
+                        "Failed to open the lock file {path}: {e}",
+                        path = path.display()
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut guards = Vec::with_capacity(locks.len());
+    for lock in locks.iter_mut() {
+        match lock.try_write() {
+            Ok(guard) => guards.push(guard),
+            Err(_) => return Ok(false),
+        }
+    }
+    std::fs::remove_dir_all(dir)
+        .map_err(|e| format!("Failed to remove {dir}: {e}", dir = dir.display()))?;
+    Ok(true)
+}
+
+// N.B.: Only the access times of files are consulted since listing a directory, as gc itself
+// does, can update the access time of the directory.
+fn last_accessed(path: &Path) -> std::io::Result<Option<SystemTime>> {
+    let mut newest = None;
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        let metadata = path.symlink_metadata()?;
+        let accessed = if metadata.is_dir() {
+            last_accessed(&path)?
+        } else {
+            Some(metadata.accessed()?)
+        };
+        newest = newest.max(accessed);
+    }
+    Ok(newest)
+}
+
+/// Selects the dirs of the nce cache `base` that are older than the `cutoff` or else that fall
+/// outside the `keep` most recently accessed.
+fn garbage(
+    base: &Path,
+    cutoff: Option<SystemTime>,
+    keep: Option<usize>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut dirs = vec![];
+    let entries = std::fs::read_dir(base).map_err(|e| {
+        format!(
+            "Failed to read the nce cache base {base}: {e}",
+            base = base.display()
+        )
+    })?;
+    for entry in entries {
+        let entry = entry.map_err(|e| {
+            format!(
+                "Failed to read an entry of the nce cache base {base}: {e}",
+                base = base.display()
+            )
+        })?;
+        let dir = entry.path();
+        if dir.is_dir() {
+            dirs.push(dir);
+        }
+    }
+
+    let mut garbage = vec![];
+    if let Some(keep) = keep {
+        let mut accessed = dirs
+            .into_iter()
+            .map(|dir| {
+                last_accessed(&dir)
+                    .map(|accessed| (accessed, dir.clone()))
+                    .map_err(|e| {
+                        format!(
+                            "Failed to determine the most recent access time in {dir}: {e}",
+                            dir = dir.display()
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        accessed.sort_by(|(a, _), (b, _)| b.cmp(a));
+        let evicted = accessed.split_off(keep.min(accessed.len()));
+        garbage.extend(evicted.into_iter().map(|(_, dir)| dir));
+        dirs = accessed.into_iter().map(|(_, dir)| dir).collect();
+    }
+    if let Some(cutoff) = cutoff {
+        for dir in dirs {
+            if expired(&dir, cutoff)? {
+                garbage.push(dir);
+            }
+        }
+    }
+    Ok(garbage)
+}
+
+pub(crate) fn gc(base: PathBuf) -> ExitResult {
+    let mut older_than = None;
+    let mut keep = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--older-than" => {
+                let value = args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message("The --older-than option requires a duration.")
+                })?;
+                older_than =
+                    Some(parse_duration(&value).map_err(|e| Code::FAILURE.with_message(e))?);
+            }
+            "--base-gc-keep" => {
+                let value = args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message("The --base-gc-keep option requires a count.")
+                })?;
+                keep = Some(value.parse::<usize>().map_err(|e| {
+                    Code::FAILURE.with_message(format!(
+                        "Failed to parse the --base-gc-keep count {value:?}: {e}"
+                    ))
+                })?);
+            }
+            _ => {
+                return Err(Code::FAILURE.with_message(format!(
+                    "The gc command only accepts --older-than [duration] and --base-gc-keep \
+                    [count]; given: {arg}"
+                )))
+            }
+        }
+    }
+    if older_than.is_none() && keep.is_none() {
+        return Err(Code::FAILURE.with_message(
+            "The gc command requires a cutoff; e.g.: --older-than 7d or --base-gc-keep 10",
+        ));
+    }
+    let cutoff = older_than
+        .map(|older_than| {
+            SystemTime::now().checked_sub(older_than).ok_or_else(|| {
+                Code::FAILURE.with_message(format!(
+                    "The --older-than duration of {older_than:?} reaches back too far."
+                ))
+            })
+        })
+        .transpose()?;
+    if !base.is_dir() {
+        return Ok(());
+    }
+    for dir in garbage(&base, cutoff, keep).map_err(|e| Code::FAILURE.with_message(e))? {
+        if remove_unlocked(&dir).map_err(|e| Code::FAILURE.with_message(e))? {
+            println!("Removed {dir}", dir = dir.display());
+        } else {
+            println!(
+                "Skipped {dir} since a scie is using it.",
+                dir = dir.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use filetime::FileTime;
+
+    use super::{expired, garbage, parse_duration, remove_unlocked};
+
+    #[test]
+    fn durations() {
+        assert_eq!(Duration::from_secs(45), parse_duration("45s").unwrap());
+        assert_eq!(Duration::from_secs(30 * 60), parse_duration("30m").unwrap());
+        assert_eq!(
+            Duration::from_secs(12 * 3600),
+            parse_duration("12h").unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(7 * 86400),
+            parse_duration("7d").unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(2 * 604800),
+            parse_duration("2w").unwrap()
+        );
+        assert_eq!(Duration::ZERO, parse_duration("0d").unwrap());
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("1d12h").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration(&format!("{}w", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn expiry() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path().join("hash");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested").join("file"), b"contents").unwrap();
+
+        let hour = Duration::from_secs(3600);
+        let now = SystemTime::now();
+        assert!(expired(&dir, now + hour).unwrap());
+        assert!(!expired(&dir, now - hour).unwrap());
+    }
+
+    #[test]
+    fn locked() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path().join("hash");
+        std::fs::create_dir_all(&dir).unwrap();
+        let lock_file = dir.join("app.lck");
+        std::fs::write(&lock_file, b"").unwrap();
+
+        let mut lock = fd_lock::RwLock::new(std::fs::File::create(&lock_file).unwrap());
+        let guard = lock.write().unwrap();
+        assert!(!remove_unlocked(&dir).unwrap());
+        assert!(dir.is_dir());
+
+        drop(guard);
+        assert!(remove_unlocked(&dir).unwrap());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn keep() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let base = tempdir.path();
+        let now = SystemTime::now();
+        for (index, name) in ["oldest", "older", "newer", "newest"].iter().enumerate() {
+            let dir = base.join(name);
+            std::fs::create_dir_all(dir.join("bin")).unwrap();
+            let exe = dir.join("bin").join("exe");
+            std::fs::write(&exe, name).unwrap();
+            let accessed = now - Duration::from_secs(3600 * (4 - index as u64));
+            filetime::set_file_atime(&exe, FileTime::from_system_time(accessed)).unwrap();
+        }
+        std::fs::write(base.join("newest.lck"), b"").unwrap();
+
+        let mut evicted = garbage(base, None, Some(2)).unwrap();
+        evicted.sort();
+        assert_eq!(vec![base.join("older"), base.join("oldest")], evicted);
+        for dir in evicted {
+            assert!(remove_unlocked(&dir).unwrap());
+        }
+        let mut survivors = std::fs::read_dir(base)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        survivors.sort();
+        assert_eq!(vec![base.join("newer"), base.join("newest")], survivors);
+
+        assert_eq!(2, garbage(base, None, Some(0)).unwrap().len());
+        assert!(
+            garbage(base, Some(now - Duration::from_secs(3600)), Some(5))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -2,39 +2,123 @@
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
 use std::env;
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 use std::path::{Path, PathBuf};
 
-use jump::config::{ArchiveType, FileType, Fmt};
-use jump::{check_is_zip, create_options, fingerprint, load_lift, File, Jump, Lift, Source};
+use jump::config::{ArchiveType, Compression, Config, FileType, Fmt};
+use jump::fingerprint::FingerprintCache;
+use jump::{
+    check_compatible, create_options, fingerprint, load_lift, load_lift_data, source_date_epoch,
+    zip_payload_bounds, File, Jump, Lift, LoadOptions, Source, COMPRESSED_LIFT_MAGIC,
+    GZIP_COMPRESSED_LIFT_MAGIC,
+};
+use log::{info, warn};
 use logging_timer::time;
 use proc_exit::{Code, ExitResult};
 use zip::{CompressionMethod, ZipWriter};
 
+// The manifest argument that says to read the lift manifest from stdin.
+const STDIN: &str = "-";
+
+fn read_manifest<R: Read>(
+    mut manifest: R,
+    resolve_base: &Path,
+    options: LoadOptions,
+) -> Result<(Option<Jump>, Lift), String> {
+    let mut data = vec![];
+    manifest
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read the lift manifest from stdin: {e}"))?;
+    validate_manifest(&data, "read from stdin")?;
+    load_lift_data(&data, resolve_base, options)
+}
+
+// N.B.: Packing only catches structural problems in the manifest; so we check it for semantic
+// problems up front lest they only surface when the scie is run.
+fn validate_manifest(data: &[u8], manifest: &str) -> Result<(), String> {
+    Config::parse(data)?.validate().map_err(|errors| {
+        format!(
+            "The lift manifest {manifest} is invalid:\n{errors}",
+            errors = errors
+                .iter()
+                .map(|error| format!("+ {error}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    })
+}
+
+/// Loads the lift manifest at `path` and returns it along with the directory its files are
+/// resolved against.
+///
+/// A `path` of `-` reads the lift manifest from stdin and resolves its files against the given
+/// `stdin_resolve_base` since there is no lift manifest directory to resolve them against.
 #[time("debug", "pack::{}")]
-fn load_manifest(path: &Path, jump: &Jump) -> Result<(Lift, PathBuf), String> {
-    let manifest_path = if path.is_dir() {
-        path.join("lift.json")
+fn load_manifest(
+    path: &Path,
+    jump: &Jump,
+    options: LoadOptions,
+    stdin_resolve_base: &Path,
+) -> Result<(Lift, PathBuf, PathBuf), String> {
+    let (manifest_path, resolve_base, (maybe_jump, lift)) = if Path::new(STDIN) == path {
+        (
+            path.to_path_buf(),
+            stdin_resolve_base.to_path_buf(),
+            read_manifest(std::io::stdin(), stdin_resolve_base, options)?,
+        )
     } else {
-        path.to_path_buf()
+        let manifest_path = if path.is_dir() {
+            path.join("lift.json")
+        } else {
+            path.to_path_buf()
+        };
+        if !manifest_path.is_file() {
+            return Err(format!(
+                "The given path does not contain a lift manifest: {path}",
+                path = path.display()
+            ));
+        }
+        let resolve_base = manifest_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .to_path_buf();
+        let data = std::fs::read(&manifest_path).map_err(|e| {
+            format!(
+                "Failed to read the lift manifest at {manifest}: {e}",
+                manifest = manifest_path.display()
+            )
+        })?;
+        validate_manifest(
+            &data,
+            format!("at {manifest}", manifest = manifest_path.display()).as_str(),
+        )?;
+        let lift = load_lift(&manifest_path, options)?;
+        (manifest_path, resolve_base, lift)
     };
-    if !manifest_path.is_file() {
-        return Err(format!(
-            "The given path does not contain a lift manifest: {path}",
-            path = path.display()
-        ));
-    }
-    let (maybe_jump, lift) = load_lift(&manifest_path)?;
     if let Some(ref configured_jump) = maybe_jump {
-        if jump != configured_jump {
-            return Err(format!(
-                "The lift manifest {manifest} specifies a scie jump binary of \
-                    {configured_jump:?} that does not match the current of {jump:?}.",
+        if let Some(warning) = check_compatible(jump, configured_jump).map_err(|e| {
+            format!(
+                "The lift manifest {manifest} is not compatible with the current scie-jump: {e}",
                 manifest = manifest_path.display()
-            ));
+            )
+        })? {
+            warn!(
+                "{warning} Proceeding to pack {manifest} anyhow.",
+                manifest = manifest_path.display()
+            );
         }
     }
-    Ok((lift, manifest_path))
+    Ok((lift, manifest_path, resolve_base))
+}
+
+fn check_jump_version(jump: &Jump, required_version: &str) -> Result<(), String> {
+    if jump.version != required_version {
+        return Err(format!(
+            "The scie-jump tip has version {version} but version {required_version} is required.",
+            version = jump.version
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(target_family = "windows")]
@@ -77,10 +161,15 @@ fn finalize_executable(path: &Path) -> Result<PathBuf, String> {
 struct ScieTote {
     zip_file: std::fs::File,
     zip_writer: ZipWriter<std::fs::File>,
+    compression_method: CompressionMethod,
+    source_date_epoch: Option<u64>,
 }
 
 impl ScieTote {
-    fn new() -> Result<Self, String> {
+    fn new(
+        compression_method: CompressionMethod,
+        source_date_epoch: Option<u64>,
+    ) -> Result<Self, String> {
         let zip_file = tempfile::tempfile().map_err(|e| {
             format!("Failed to create a temporary file to built the scie-tote with: {e}")
         })?;
@@ -92,21 +181,297 @@ impl ScieTote {
         Ok(Self {
             zip_file,
             zip_writer,
+            compression_method,
+            source_date_epoch,
         })
     }
+
+    fn add(&mut self, name: &str, path: &Path, blob: &mut std::fs::File) -> Result<(), String> {
+        let metadata = blob.metadata().map_err(|e| {
+            format!(
+                "Failed to read metadata for {path}: {e}",
+                path = path.display()
+            )
+        })?;
+        let options = create_options(&metadata, self.source_date_epoch)?
+            .compression_method(self.compression_method);
+        self.zip_writer.start_file(name, options).map_err(|e| {
+            format!(
+                "Failed to start a scie-tote file entry for {path}: {e}",
+                path = path.display()
+            )
+        })?;
+        std::io::copy(blob, &mut self.zip_writer).map_err(|e| {
+            format!(
+                "Failed to add {path} to the scie-tote: {e}",
+                path = path.display()
+            )
+        })?;
+        Ok(())
+    }
 }
 
+fn parse_tote_compression(value: &str) -> Result<CompressionMethod, String> {
+    match value {
+        "stored" => Ok(CompressionMethod::Stored),
+        "deflate" => Ok(CompressionMethod::Deflated),
+        _ => Err(format!(
+            "The --tote-compression must be one of stored or deflate; given: {value}"
+        )),
+    }
+}
+
+fn parse_manifest_compression(value: &str) -> Result<Compression, String> {
+    match value {
+        "zstd" => Ok(Compression::Zstd),
+        "gzip" => Ok(Compression::Gzip),
+        _ => Err(format!(
+            "The --manifest-compression must be one of zstd or gzip; given: {value}"
+        )),
+    }
+}
+
+fn parse_dir_archive_type(value: &str) -> Result<ArchiveType, String> {
+    match value {
+        "zip" => Ok(ArchiveType::Zip),
+        "tar" => Ok(ArchiveType::Tar),
+        _ => Err(format!(
+            "The --store-dirs-as must be one of zip or tar; given: {value}"
+        )),
+    }
+}
+
+// N.B.: Directory files are stored as an archive created alongside the directory.
+fn file_path(resolve_base: &Path, file: &File) -> PathBuf {
+    let path = resolve_base.join(&file.name);
+    match file.file_type {
+        FileType::Directory => path.with_extension("zip"),
+        FileType::Archive(ArchiveType::Tar) if path.is_dir() => path.with_extension("tar"),
+        _ => path,
+    }
+}
+
+// N.B.: The lift manifest of a scie is found where the zip ending its payload ends; so a last file
+// with bytes trailing its zip does not end in a zip as far as the scie is concerned.
+fn check_ends_in_zip(path: &Path) -> Result<(), String> {
+    let (_, end) = zip_payload_bounds(path)?;
+    let size = path
+        .metadata()
+        .map_err(|e| {
+            format!(
+                "Failed to determine the size of {path}: {e}",
+                path = path.display()
+            )
+        })?
+        .len();
+    if end != size {
+        return Err(format!(
+            "The zip in {path} ends at byte {end} but is followed by {trailing} more bytes.",
+            path = path.display(),
+            trailing = size - end
+        ));
+    }
+    Ok(())
+}
+
+fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>, String> {
+    compress_to(compression, data, vec![])
+}
+
+/// Compresses all of `reader` into `writer` with `compression` and returns the `writer`.
+fn compress_to<R: Read, W: Write>(
+    compression: Compression,
+    mut reader: R,
+    writer: W,
+) -> Result<W, String> {
+    match compression {
+        Compression::Xz => {
+            let mut encoder = xz2::write::XzEncoder::new(writer, 9);
+            std::io::copy(&mut reader, &mut encoder)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("Failed to xz compress: {e}"))
+        }
+        Compression::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(writer, 19)
+                .map_err(|e| format!("Failed to zstd compress: {e}"))?;
+            std::io::copy(&mut reader, &mut encoder)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("Failed to zstd compress: {e}"))
+        }
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::best());
+            std::io::copy(&mut reader, &mut encoder)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("Failed to gzip compress: {e}"))
+        }
+        _ => Err(format!(
+            "Auto-compression does not support {compression:?} compression."
+        )),
+    }
+}
+
+/// Compresses the file at `path` with each of the `candidates` into a temporary file and returns
+/// the smallest result if any is smaller than the file itself.
+fn smallest_compression(
+    path: &Path,
+    candidates: &[Compression],
+) -> Result<Option<(Compression, std::fs::File)>, String> {
+    let mut smallest: Option<(Compression, u64, std::fs::File)> = None;
+    let size = path
+        .metadata()
+        .map_err(|e| {
+            format!(
+                "Failed to determine the size of {path}: {e}",
+                path = path.display()
+            )
+        })?
+        .len();
+    for compression in candidates {
+        let data = std::fs::File::open(path).map_err(|e| {
+            format!(
+                "Failed to open {path} to compress it: {e}",
+                path = path.display()
+            )
+        })?;
+        let compressed_file = tempfile::tempfile().map_err(|e| {
+            format!(
+                "Failed to create a temporary file to compress {path} to: {e}",
+                path = path.display()
+            )
+        })?;
+        let mut compressed_file = compress_to(*compression, data, compressed_file)?;
+        let compressed_size = compressed_file.stream_position().map_err(|e| {
+            format!(
+                "Failed to determine the {compression:?} compressed size of {path}: {e}",
+                path = path.display()
+            )
+        })?;
+        let smallest_size = smallest.as_ref().map(|(_, size, _)| *size).unwrap_or(size);
+        if compressed_size < smallest_size {
+            smallest = Some((*compression, compressed_size, compressed_file));
+        }
+    }
+    Ok(smallest.map(|(compression, _, compressed_file)| (compression, compressed_file)))
+}
+
+/// Replaces an uncompressed tar `file` with its smallest compressed form, if any, returning a
+/// handle to the compressed bytes.
+fn auto_compress(
+    file: &mut File,
+    path: &Path,
+    candidates: &[Compression],
+) -> Result<Option<std::fs::File>, String> {
+    let Some((compression, mut compressed_file)) = smallest_compression(path, candidates)
+        .map_err(|e| format!("Failed to compress {path}: {e}", path = path.display()))?
+    else {
+        return Ok(None);
+    };
+    compressed_file
+        .rewind()
+        .map_err(|e| format!("Failed to re-wind compressed {file:?} to digest it: {e}"))?;
+    let (size, hash) = fingerprint::digest_reader(&mut compressed_file)?;
+    compressed_file
+        .rewind()
+        .map_err(|e| format!("Failed to re-wind compressed {file:?}: {e}"))?;
+    file.file_type = FileType::Archive(ArchiveType::CompressedTar(compression));
+    file.size = size;
+    file.hash = hash;
+    Ok(Some(compressed_file))
+}
+
+/// Writes the lift manifest compressed followed by its size and the magic for its `compression`.
+fn write_compressed_manifest(
+    config: &Config,
+    compression: Compression,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let magic = match compression {
+        Compression::Gzip => GZIP_COMPRESSED_LIFT_MAGIC,
+        Compression::Zstd => COMPRESSED_LIFT_MAGIC,
+        _ => {
+            return Err(format!(
+                "The lift manifest cannot be {compression:?} compressed."
+            ))
+        }
+    };
+    let mut manifest = vec![];
+    config.serialize(&mut manifest, Fmt::new())?;
+    let compressed = compress(compression, &manifest)?;
+    let size = u32::try_from(compressed.len()).map_err(|e| {
+        format!("Expected the compressed lift manifest size to fit in 32 bits: {e}")
+    })?;
+    out.write_all(&compressed)
+        .and_then(|_| out.write_all(&size.to_le_bytes()))
+        .and_then(|_| out.write_all(&magic.to_le_bytes()))
+        .map_err(|e| format!("{e}"))
+}
+
+/// Determines the path to write the scie for the lift named `name` to.
+///
+/// With no `output`, scies are written to the current directory. An `output` that is a directory,
+/// or that is shared by `multiple` scies, is the directory to write scies to; otherwise it is the
+/// path of the scie itself. Any missing parent directories are created.
+fn binary_path(output: Option<&Path>, name: &str, multiple: bool) -> Result<PathBuf, String> {
+    let binary_path = match output {
+        None => env::current_dir()
+            .map(|cwd| cwd.join(name))
+            .map_err(|e| format!("Failed to determine the output directory for scies: {e}"))?,
+        Some(output) if multiple || output.is_dir() => output.join(name),
+        Some(output) => output.to_path_buf(),
+    };
+    if let Some(parent) = binary_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            format!(
+                "Failed to create the output directory {parent} for {binary}: {e}",
+                parent = parent.display(),
+                binary = binary_path.display()
+            )
+        })?;
+    }
+    Ok(binary_path)
+}
+
+#[allow(clippy::too_many_arguments)]
 #[time("debug", "pack::{}")]
 fn pack(
+    binary_path: PathBuf,
     mut lift: Lift,
-    manifest_path: &Path,
+    resolve_base: &Path,
     jump: &Jump,
     scie_jump_path: &Path,
     single_line: bool,
+    no_tote: bool,
+    tote_compression: CompressionMethod,
+    auto_compression: bool,
+    manifest_compression: Option<Compression>,
+    source_date_epoch: Option<u64>,
 ) -> Result<PathBuf, String> {
-    let binary_path = env::current_dir()
-        .map(|cwd| cwd.join(&lift.name))
-        .map_err(|e| format!("Failed to determine the output directory for scies: {e}"))?;
+    let mut scie_tote: Option<ScieTote> = None;
+    // N.B.: Only files stored in the scie make up its payload; so a scie whose files are all loaded
+    // by bindings has no payload and needs no scie-tote.
+    if let Some(last_file) = lift
+        .files
+        .iter()
+        .rev()
+        .find(|file| Source::Scie == file.source)
+    {
+        let path = file_path(resolve_base, last_file);
+        if let Err(e) = check_ends_in_zip(&path) {
+            if !no_tote {
+                scie_tote = Some(ScieTote::new(tote_compression, source_date_epoch)?)
+            } else if manifest_compression.is_none() {
+                // N.B.: An uncompressed lift manifest is found by the end of the zip that ends
+                // the payload; so a payload of raw files needs a lift manifest that records its
+                // own size instead.
+                return Err(format!(
+                    "The last file {path} is not a zip; so with --no-tote the scie could not find \
+                    its lift manifest. Also pass --compress-manifest or --manifest-compression to \
+                    store a lift manifest that records its own size: {e}",
+                    path = path.display()
+                ));
+            }
+        }
+    }
     let mut binary = std::fs::OpenOptions::new()
         .write(true)
         .create_new(true)
@@ -135,25 +500,11 @@ fn pack(
             binary = binary_path.display()
         )
     })?;
-    let resolve_base = manifest_path.parent().unwrap_or_else(|| Path::new(""));
-    let mut scie_tote: Option<ScieTote> = None;
-    if let Some(last_file) = lift.files.last() {
-        let mut path = resolve_base.join(&last_file.name);
-        if FileType::Directory == last_file.file_type {
-            path = path.with_extension("zip");
-        }
-        if check_is_zip(&path).is_err() {
-            scie_tote = Some(ScieTote::new()?)
-        }
-    }
     for file in lift.files.iter_mut() {
         if Source::Scie != file.source {
             continue;
         }
-        let mut path = resolve_base.join(&file.name);
-        if FileType::Directory == file.file_type {
-            path = path.with_extension("zip");
-        }
+        let path = file_path(resolve_base, file);
         let mut blob = std::fs::File::open(&path).map_err(|e| {
             format!(
                 "Failed to open {src} / {file:?} for writing to {binary}: {e}",
@@ -161,29 +512,15 @@ fn pack(
                 binary = binary_path.display()
             )
         })?;
+        if auto_compression && FileType::Archive(ArchiveType::Tar) == file.file_type {
+            if let Some(compressed) =
+                auto_compress(file, &path, &[Compression::Zstd, Compression::Xz])?
+            {
+                blob = compressed;
+            }
+        }
         if let Some(tote) = scie_tote.as_mut() {
-            let metadata = blob.metadata().map_err(|e| {
-                format!(
-                    "Failed to read metadata for {path}: {e}",
-                    path = path.display()
-                )
-            })?;
-            let options = create_options(&metadata)?.compression_method(CompressionMethod::Stored);
-            tote.zip_writer
-                .start_file(&file.name, options)
-                .map_err(|e| {
-                    format!(
-                        "Failed to start a scie-tote file entry for {path}: {e}",
-                        path = path.display()
-                    )
-                })?;
-            std::io::copy(&mut blob, &mut tote.zip_writer).map_err(|e| {
-                format!(
-                    "Failed to append {src} / {file:?} to {binary}: {e}",
-                    src = path.display(),
-                    binary = binary_path.display()
-                )
-            })?;
+            tote.add(&file.name, &path, &mut blob)?;
             file.size = 0;
         } else {
             std::io::copy(&mut blob, &mut binary).map_err(|e| {
@@ -214,7 +551,12 @@ fn pack(
             hash,
             file_type: FileType::Archive(ArchiveType::Zip),
             executable: None,
+            mode: None,
+            compression: None,
             eager_extract: false,
+            extract_members: vec![],
+            use_zstd_dictionary: false,
+            decompressor: None,
             source: Source::Scie,
         };
 
@@ -228,6 +570,15 @@ fn pack(
         lift.files.push(tote_file);
     }
     let config = jump::config(jump.clone(), lift);
+    if let Some(compression) = manifest_compression {
+        write_compressed_manifest(&config, compression, &mut binary).map_err(|e| {
+            format!(
+                "Failed to write the compressed lift manifest to {binary}: {e}",
+                binary = binary_path.display()
+            )
+        })?;
+        return finalize_executable(&binary_path);
+    }
     // We configure the lift manifest format to allow for easiest inspection via standard tools.
     // In the single line case in particular, this configuration allows for inspection via
     // `tail -1 scie` or `tail -1 scie | jq .` on systems with these common tools.
@@ -244,14 +595,120 @@ fn pack(
     finalize_executable(&binary_path)
 }
 
+/// Writes a JSON summary of each packed scie to `path` for consumption by release tooling.
+fn write_outputs(path: &Path, results: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let outputs = results
+        .iter()
+        .map(|(manifest, binary)| {
+            let (size, hash) = fingerprint::digest_file(binary)?;
+            Ok(serde_json::json!({
+                "path": binary,
+                "size": size,
+                "sha256": hash,
+                "manifest": manifest,
+            }))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut data = serde_json::to_vec_pretty(&outputs)
+        .map_err(|e| format!("Failed to serialize the summary of packed scies: {e}"))?;
+    data.push(b'\n');
+    std::fs::write(path, data).map_err(|e| {
+        format!(
+            "Failed to write the summary of packed scies to {path}: {e}",
+            path = path.display()
+        )
+    })
+}
+
 pub(crate) fn set(mut jump: Jump, mut scie_jump_path: PathBuf) -> ExitResult {
-    let mut lifts = vec![];
+    let mut manifests = vec![];
     let mut single_line = true;
+    let mut dev = env::var_os("SCIE_DEV")
+        .map(|value| value == "1")
+        .unwrap_or(false);
+    let mut no_tote = false;
+    let mut auto_compression = false;
+    let mut manifest_compression = None;
+    let mut verify = false;
+    let mut tote_compression = CompressionMethod::Stored;
+    let mut dir_archive_type = ArchiveType::Zip;
+    let mut use_fingerprint_cache = true;
+    let mut required_jump_version = None;
+    let mut output_hash_file = None;
+    let mut output = None;
+    let mut stdin_resolve_base = None;
     let mut args = env::args().skip(1);
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "-1" | "--single-lift-line" => single_line = true,
             "--no-single-lift-line" => single_line = false,
+            "--no-tote" => no_tote = true,
+            "--auto-compress" => auto_compression = true,
+            "--compress-manifest" => manifest_compression = Some(Compression::Zstd),
+            "--manifest-compression" => {
+                let value = args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument of either zstd or gzip."
+                    ))
+                })?;
+                manifest_compression = Some(
+                    parse_manifest_compression(&value)
+                        .map_err(|e| Code::FAILURE.with_message(e))?,
+                );
+            }
+            "--dev" => dev = true,
+            "--verify" => verify = true,
+            "--no-fingerprint-cache" => use_fingerprint_cache = false,
+            "--tote-compression" => {
+                let value = args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument of either stored or deflate."
+                    ))
+                })?;
+                tote_compression =
+                    parse_tote_compression(&value).map_err(|e| Code::FAILURE.with_message(e))?;
+            }
+            "--store-dirs-as" => {
+                let value = args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument of either zip or tar."
+                    ))
+                })?;
+                dir_archive_type =
+                    parse_dir_archive_type(&value).map_err(|e| Code::FAILURE.with_message(e))?;
+            }
+            "--require-jump-version" => {
+                required_jump_version = Some(args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument specifying the exact scie-jump \
+                        version the scie tip must have."
+                    ))
+                })?);
+            }
+            "--resolve-base" => {
+                stdin_resolve_base = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument specifying the directory to resolve \
+                        the files of a lift manifest read from stdin against."
+                    ))
+                })?));
+            }
+            "--output" => {
+                output = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument specifying the path to write the \
+                        scie to or else the directory to write scies to."
+                    ))
+                })?));
+            }
+            "--output-hash-file" => {
+                output_hash_file = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument specifying the path to write the \
+                        summary of packed scies to."
+                    ))
+                })?));
+            }
             "-sj" | "--jump" | "--scie-jump" => {
                 scie_jump_path = PathBuf::from(args.next().ok_or_else(|| {
                     Code::FAILURE.with_message(format!(
@@ -269,18 +726,70 @@ pub(crate) fn set(mut jump: Jump, mut scie_jump_path: PathBuf) -> ExitResult {
                     })?
                     .len() as usize;
             }
-            _ => {
-                let (lift, path) = load_manifest(Path::new(arg.as_str()), &jump)
-                    .map_err(|e| Code::FAILURE.with_message(e))?;
-                lifts.push((lift, path));
-            }
+            _ => manifests.push(PathBuf::from(arg)),
         }
     }
-    if lifts.is_empty() {
+    if let Some(required_version) = required_jump_version {
+        check_jump_version(&jump, &required_version).map_err(|e| Code::FAILURE.with_message(e))?;
+    }
+    if manifests.is_empty() {
         if let Ok(cwd) = env::current_dir() {
-            let (lift, path) =
-                load_manifest(&cwd, &jump).map_err(|e| Code::FAILURE.with_message(e))?;
-            lifts.push((lift, path));
+            manifests.push(cwd);
+        }
+    }
+    if dev {
+        warn!(
+            "Packing in dev mode. Files without hashes will not be verified when the scie boots; \
+            so the resulting scies must not be distributed."
+        );
+    }
+    if manifests
+        .iter()
+        .filter(|manifest| Path::new(STDIN) == manifest.as_path())
+        .count()
+        > 1
+    {
+        return Err(
+            Code::FAILURE.with_message("The lift manifest can only be read from stdin (-) once.")
+        );
+    }
+    let stdin_resolve_base = match stdin_resolve_base {
+        Some(resolve_base) => resolve_base,
+        None => env::current_dir().map_err(|e| {
+            Code::FAILURE.with_message(format!(
+                "Failed to determine the current directory to resolve lift manifest files \
+                against: {e}"
+            ))
+        })?,
+    };
+    let source_date_epoch = source_date_epoch().map_err(|e| Code::FAILURE.with_message(e))?;
+    let fingerprint_cache = if use_fingerprint_cache {
+        FingerprintCache::user()
+    } else {
+        None
+    };
+    let lifts = manifests
+        .iter()
+        .map(|manifest| {
+            load_manifest(
+                manifest,
+                &jump,
+                LoadOptions {
+                    dev,
+                    dir_archive_type,
+                    fingerprint_cache: fingerprint_cache.as_ref(),
+                    source_date_epoch,
+                },
+                &stdin_resolve_base,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Code::FAILURE.with_message(e))?;
+    // N.B.: The fingerprint cache is only consulted when loading lift manifests; so this is the
+    // time to clear out the entries for files that have since gone away.
+    if let Some(ref fingerprint_cache) = fingerprint_cache {
+        if let Err(e) = fingerprint_cache.prune() {
+            warn!("{e}");
         }
     }
 
@@ -291,14 +800,40 @@ pub(crate) fn set(mut jump: Jump, mut scie_jump_path: PathBuf) -> ExitResult {
                 named `lift.json`.",
         ));
     }
+    let multiple = lifts.len() > 1;
     let results = lifts
         .into_iter()
-        .map(|(lift, manifest)| {
-            pack(lift, &manifest, &jump, &scie_jump_path, single_line)
-                .map(|binary| (manifest, binary))
+        .map(|(lift, manifest, resolve_base)| {
+            pack(
+                binary_path(output.as_deref(), &lift.name, multiple)?,
+                lift,
+                &resolve_base,
+                &jump,
+                &scie_jump_path,
+                single_line,
+                no_tote,
+                tote_compression,
+                auto_compression,
+                manifest_compression,
+                source_date_epoch,
+            )
+            .and_then(|binary| {
+                if verify {
+                    jump::verify_scie(&binary).map_err(|e| {
+                        format!(
+                            "The scie packed from {manifest} failed verification: {e}",
+                            manifest = manifest.display()
+                        )
+                    })?;
+                }
+                Ok((manifest, binary))
+            })
         })
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| Code::FAILURE.with_message(e))?;
+    if let Some(output_hash_file) = output_hash_file {
+        write_outputs(&output_hash_file, &results).map_err(|e| Code::FAILURE.with_message(e))?;
+    }
     for (manifest, binary) in results {
         println!(
             "{manifest}: {binary}",
@@ -308,3 +843,423 @@ pub(crate) fn set(mut jump: Jump, mut scie_jump_path: PathBuf) -> ExitResult {
     }
     Code::SUCCESS.ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, Write};
+
+    use jump::config::{ArchiveType, Compression};
+    use jump::{Jump, LoadOptions};
+    use zip::{CompressionMethod, ZipArchive};
+
+    use super::{
+        binary_path, check_ends_in_zip, check_jump_version, compress, pack, parse_dir_archive_type,
+        parse_manifest_compression, parse_tote_compression, read_manifest, smallest_compression,
+        write_compressed_manifest, write_outputs, ScieTote,
+    };
+
+    #[test]
+    fn require_jump_version() {
+        let jump = Jump {
+            size: 1137,
+            version: "1.2.3".to_string(),
+        };
+        assert!(check_jump_version(&jump, "1.2.3").is_ok());
+        let err = check_jump_version(&jump, "1.2.4").unwrap_err();
+        assert!(err.contains("version 1.2.4 is required"), "{err}");
+    }
+
+    #[test]
+    fn store_dirs_as() {
+        assert_eq!(ArchiveType::Zip, parse_dir_archive_type("zip").unwrap());
+        assert_eq!(ArchiveType::Tar, parse_dir_archive_type("tar").unwrap());
+        assert!(parse_dir_archive_type("tar.gz").is_err());
+    }
+
+    #[test]
+    fn manifest_compression() {
+        assert_eq!(
+            Compression::Zstd,
+            parse_manifest_compression("zstd").unwrap()
+        );
+        assert_eq!(
+            Compression::Gzip,
+            parse_manifest_compression("gzip").unwrap()
+        );
+        assert!(parse_manifest_compression("xz").is_err());
+
+        let (_, lift) = read_manifest(
+            br#"{"scie": {"lift": {"name": "compressed", "files": [], "boot": {"commands": {}}}}}"#
+                .as_slice(),
+            std::path::Path::new("."),
+            LoadOptions::default(),
+        )
+        .unwrap();
+        let jump = Jump {
+            size: 1137,
+            version: "1.2.3".to_string(),
+        };
+        let config = jump::config(jump, lift);
+        for (compression, magic) in [
+            (Compression::Zstd, jump::COMPRESSED_LIFT_MAGIC),
+            (Compression::Gzip, jump::GZIP_COMPRESSED_LIFT_MAGIC),
+        ] {
+            let mut out = vec![];
+            write_compressed_manifest(&config, compression, &mut out).unwrap();
+            assert_eq!(magic.to_le_bytes().as_slice(), &out[out.len() - 4..]);
+        }
+        assert!(write_compressed_manifest(&config, Compression::Xz, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn ends_in_zip() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("app.zip");
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        zip.start_file("file", Default::default()).unwrap();
+        zip.write_all(b"contents").unwrap();
+        let mut data = zip.finish().unwrap().into_inner();
+        std::fs::write(&path, &data).unwrap();
+        check_ends_in_zip(&path).unwrap();
+
+        data.extend(b"trailing");
+        std::fs::write(&path, &data).unwrap();
+        let err = check_ends_in_zip(&path).unwrap_err();
+        assert!(err.contains("followed by 8 more bytes"), "{err}");
+    }
+
+    #[test]
+    fn no_tote() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("a.txt"), "a".repeat(100)).unwrap();
+        std::fs::write(tempdir.path().join("b.txt"), "b".repeat(10)).unwrap();
+        let (_, lift) = read_manifest(
+            br#"{"scie": {"lift": {"name": "raw", "files": [{"name": "a.txt"}, {"name": "b.txt"}], "boot": {"commands": {"": {"exe": "{a.txt}"}}}}}}"#
+                .as_slice(),
+            tempdir.path(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+        let scie_jump = tempdir.path().join("scie-jump");
+        std::fs::write(&scie_jump, b"#!scie-jump").unwrap();
+        let jump = Jump {
+            size: 11,
+            version: "0.9.0".to_string(),
+        };
+
+        let err = pack(
+            tempdir.path().join("raw"),
+            lift.clone(),
+            tempdir.path(),
+            &jump,
+            &scie_jump,
+            true,
+            true,
+            CompressionMethod::Stored,
+            false,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.contains("--compress-manifest"), "{err}");
+        assert!(!tempdir.path().join("raw").exists());
+
+        let scie = pack(
+            tempdir.path().join("raw"),
+            lift,
+            tempdir.path(),
+            &jump,
+            &scie_jump,
+            true,
+            true,
+            CompressionMethod::Stored,
+            false,
+            Some(Compression::Zstd),
+            None,
+        )
+        .unwrap();
+        jump::verify_scie(&scie).unwrap();
+
+        let data = std::fs::read(&scie).unwrap();
+        assert_eq!(
+            ["a".repeat(100), "b".repeat(10)].concat().as_bytes(),
+            &data[11..121]
+        );
+        let size = u32::from_le_bytes(data[data.len() - 8..data.len() - 4].try_into().unwrap());
+        let trailer = &data[data.len() - 8 - size as usize..data.len() - 8];
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&zstd::stream::decode_all(trailer).unwrap()).unwrap();
+        let files = manifest["scie"]["lift"]["files"].as_array().unwrap();
+        assert_eq!(
+            vec![("a.txt", 100), ("b.txt", 10)],
+            files
+                .iter()
+                .map(|file| (
+                    file["name"].as_str().unwrap(),
+                    file["size"].as_u64().unwrap()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(121 + trailer.len() + 8, data.len());
+    }
+
+    #[test]
+    fn reproducible() {
+        let pack_in = |dir: &std::path::Path| {
+            std::fs::create_dir_all(dir.join("dir").join("sub")).unwrap();
+            std::fs::write(dir.join("dir").join("sub").join("file"), "file").unwrap();
+            std::fs::write(dir.join("blob.txt"), "blob").unwrap();
+            let (_, lift) = read_manifest(
+                br#"{"scie": {"lift": {"name": "reproducible", "files": [{"name": "dir"}, {"name": "blob.txt"}], "boot": {"commands": {"": {"exe": "{blob.txt}"}}}}}}"#
+                    .as_slice(),
+                dir,
+                LoadOptions {
+                    dir_archive_type: ArchiveType::Tar,
+                    source_date_epoch: Some(1_000_000_000),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let scie_jump = dir.join("scie-jump");
+            std::fs::write(&scie_jump, b"#!scie-jump").unwrap();
+            let jump = Jump {
+                size: 11,
+                version: "0.9.0".to_string(),
+            };
+            let scie = pack(
+                dir.join("reproducible"),
+                lift,
+                dir,
+                &jump,
+                &scie_jump,
+                true,
+                false,
+                CompressionMethod::Deflated,
+                false,
+                None,
+                Some(1_000_000_000),
+            )
+            .unwrap();
+            std::fs::read(scie).unwrap()
+        };
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let scie = pack_in(first.path());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(scie, pack_in(second.path()));
+    }
+
+    #[test]
+    fn deflate_tote() {
+        assert_eq!(
+            CompressionMethod::Stored,
+            parse_tote_compression("stored").unwrap()
+        );
+        assert!(parse_tote_compression("bzip2").is_err());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let contents = [
+            ("a.txt", "a".repeat(1000)),
+            ("b.txt", "Hello, tote!\n".repeat(100)),
+        ];
+        let mut tote = ScieTote::new(parse_tote_compression("deflate").unwrap(), None).unwrap();
+        for (name, content) in &contents {
+            let path = tempdir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let mut blob = std::fs::File::open(&path).unwrap();
+            tote.add(name, &path, &mut blob).unwrap();
+        }
+        tote.zip_writer.finish().unwrap();
+        tote.zip_file.rewind().unwrap();
+
+        let mut zip = ZipArchive::new(tote.zip_file).unwrap();
+        for (name, content) in &contents {
+            let mut entry = zip.by_name(name).unwrap();
+            assert_eq!(CompressionMethod::Deflated, entry.compression());
+            assert!(entry.compressed_size() < entry.size());
+            let mut data = String::new();
+            entry.read_to_string(&mut data).unwrap();
+            assert_eq!(content, &data);
+        }
+    }
+
+    #[test]
+    fn reproducible_tote() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tote = || {
+            let mut tote = ScieTote::new(CompressionMethod::Deflated, Some(1_000_000_000)).unwrap();
+            for name in ["a.txt", "b.txt"] {
+                let path = tempdir.path().join(name);
+                std::fs::write(&path, name).unwrap();
+                let mut blob = std::fs::File::open(&path).unwrap();
+                tote.add(name, &path, &mut blob).unwrap();
+            }
+            tote.zip_writer.finish().unwrap();
+            tote.zip_file.rewind().unwrap();
+            let mut data = vec![];
+            tote.zip_file.read_to_end(&mut data).unwrap();
+            data
+        };
+        let data = tote();
+        assert_eq!(data, tote());
+
+        let mut zip = ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let entry = zip.by_name("a.txt").unwrap();
+        assert_eq!(2001, entry.last_modified().year());
+    }
+
+    #[test]
+    fn outputs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let results = ["app", "tool"]
+            .iter()
+            .map(|name| {
+                let binary = tempdir.path().join(name);
+                std::fs::write(&binary, format!("{name} scie")).unwrap();
+                (tempdir.path().join(format!("{name}.json")), binary)
+            })
+            .collect::<Vec<_>>();
+        let outputs_file = tempdir.path().join("outputs.json");
+        write_outputs(&outputs_file, &results).unwrap();
+
+        let outputs: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&outputs_file).unwrap()).unwrap();
+        let outputs = outputs.as_array().unwrap();
+        assert_eq!(2, outputs.len());
+        for (output, (manifest, binary)) in outputs.iter().zip(results.iter()) {
+            let contents = std::fs::read(binary).unwrap();
+            assert_eq!(binary.to_str().unwrap(), output["path"]);
+            assert_eq!(contents.len(), output["size"].as_u64().unwrap() as usize);
+            assert_eq!(
+                jump::fingerprint::digest(&contents).as_str(),
+                output["sha256"]
+            );
+            assert_eq!(manifest.to_str().unwrap(), output["manifest"]);
+        }
+    }
+
+    #[test]
+    fn auto_compress() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("data");
+        let data = "The quick brown fox jumped over the lazy dog.\n".repeat(1000);
+        std::fs::write(&path, &data).unwrap();
+        let (compression, mut compressed_file) =
+            smallest_compression(&path, &[Compression::Zstd, Compression::Xz])
+                .unwrap()
+                .unwrap();
+        compressed_file.rewind().unwrap();
+        let mut compressed = vec![];
+        compressed_file.read_to_end(&mut compressed).unwrap();
+        for candidate in [Compression::Zstd, Compression::Xz] {
+            assert!(compressed.len() <= compress(candidate, data.as_bytes()).unwrap().len());
+        }
+        assert_eq!(compressed, compress(compression, data.as_bytes()).unwrap());
+
+        std::fs::write(&path, "a").unwrap();
+        assert!(
+            smallest_compression(&path, &[Compression::Zstd, Compression::Xz])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn stdin_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("app.txt"), "Hello from stdin!").unwrap();
+        let manifest = r#"
+        {
+            "scie": {
+                "lift": {
+                    "name": "app",
+                    "files": [{"name": "app.txt"}],
+                    "boot": {"commands": {"": {"exe": "cat", "args": ["{app.txt}"]}}}
+                }
+            }
+        }
+        "#;
+        let (_, lift) =
+            read_manifest(manifest.as_bytes(), tempdir.path(), LoadOptions::default()).unwrap();
+        assert_eq!("app", lift.name);
+        assert_eq!(1, lift.files.len());
+        assert_eq!(17, lift.files[0].size);
+        assert_eq!(
+            jump::fingerprint::digest(b"Hello from stdin!"),
+            lift.files[0].hash
+        );
+
+        assert!(read_manifest(
+            manifest.as_bytes(),
+            &tempdir.path().join("dne"),
+            LoadOptions::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("app.txt"), "Hello from stdin!").unwrap();
+        let manifest = r#"
+        {
+            "scie": {
+                "lift": {
+                    "name": "app",
+                    "files": [{"name": "app.txt"}],
+                    "boot": {
+                        "commands": {"": {"exe": "cat", "args": ["{app.txt}", "{README}"]}},
+                        "bindings": {"setup": {"exe": "{scie.bindings.install}/setup"}}
+                    }
+                }
+            }
+        }
+        "#;
+        let err =
+            read_manifest(manifest.as_bytes(), tempdir.path(), LoadOptions::default()).unwrap_err();
+        assert!(
+            err.starts_with("The lift manifest read from stdin is invalid:\n"),
+            "{err}"
+        );
+        assert!(
+            err.contains("\n+ The default command refers to README"),
+            "{err}"
+        );
+        assert!(
+            err.contains("\n+ The binding setup refers to the binding install"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn output() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            std::env::current_dir().unwrap().join("app"),
+            binary_path(None, "app", false).unwrap()
+        );
+
+        let scie = tempdir.path().join("dist").join("app-linux");
+        assert_eq!(scie, binary_path(Some(&scie), "app", false).unwrap());
+        assert!(tempdir.path().join("dist").is_dir());
+
+        let dist = tempdir.path().join("dist");
+        assert_eq!(
+            dist.join("app"),
+            binary_path(Some(&dist), "app", false).unwrap()
+        );
+
+        let variants = tempdir.path().join("variants");
+        assert_eq!(
+            variants.join("app"),
+            binary_path(Some(&variants), "app", true).unwrap()
+        );
+        assert_eq!(
+            variants.join("tool"),
+            binary_path(Some(&variants), "tool", true).unwrap()
+        );
+        assert!(variants.is_dir());
+    }
+}
@@ -3,17 +3,30 @@
 
 use std::env;
 use std::fs::Permissions;
-use std::io::Read;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use jump::config::{FileType, Fmt};
 use jump::{File, Jump, Lift, Source};
-use log::debug;
+use log::{debug, warn};
 use proc_exit::{Code, Exit, ExitResult};
 use zip::ZipArchive;
 
-fn ensure_parent_dir(base: &Path, file: &File) -> Result<PathBuf, Exit> {
-    let dst = base.join(&file.name);
+// In flat mode, only the basename of the file is used; so files from different directories land
+// side by side in the base.
+fn destination(base: &Path, name: &str, flat: bool) -> PathBuf {
+    if flat {
+        if let Some(basename) = Path::new(name).file_name() {
+            return base.join(basename);
+        }
+    }
+    base.join(name)
+}
+
+fn ensure_parent_dir(base: &Path, file: &File, flat: bool) -> Result<PathBuf, Exit> {
+    let dst = destination(base, &file.name, flat);
     if let Some(parent) = dst.parent() {
         std::fs::create_dir_all(parent).map_err(|e| {
             Code::FAILURE.with_message(format!(
@@ -25,20 +38,275 @@ fn ensure_parent_dir(base: &Path, file: &File) -> Result<PathBuf, Exit> {
     Ok(dst)
 }
 
+fn create_new(dst: &Path, flat: bool) -> Result<std::fs::File, Exit> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .map_err(|e| {
+            if flat && ErrorKind::AlreadyExists == e.kind() {
+                Code::FAILURE.with_message(format!(
+                    "Failed to split to {dst} since a file with the same basename was already split \
+                    there. Omit --flat to preserve the directory structure and avoid the collision.",
+                    dst = dst.display()
+                ))
+            } else {
+                Code::FAILURE.with_message(format!(
+                    "Failed to open {dst} for extraction: {e}",
+                    dst = dst.display()
+                ))
+            }
+        })
+}
+
 #[cfg(not(target_family = "unix"))]
 fn executable_permissions() -> Option<Permissions> {
     None
 }
 
+#[cfg(not(target_family = "unix"))]
+fn mode_permissions(_mode: u32) -> Option<Permissions> {
+    None
+}
+
 #[cfg(target_family = "unix")]
 fn executable_permissions() -> Option<Permissions> {
     use std::os::unix::fs::PermissionsExt;
     Some(Permissions::from_mode(0o755))
 }
 
+#[cfg(target_family = "unix")]
+fn mode_permissions(mode: u32) -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(Permissions::from_mode(mode))
+}
+
+fn extract_flat<R: Read + Seek>(zip_archive: &mut ZipArchive<R>, base: &Path) -> ExitResult {
+    for index in 0..zip_archive.len() {
+        let mut entry = zip_archive.by_index(index).map_err(|e| {
+            Code::FAILURE.with_message(format!("Failed to read scie-tote entry {index}: {e}"))
+        })?;
+        if entry.is_dir() {
+            continue;
+        }
+        let dst = destination(base, entry.name(), true);
+        let mut out = create_new(&dst, true)?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| {
+            Code::FAILURE.with_message(format!(
+                "Failed to extract {name} to {dst}: {e}",
+                name = entry.name(),
+                dst = dst.display()
+            ))
+        })?;
+        if let Some(permissions) = entry.unix_mode().and_then(mode_permissions) {
+            out.set_permissions(permissions).map_err(|e| {
+                Code::FAILURE.with_message(format!(
+                    "Failed to set the permissions of {dst}: {e}",
+                    dst = dst.display()
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn gzip_tote<R: Read + Seek>(zip_archive: &mut ZipArchive<R>, dst: &Path) -> ExitResult {
+    let tote = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .map_err(|e| {
+            Code::FAILURE.with_message(format!(
+                "Failed to open {dst} for writing: {e}",
+                dst = dst.display()
+            ))
+        })?;
+    let mut tar = tar::Builder::new(GzEncoder::new(tote, Compression::default()));
+    for index in 0..zip_archive.len() {
+        let mut entry = zip_archive.by_index(index).map_err(|e| {
+            Code::FAILURE.with_message(format!("Failed to read scie-tote entry {index}: {e}"))
+        })?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(entry.size());
+        header.set_mode(entry.unix_mode().unwrap_or(0o644));
+        tar.append_data(&mut header, &name, &mut entry)
+            .map_err(|e| {
+                Code::FAILURE.with_message(format!(
+                    "Failed to add {name} to {dst}: {e}",
+                    dst = dst.display()
+                ))
+            })?;
+    }
+    tar.into_inner().and_then(|gz| gz.finish()).map_err(|e| {
+        Code::FAILURE.with_message(format!(
+            "Failed to finalize {dst}: {e}",
+            dst = dst.display()
+        ))
+    })?;
+    Ok(())
+}
+
+// A window onto the `len` bytes of the scie starting at `start`; so the scie-tote zip can be read
+// in place.
+struct Embedded<'a> {
+    scie: &'a std::fs::File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<'a> Read for Embedded<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let size = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        self.scie.seek(SeekFrom::Start(self.start + self.pos))?;
+        let read = self.scie.read(&mut buf[..size])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a> Seek for Embedded<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Cannot seek to before the start of the scie-tote.",
+            )
+        })?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+// The offset of the given file in the scie; i.e.: the sum of the sizes of the scie-jump and the
+// files stored before it.
+fn offset(jump_size: u64, files: &[File], file: &File) -> u64 {
+    jump_size
+        + files
+            .iter()
+            .filter(|stored| Source::Scie == stored.source)
+            .take_while(|stored| !std::ptr::eq(*stored, file))
+            .map(|stored| stored.size as u64)
+            .sum::<u64>()
+}
+
+/// Writes the bytes of the file with the given name or key to `out`.
+///
+/// Archives, directories and compressed blobs are only written when `raw` is requested since their
+/// stored bytes are not the bytes they are extracted to.
+fn stream<W: Write>(
+    jump_size: u64,
+    files: &[File],
+    scie: &std::fs::File,
+    name: &str,
+    raw: bool,
+    out: &mut W,
+) -> Result<(), String> {
+    let file = files
+        .iter()
+        .find(|file| file.name == name || file.key.as_deref() == Some(name))
+        .ok_or_else(|| format!("This scie contains no file named {name}."))?;
+    if Source::Scie != file.source {
+        return Err(format!(
+            "The file {name} is loaded by a binding when needed and is not stored in this scie."
+        ));
+    }
+    let kind = match (file.file_type, file.compression) {
+        (FileType::Blob, None) => None,
+        (FileType::Blob, Some(_)) => Some("compressed blob".to_string()),
+        (FileType::Archive(archive_type), _) => Some(format!("{} archive", archive_type.as_ext())),
+        (FileType::Directory, _) => Some("directory".to_string()),
+    };
+    if let (Some(kind), false) = (kind, raw) {
+        return Err(format!(
+            "The file {name} is a {kind}; so its stored bytes are not the bytes it is extracted \
+            to. Pass --raw to write its stored bytes anyway."
+        ));
+    }
+    let copy = |src: &mut dyn Read, out: &mut W| {
+        std::io::copy(src, out)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write {name} to stdout: {e}"))
+    };
+    if file.size > 0 {
+        let mut scie = scie;
+        scie.seek(SeekFrom::Start(offset(jump_size, files, file)))
+            .map_err(|e| format!("Failed to seek to {name} in the scie: {e}"))?;
+        return copy(&mut scie.take(file.size as u64), out);
+    }
+    let tote = files
+        .last()
+        .filter(|tote| tote.size > 0 && Source::Scie == tote.source)
+        .ok_or_else(|| format!("The file {name} is not stored in this scie's scie-tote."))?;
+    let mut zip_archive = ZipArchive::new(Embedded {
+        scie,
+        start: offset(jump_size, files, tote),
+        len: tote.size as u64,
+        pos: 0,
+    })
+    .map_err(|e| format!("Failed to open the scie-tote: {e}"))?;
+    let mut entry = zip_archive
+        .by_name(&file.name)
+        .map_err(|e| format!("Failed to find {name} in the scie-tote: {e}"))?;
+    copy(&mut entry, out)
+}
+
 pub(crate) fn split(jump: Jump, mut lift: Lift, scie_path: PathBuf) -> ExitResult {
-    let base = if let Some(base) = env::args().nth(1) {
-        PathBuf::from(base)
+    let mut gzip = false;
+    let mut flat = false;
+    let mut raw = false;
+    let mut stdout = None;
+    let mut target = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--flat" => flat = true,
+            "--gzip-tote" => gzip = true,
+            "--raw" => raw = true,
+            "--stdout" => {
+                stdout = Some(args.next().ok_or_else(|| {
+                    Code::FAILURE.with_message(format!(
+                        "The {arg} flag requires an argument naming the file to write to stdout."
+                    ))
+                })?);
+            }
+            path => target = Some(PathBuf::from(path)),
+        }
+    }
+    if let Some(name) = stdout {
+        let scie = std::fs::File::open(&scie_path).map_err(|e| {
+            Code::FAILURE.with_message(format!(
+                "Failed to open scie at {scie_path} for splitting: {e}",
+                scie_path = scie_path.display()
+            ))
+        })?;
+        return stream(
+            jump.size as u64,
+            &lift.files,
+            &scie,
+            &name,
+            raw,
+            &mut std::io::stdout().lock(),
+        )
+        .map_err(|e| Code::FAILURE.with_message(e));
+    }
+    let base = if let Some(base) = target {
+        base
     } else {
         env::current_dir().map_err(|e| {
             Code::FAILURE.with_message(format!(
@@ -116,29 +384,29 @@ pub(crate) fn split(jump: Jump, mut lift: Lift, scie_path: PathBuf) -> ExitResul
                     .with_message(format!("Failed to open {file} zip: {e}", file = file.name))
             })?;
             let dst = if file.file_type == FileType::Directory {
-                ensure_parent_dir(&base, file)?
+                ensure_parent_dir(&base, file, flat)?
             } else {
                 base.to_path_buf()
             };
             debug!("Extracting {file:?} to {dst}...", dst = dst.display());
-            zip_archive.extract(&dst).map_err(|e| {
-                Code::FAILURE.with_message(format!(
-                    "Failed to extract scie-tote to {base}: {e}",
-                    base = base.display()
-                ))
-            })?;
-        } else {
-            let dst = ensure_parent_dir(&base, file)?;
-            let mut out = std::fs::OpenOptions::new()
-                .write(true)
-                .create_new(true)
-                .open(&dst)
-                .map_err(|e| {
+            if flat && file.file_type != FileType::Directory {
+                extract_flat(&mut zip_archive, &dst)?;
+            } else {
+                zip_archive.extract(&dst).map_err(|e| {
                     Code::FAILURE.with_message(format!(
-                        "Failed to open {dst} for extraction: {e}",
-                        dst = dst.display()
+                        "Failed to extract scie-tote to {base}: {e}",
+                        base = base.display()
                     ))
                 })?;
+            }
+            if gzip && file.file_type != FileType::Directory {
+                let tote = base.join("tote.tar.gz");
+                debug!("Re-packing {file:?} to {tote}...", tote = tote.display());
+                gzip_tote(&mut zip_archive, &tote)?;
+            }
+        } else {
+            let dst = ensure_parent_dir(&base, file, flat)?;
+            let mut out = create_new(&dst, flat)?;
             let file_size = file.size as u64;
             let mut src = scie
                 .try_clone()
@@ -153,16 +421,22 @@ pub(crate) fn split(jump: Jump, mut lift: Lift, scie_path: PathBuf) -> ExitResul
         }
     }
 
+    if gzip && scie_tote.is_empty() {
+        warn!("The --gzip-tote option was specified but the scie has no scie-tote.");
+    }
+
     if !scie_tote.is_empty() {
         lift.files.remove(lift.files.len() - 1);
         for mut file in lift.files.iter_mut() {
             if file.source == Source::Scie {
-                let metadata = base.join(&file.name).metadata().map_err(|e| {
-                    Code::FAILURE.with_message(format!(
-                        "Failed to determine size of {file}: {e}",
-                        file = file.name
-                    ))
-                })?;
+                let metadata = destination(&base, &file.name, flat)
+                    .metadata()
+                    .map_err(|e| {
+                        Code::FAILURE.with_message(format!(
+                            "Failed to determine size of {file}: {e}",
+                            file = file.name
+                        ))
+                    })?;
                 file.size = metadata.len() as usize;
             }
         }
@@ -189,3 +463,132 @@ pub(crate) fn split(jump: Jump, mut lift: Lift, scie_path: PathBuf) -> ExitResul
 
     Code::SUCCESS.ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::path::Path;
+
+    use jump::config::{ArchiveType, FileType};
+    use jump::File;
+    use zip::write::FileOptions;
+    use zip::ZipArchive;
+
+    use super::{create_new, destination, gzip_tote, stream};
+
+    #[test]
+    fn flat() {
+        let base = Path::new("split");
+        assert_eq!(base.join("lib/foo"), destination(base, "lib/foo", false));
+        assert_eq!(base.join("foo"), destination(base, "lib/foo", true));
+        assert_eq!(base.join("foo"), destination(base, "foo", true));
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = destination(tempdir.path(), "bin/foo", true);
+        create_new(&dst, true).unwrap();
+        let err = create_new(&destination(tempdir.path(), "lib/foo", true), true).unwrap_err();
+        assert!(format!("{err:?}").contains("Omit --flat"), "{err:?}");
+    }
+
+    #[test]
+    fn gzip_tote_round_trip() {
+        let mut tote = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        tote.add_directory("bin", FileOptions::default()).unwrap();
+        tote.start_file("bin/tool", FileOptions::default().unix_permissions(0o755))
+            .unwrap();
+        tote.write_all(b"#!/bin/sh").unwrap();
+        tote.start_file("data.txt", FileOptions::default().unix_permissions(0o644))
+            .unwrap();
+        tote.write_all(b"Hello from the tote!").unwrap();
+        let mut tote = ZipArchive::new(tote.finish().unwrap()).unwrap();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dst = tempdir.path().join("scie-tote.tar.gz");
+        gzip_tote(&mut tote, &dst).unwrap();
+        assert!(gzip_tote(&mut tote, &dst).is_err());
+
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(
+            std::fs::File::open(&dst).unwrap(),
+        ));
+        let entries = tar
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+                (
+                    entry.path().unwrap().display().to_string(),
+                    entry.header().mode().unwrap() & 0o777,
+                    contents,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("bin/tool".to_string(), 0o755, "#!/bin/sh".to_string()),
+                (
+                    "data.txt".to_string(),
+                    0o644,
+                    "Hello from the tote!".to_string()
+                ),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn stdout() {
+        let mut tote = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        tote.start_file("member.txt", Default::default()).unwrap();
+        tote.write_all(b"Hello from the tote!").unwrap();
+        let tote = tote.finish().unwrap().into_inner();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let scie_path = tempdir.path().join("scie");
+        let mut data = b"jump".to_vec();
+        data.extend_from_slice(b"blob!");
+        data.extend_from_slice(b"archive");
+        data.extend_from_slice(&tote);
+        data.extend_from_slice(b"\n{}\n");
+        std::fs::write(&scie_path, data).unwrap();
+        let scie = std::fs::File::open(&scie_path).unwrap();
+
+        let files = vec![
+            File {
+                name: "blob".to_string(),
+                key: Some("key".to_string()),
+                size: 5,
+                ..File::default()
+            },
+            File {
+                name: "app.tar".to_string(),
+                size: 7,
+                file_type: FileType::Archive(ArchiveType::Tar),
+                ..File::default()
+            },
+            File {
+                name: "member.txt".to_string(),
+                ..File::default()
+            },
+            File {
+                name: "scie-tote".to_string(),
+                size: tote.len(),
+                file_type: FileType::Archive(ArchiveType::Zip),
+                ..File::default()
+            },
+        ];
+        let write = |name: &str, raw: bool| {
+            let mut out = vec![];
+            stream(4, &files, &scie, name, raw, &mut out).map(|_| String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!("blob!", write("blob", false).unwrap());
+        assert_eq!("blob!", write("key", false).unwrap());
+        assert_eq!("Hello from the tote!", write("member.txt", false).unwrap());
+        let err = write("app.tar", false).unwrap_err();
+        assert!(err.contains("Pass --raw"), "{err}");
+        assert_eq!("archive", write("app.tar", true).unwrap());
+        assert!(write("dne", false).is_err());
+    }
+}
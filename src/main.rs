@@ -1,41 +1,76 @@
 // Copyright 2022 Science project contributors.
 // Licensed under the Apache License, Version 2.0 (see LICENSE).
 
-use std::ffi::OsString;
-
 use proc_exit::{Code, ExitResult};
 
 mod boot;
 
-use jump::BootAction;
+use jump::{exit_code, BootAction, Process};
+
+// N.B.: Windows exit codes are 32 bit values; so we propagate the raw code as-is instead of
+// narrowing it to the 0-255 range of Unix exit codes.
+#[cfg(windows)]
+fn status_code(exit_status: std::process::ExitStatus) -> Code {
+    exit_status.code().map(Code::new).unwrap_or(Code::FAILURE)
+}
 
 #[cfg(windows)]
-fn exec(exe: OsString, args: Vec<OsString>, argv_skip: usize) -> ExitResult {
-    let result = jump::execute(exe, args, argv_skip);
+fn exec(process: Process, argv_skip: usize) -> ExitResult {
+    let result = jump::execute(process, argv_skip);
     match result {
-        Ok(exit_status) => Code::from(exit_status).ok(),
-        Err(message) => Err(Code::FAILURE.with_message(message)),
+        Ok(exit_status) => status_code(exit_status).ok(),
+        Err(message) => Err(Code::new(exit_code::EXEC_ERROR).with_message(message)),
+    }
+}
+
+#[cfg(unix)]
+fn redirect(path: Option<std::ffi::OsString>, fd: std::os::unix::io::RawFd) -> ExitResult {
+    use std::os::unix::io::AsRawFd;
+
+    use nix::unistd::dup2;
+
+    if let Some(path) = path {
+        let file = jump::open_output(&path)
+            .map_err(|e| Code::new(exit_code::EXEC_ERROR).with_message(e))?;
+        dup2(file.as_raw_fd(), fd).map_err(|e| {
+            Code::new(exit_code::EXEC_ERROR)
+                .with_message(format!("Failed to redirect fd {fd} to {path:?}: {e}"))
+        })?;
     }
+    Ok(())
 }
 
 #[cfg(unix)]
-fn exec(exe: OsString, args: Vec<OsString>, argv_skip: usize) -> ExitResult {
+fn exec(process: Process, argv_skip: usize) -> ExitResult {
     use std::ffi::CString;
     use std::os::unix::ffi::OsStringExt;
 
     use nix::unistd::execv;
 
+    process
+        .inherit_fds()
+        .map_err(|e| Code::new(exit_code::EXEC_ERROR).with_message(e))?;
+    let args = process.argv(std::env::args_os().skip(argv_skip));
+    let Process {
+        exe,
+        stdout,
+        stderr,
+        ..
+    } = process;
+    redirect(stdout, 1)?;
+    redirect(stderr, 2)?;
+
     let c_exe = CString::new(exe.into_vec()).map_err(|e| {
-        Code::FAILURE.with_message(format!("Failed to convert executable to a C string: {e}",))
+        Code::new(exit_code::EXEC_ERROR)
+            .with_message(format!("Failed to convert executable to a C string: {e}",))
     })?;
 
     let mut c_args = vec![c_exe.clone()];
     c_args.extend(
         args.into_iter()
-            .chain(std::env::args().skip(argv_skip).map(OsString::from))
             .map(|arg| {
                 CString::new(arg.into_vec()).map_err(|e| {
-                    Code::FAILURE
+                    Code::new(exit_code::EXEC_ERROR)
                         .with_message(format!("Failed to convert argument to a C string: {e}",))
                 })
             })
@@ -44,7 +79,7 @@ fn exec(exe: OsString, args: Vec<OsString>, argv_skip: usize) -> ExitResult {
 
     execv(&c_exe, &c_args)
         .map_err(|e| {
-            Code::new(e as i32).with_message(format!(
+            Code::new(exit_code::EXEC_ERROR).with_message(format!(
                 "Failed to exec {c_exe:?} with argv {c_args:?}: {e}"
             ))
         })
@@ -54,18 +89,26 @@ fn exec(exe: OsString, args: Vec<OsString>, argv_skip: usize) -> ExitResult {
 fn main() -> ExitResult {
     env_logger::init();
 
-    let action = jump::prepare_boot().map_err(|e| {
-        Code::FAILURE.with_message(format!("Failed to prepare a scie jump action: {e}"))
+    let action = jump::prepare_boot().map_err(|(e, exit_code)| {
+        Code::new(exit_code).with_message(format!("Failed to prepare a scie jump action: {e}"))
     })?;
 
     match action {
-        BootAction::Execute((process, argv1_consumed)) => {
+        BootAction::Execute((process, argv_skip)) => {
+            if std::env::var_os("SCIE_WAIT")
+                .map(|value| value == "1")
+                .unwrap_or(false)
+            {
+                boot::wait(&process, argv_skip)?;
+            }
             process.env.export();
-            let argv_skip = if argv1_consumed { 2 } else { 1 };
-            exec(process.exe, process.args, argv_skip)
+            exec(process, argv_skip)
         }
+        BootAction::Completions((scie, commands)) => boot::completions(scie, commands),
+        BootAction::Env(process) => boot::print_env(process),
+        BootAction::Gc(base) => boot::gc(base),
         BootAction::Help((message, exit_code)) => boot::help(message, exit_code),
-        BootAction::Inspect((jump, lift)) => boot::inspect(jump, lift),
+        BootAction::Inspect((jump, lift, scie)) => boot::inspect(jump, lift, scie),
         BootAction::Install((scie, commands)) => boot::install(scie, commands),
         BootAction::List(commands) => boot::list(commands),
         BootAction::Pack((jump, scie_jump_path)) => boot::pack(jump, scie_jump_path),
@@ -73,3 +116,28 @@ fn main() -> ExitResult {
         BootAction::Split((jump, lift, scie_path)) => boot::split(jump, lift, scie_path),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(windows)]
+    #[test]
+    fn large_exit_code() {
+        use jump::{EnvVars, Process};
+        use proc_exit::Code;
+
+        use super::status_code;
+
+        // N.B.: This is STATUS_ACCESS_VIOLATION (0xC0000005) as a signed 32 bit value.
+        let process = Process {
+            env: EnvVars { vars: vec![] },
+            exe: "cmd".into(),
+            args: vec!["/c".into(), "exit".into(), "-1073741819".into()],
+            args_append: vec![],
+            stdout: None,
+            stderr: None,
+            inherit_fds: vec![],
+        };
+        let exit_status = process.execute(std::iter::empty()).unwrap();
+        assert_eq!(Code::new(-1073741819), status_code(exit_status));
+    }
+}